@@ -0,0 +1,576 @@
+//! The application configuration. A `Config` can only be made through
+//! `ConfigBuilder::build`, which checks every value, whether it comes from
+//! config.json or from code.
+
+#[cfg(feature = "webhooks")]
+use crate::secrets;
+use crate::{
+    default_list_name, parse_json_file, sha256_hex, validate_date_format, AppError, Collation, DisplayTimezone,
+    Theme, UserSettings, PEPPER_ENV_VAR,
+};
+use bcrypt::DEFAULT_COST;
+use chrono::{DateTime, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// bcrypt cost used when hashing new passwords.
+    pub cost: u32,
+    /// Directory holding tasks.json and users.json.
+    pub data_dir: PathBuf,
+    /// Named data set, e.g. "work", kept in tasks_work.json, users_work.json
+    /// and so on inside `data_dir`. `None` is the default profile, which uses
+    /// the plain file names.
+    pub profile: Option<String>,
+    /// Maximum number of tasks a single user may hold.
+    pub max_tasks: usize,
+    /// Name of the list a user's first task creates.
+    pub default_list: String,
+    /// Interactive sessions end after this many idle minutes.
+    pub session_timeout_minutes: u64,
+    /// Pending tasks untouched for longer than this are reported as stale.
+    pub stale_after_days: u32,
+    /// Open tasks untouched for this many days get one, two or three dots in
+    /// listings. Must be in ascending order.
+    pub age_marker_days: [u32; 3],
+    /// Descriptions in listings are cut to this many terminal columns. 0
+    /// uses half the terminal width, and doesn't cut output that isn't going
+    /// to a terminal. Detail views and exports always show the full text.
+    pub description_width: usize,
+    /// Tasks untouched for longer than this many days are archived when an
+    /// interactive session starts. 0 turns auto-archiving off.
+    pub auto_archive_days: u32,
+    /// Tasks due within this many days count as urgent in the Eisenhower
+    /// matrix, as do overdue ones.
+    pub due_soon_days: u32,
+    /// Show and sort pending tasks due within `escalate_within_days` days,
+    /// or overdue, one priority level higher. The stored priority is kept.
+    pub escalate_priorities: bool,
+    pub escalate_within_days: u32,
+    /// Raise the stored priority of pending tasks one level, once, when they
+    /// become overdue. Checked when an interactive session starts.
+    pub persist_overdue_escalation: bool,
+    /// Story points that fit in one day; days with more due are reported as
+    /// overbooked. 0 turns the warning off.
+    pub daily_capacity: u32,
+    /// Points counted for a task without a story point estimate when
+    /// checking `daily_capacity`.
+    pub default_task_points: u32,
+    /// Length of one Pomodoro focus interval.
+    pub pomodoro_minutes: u32,
+    /// fsync every journal append. Safer, but slower on some filesystems.
+    pub journal_fsync: bool,
+    /// How many changes can be undone; older ones are forgotten. 0 turns
+    /// undo off.
+    pub undo_depth: usize,
+    /// Interactive sessions write unsaved changes to disk in the background
+    /// this often. 0 turns autosave off.
+    pub autosave_seconds: u64,
+    /// Secret mixed into every password before hashing, read from
+    /// TASKS_PASSWORD_PEPPER. Changing or removing it invalidates every
+    /// stored password hash, so users would have to re-register.
+    pepper: Option<String>,
+    /// IANA zone name ("Europe/Kyiv") or fixed offset ("+02:00") used when
+    /// displaying timestamps. Timestamps are always stored in UTC.
+    pub timezone: Option<String>,
+    /// strftime format for displayed timestamps.
+    pub date_format: String,
+    /// Task listings on a terminal pause after this many tasks. 0 shows them
+    /// all at once.
+    pub page_size: usize,
+    /// First column of the calendar view, e.g. "Mon" or "Sun".
+    pub week_start: Weekday,
+    pub theme: Theme,
+    /// Locale for sorting text, e.g. "sv" or "tr_TR". Defaults to the
+    /// language in $LANG.
+    pub locale: Option<String>,
+    /// Plain-sentence listings without decorative characters, for screen
+    /// readers. Also turned on by the --accessible flag.
+    pub accessible: bool,
+    /// Open task links in the system browser. When off (or with --no-open)
+    /// the link is printed instead.
+    pub open_links: bool,
+    /// Start interactive sessions at the `> ` command prompt instead of the
+    /// numbered menu, which stays reachable with the `menu` command.
+    pub repl: bool,
+    /// Main menu entries with a key letter run on that key press, without
+    /// Enter. Ignored when the terminal can't be put in raw mode.
+    pub single_key_menu: bool,
+    /// URLs that get a POST for every task added, completed or removed.
+    /// Needs the "webhooks" feature.
+    pub webhooks: Vec<String>,
+    /// Key for the HMAC-SHA256 signature sent in `X-Signature-256`.
+    #[cfg_attr(not(feature = "webhooks"), allow(dead_code))]
+    pub webhook_secret: Option<String>,
+    /// Calendar collection synced by `sync caldav`, e.g.
+    /// "https://cloud.example.com/remote.php/dav/calendars/bob/tasks/".
+    #[cfg_attr(not(feature = "caldav"), allow(dead_code))]
+    pub caldav_url: Option<String>,
+    /// Basic auth credentials for `caldav_url`.
+    #[cfg_attr(not(feature = "caldav"), allow(dead_code))]
+    pub caldav_username: Option<String>,
+    #[cfg_attr(not(feature = "caldav"), allow(dead_code))]
+    pub caldav_password: Option<String>,
+}
+
+/// Builds a validated `Config`. Also the shape of config.json, so values read
+/// from disk go through the same checks.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ConfigBuilder {
+    cost: u32,
+    data_dir: Option<PathBuf>,
+    profile: Option<String>,
+    max_tasks: usize,
+    default_list: String,
+    session_timeout_minutes: u64,
+    stale_after_days: u32,
+    age_marker_days: [u32; 3],
+    description_width: usize,
+    auto_archive_days: u32,
+    due_soon_days: u32,
+    escalate_priorities: bool,
+    escalate_within_days: u32,
+    persist_overdue_escalation: bool,
+    daily_capacity: u32,
+    default_task_points: u32,
+    pomodoro_minutes: u32,
+    journal_fsync: bool,
+    undo_depth: usize,
+    autosave_seconds: u64,
+    /// Never read from config.json; see `Config::pepper`.
+    #[serde(skip)]
+    pepper: Option<String>,
+    timezone: Option<String>,
+    date_format: String,
+    page_size: usize,
+    week_start: Weekday,
+    theme: Theme,
+    locale: Option<String>,
+    accessible: bool,
+    open_links: bool,
+    repl: bool,
+    single_key_menu: bool,
+    webhooks: Vec<String>,
+    webhook_secret: Option<String>,
+    caldav_url: Option<String>,
+    caldav_username: Option<String>,
+    caldav_password: Option<String>,
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        ConfigBuilder {
+            cost: DEFAULT_COST,
+            data_dir: None,
+            profile: None,
+            max_tasks: 1000,
+            default_list: default_list_name(),
+            session_timeout_minutes: 30,
+            stale_after_days: 30,
+            age_marker_days: [7, 30, 90],
+            description_width: 0,
+            auto_archive_days: 0,
+            due_soon_days: 3,
+            escalate_priorities: false,
+            escalate_within_days: 2,
+            persist_overdue_escalation: false,
+            daily_capacity: 8,
+            default_task_points: 1,
+            pomodoro_minutes: 25,
+            journal_fsync: true,
+            undo_depth: 20,
+            autosave_seconds: 60,
+            pepper: None,
+            timezone: None,
+            date_format: "%Y-%m-%d %H:%M %Z".to_string(),
+            page_size: 0,
+            week_start: Weekday::Mon,
+            theme: Theme::default(),
+            locale: None,
+            accessible: false,
+            open_links: true,
+            repl: false,
+            single_key_menu: false,
+            webhooks: Vec::new(),
+            webhook_secret: None,
+            caldav_url: None,
+            caldav_username: None,
+            caldav_password: None,
+        }
+    }
+}
+
+// The with_* setters are for constructing configs in code; config.json
+// fills the builder through serde instead.
+#[allow(dead_code)]
+impl ConfigBuilder {
+    pub fn with_cost(mut self, cost: u32) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    pub fn with_data_dir(mut self, data_dir: PathBuf) -> Self {
+        self.data_dir = Some(data_dir);
+        self
+    }
+
+    pub fn with_profile(mut self, profile: String) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    pub fn with_max_tasks(mut self, max_tasks: usize) -> Self {
+        self.max_tasks = max_tasks;
+        self
+    }
+
+    pub fn with_default_list(mut self, default_list: String) -> Self {
+        self.default_list = default_list;
+        self
+    }
+
+    pub fn with_session_timeout_minutes(mut self, minutes: u64) -> Self {
+        self.session_timeout_minutes = minutes;
+        self
+    }
+
+    pub fn with_stale_after_days(mut self, days: u32) -> Self {
+        self.stale_after_days = days;
+        self
+    }
+
+    pub fn with_age_marker_days(mut self, days: [u32; 3]) -> Self {
+        self.age_marker_days = days;
+        self
+    }
+
+    pub fn with_description_width(mut self, columns: usize) -> Self {
+        self.description_width = columns;
+        self
+    }
+
+    pub fn with_auto_archive_days(mut self, days: u32) -> Self {
+        self.auto_archive_days = days;
+        self
+    }
+
+    pub fn with_due_soon_days(mut self, days: u32) -> Self {
+        self.due_soon_days = days;
+        self
+    }
+
+    pub fn with_escalation(mut self, within_days: u32, persist_overdue: bool) -> Self {
+        self.escalate_priorities = true;
+        self.escalate_within_days = within_days;
+        self.persist_overdue_escalation = persist_overdue;
+        self
+    }
+
+    pub fn with_daily_capacity(mut self, points: u32) -> Self {
+        self.daily_capacity = points;
+        self
+    }
+
+    pub fn with_default_task_points(mut self, points: u32) -> Self {
+        self.default_task_points = points;
+        self
+    }
+
+    pub fn with_pomodoro_minutes(mut self, minutes: u32) -> Self {
+        self.pomodoro_minutes = minutes;
+        self
+    }
+
+    pub fn with_journal_fsync(mut self, fsync: bool) -> Self {
+        self.journal_fsync = fsync;
+        self
+    }
+
+    pub fn with_undo_depth(mut self, undo_depth: usize) -> Self {
+        self.undo_depth = undo_depth;
+        self
+    }
+
+    pub fn with_autosave_seconds(mut self, autosave_seconds: u64) -> Self {
+        self.autosave_seconds = autosave_seconds;
+        self
+    }
+
+    pub fn with_pepper(mut self, pepper: Option<String>) -> Self {
+        self.pepper = pepper;
+        self
+    }
+
+    pub fn with_timezone(mut self, timezone: Option<String>) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    pub fn with_date_format(mut self, date_format: String) -> Self {
+        self.date_format = date_format;
+        self
+    }
+
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    pub fn with_week_start(mut self, week_start: Weekday) -> Self {
+        self.week_start = week_start;
+        self
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    pub fn with_locale(mut self, locale: String) -> Self {
+        self.locale = Some(locale);
+        self
+    }
+
+    pub fn with_accessible(mut self, accessible: bool) -> Self {
+        self.accessible = accessible;
+        self
+    }
+
+    pub fn with_open_links(mut self, open_links: bool) -> Self {
+        self.open_links = open_links;
+        self
+    }
+
+    pub fn with_repl(mut self, repl: bool) -> Self {
+        self.repl = repl;
+        self
+    }
+
+    pub fn with_single_key_menu(mut self, single_key_menu: bool) -> Self {
+        self.single_key_menu = single_key_menu;
+        self
+    }
+
+    pub fn with_webhooks(mut self, urls: Vec<String>, secret: Option<String>) -> Self {
+        self.webhooks = urls;
+        self.webhook_secret = secret;
+        self
+    }
+
+    pub fn with_caldav(mut self, url: String, username: Option<String>, password: Option<String>) -> Self {
+        self.caldav_url = Some(url);
+        self.caldav_username = username;
+        self.caldav_password = password;
+        self
+    }
+}
+
+impl ConfigBuilder {
+    pub fn build(self) -> Result<Config, AppError> {
+        if !(4..=31).contains(&self.cost) {
+            return Err(AppError::InvalidConfig(format!("cost must be between 4 and 31, got {}", self.cost)));
+        }
+        let data_dir = match self.data_dir {
+            Some(dir) if dir.is_absolute() => dir,
+            Some(dir) => {
+                return Err(AppError::InvalidConfig(format!(
+                    "data_dir must be an absolute path, got '{}'",
+                    dir.display()
+                )))
+            }
+            None => env::current_dir()?,
+        };
+        let profile = self.profile.filter(|profile| profile != "default");
+        if let Some(profile) = &profile {
+            let valid = !profile.is_empty()
+                && profile.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                return Err(AppError::InvalidConfig(format!(
+                    "profile must be letters, digits, '-' or '_', got '{}'",
+                    profile
+                )));
+            }
+        }
+        if self.max_tasks == 0 {
+            return Err(AppError::InvalidConfig("max_tasks must be greater than 0".to_string()));
+        }
+        if self.default_list.trim().is_empty() {
+            return Err(AppError::InvalidConfig("default_list must not be empty".to_string()));
+        }
+        if self.session_timeout_minutes == 0 {
+            return Err(AppError::InvalidConfig("session_timeout_minutes must be greater than 0".to_string()));
+        }
+        if self.pomodoro_minutes == 0 {
+            return Err(AppError::InvalidConfig("pomodoro_minutes must be greater than 0".to_string()));
+        }
+        validate_date_format(&self.date_format)?;
+        if !self.age_marker_days.is_sorted() {
+            return Err(AppError::InvalidConfig("age_marker_days must be in ascending order".to_string()));
+        }
+
+        Ok(Config {
+            cost: self.cost,
+            data_dir,
+            profile,
+            max_tasks: self.max_tasks,
+            default_list: self.default_list,
+            session_timeout_minutes: self.session_timeout_minutes,
+            stale_after_days: self.stale_after_days,
+            age_marker_days: self.age_marker_days,
+            description_width: self.description_width,
+            auto_archive_days: self.auto_archive_days,
+            due_soon_days: self.due_soon_days,
+            escalate_priorities: self.escalate_priorities,
+            escalate_within_days: self.escalate_within_days,
+            persist_overdue_escalation: self.persist_overdue_escalation,
+            daily_capacity: self.daily_capacity,
+            default_task_points: self.default_task_points,
+            pomodoro_minutes: self.pomodoro_minutes,
+            journal_fsync: self.journal_fsync,
+            undo_depth: self.undo_depth,
+            autosave_seconds: self.autosave_seconds,
+            pepper: self.pepper,
+            timezone: self.timezone,
+            date_format: self.date_format,
+            page_size: self.page_size,
+            week_start: self.week_start,
+            theme: self.theme,
+            locale: self.locale,
+            accessible: self.accessible,
+            open_links: self.open_links,
+            repl: self.repl,
+            single_key_menu: self.single_key_menu,
+            webhooks: self.webhooks,
+            webhook_secret: self.webhook_secret,
+            caldav_url: self.caldav_url,
+            caldav_username: self.caldav_username,
+            caldav_password: self.caldav_password,
+        })
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::builder().build().expect("default configuration is valid")
+    }
+}
+
+impl Config {
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    /// Reads config.json; `profile`, from the --profile flag, overrides the
+    /// one set there.
+    pub fn load(profile: Option<&str>) -> Result<Self, AppError> {
+        let path = Path::new("config.json");
+        let mut builder = if path.exists() {
+            parse_json_file(path, &fs::read(path)?)?
+        } else {
+            Config::builder()
+        };
+        if let Some(profile) = profile {
+            builder = builder.with_profile(profile.to_string());
+        }
+        let pepper = env::var(PEPPER_ENV_VAR).ok().filter(|pepper| !pepper.is_empty());
+        let config = builder.with_pepper(pepper).build()?;
+        if let Some(timezone) = &config.timezone {
+            if DisplayTimezone::parse(timezone).is_none() {
+                println!("Warning: unknown timezone '{}', showing times in UTC", timezone);
+            }
+        }
+        if cfg!(not(feature = "webhooks")) && !config.webhooks.is_empty() {
+            println!("Warning: webhooks are configured but this build doesn't support them");
+        }
+
+        Ok(config)
+    }
+
+    /// `webhook_secret` from config.json, or else the one in the secret store.
+    #[cfg(feature = "webhooks")]
+    pub fn webhook_secret(&self) -> Result<Option<String>, AppError> {
+        match &self.webhook_secret {
+            Some(secret) => Ok(Some(secret.clone())),
+            None => secrets::get(&self.data_dir, secrets::WEBHOOK_SECRET),
+        }
+    }
+
+    /// `<stem>.<extension>` in the data directory, or
+    /// `<stem>_<profile>.<extension>` under a named profile.
+    pub fn data_file(&self, stem: &str, extension: &str) -> PathBuf {
+        match &self.profile {
+            Some(profile) => self.data_dir.join(format!("{}_{}.{}", stem, profile, extension)),
+            None => self.data_dir.join(format!("{}.{}", stem, extension)),
+        }
+    }
+
+    pub fn collation(&self) -> Collation {
+        match &self.locale {
+            Some(locale) => Collation::for_locale(locale),
+            None => Collation::for_locale(&env::var("LANG").unwrap_or_default()),
+        }
+    }
+
+    /// The string actually handed to bcrypt for `password`. With a pepper it
+    /// is the hex SHA-256 of pepper and password, which also keeps it under
+    /// bcrypt's 72-byte input limit.
+    pub fn peppered(&self, password: &str) -> String {
+        match &self.pepper {
+            Some(pepper) => sha256_hex(format!("{}\0{}", pepper, password).as_bytes()),
+            None => password.to_string(),
+        }
+    }
+
+    pub fn display_timezone(&self) -> DisplayTimezone {
+        self.timezone.as_deref().and_then(DisplayTimezone::parse).unwrap_or(DisplayTimezone::Named(Tz::UTC))
+    }
+
+    pub fn format_timestamp(&self, timestamp: DateTime<Utc>) -> String {
+        self.display_timezone().format(timestamp, &self.date_format)
+    }
+
+    /// This config with `settings` laid over it: each setting the user has
+    /// set wins, the rest keep the value from config.json or the default.
+    pub fn with_user_settings(&self, settings: &UserSettings) -> Config {
+        let mut config = self.clone();
+        if let Some(default_list) = &settings.default_list {
+            config.default_list = default_list.clone();
+        }
+        if let Some(date_format) = &settings.date_format {
+            config.date_format = date_format.clone();
+        }
+        if let Some(days) = settings.due_soon_days {
+            config.due_soon_days = days;
+        }
+        match settings.color {
+            Some(false) => config.theme = Theme::Mono,
+            Some(true) if config.theme == Theme::Mono => config.theme = Theme::default(),
+            _ => {}
+        }
+        if let Some(page_size) = settings.page_size {
+            config.page_size = page_size;
+        }
+        if let Some(timezone) = &settings.timezone {
+            config.timezone = Some(timezone.clone());
+        }
+        config
+    }
+
+    /// The value of one of `UserSettings::KEYS` as this config has it.
+    pub fn setting_value(&self, key: &str) -> String {
+        match key {
+            "default_list" => self.default_list.clone(),
+            "date_format" => self.date_format.clone(),
+            "due_soon_days" => self.due_soon_days.to_string(),
+            "color" => if self.theme == Theme::Mono { "off" } else { "on" }.to_string(),
+            "page_size" => self.page_size.to_string(),
+            "timezone" => self.timezone.clone().unwrap_or_else(|| "UTC".to_string()),
+            _ => String::new(),
+        }
+    }
+}
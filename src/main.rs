@@ -1,253 +1,12114 @@
-use bcrypt::{hash, verify, DEFAULT_COST};
+mod config;
+mod secrets;
+
+use bcrypt::{hash, verify};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, Days, FixedOffset, Months, NaiveDate, NaiveTime, TimeDelta, Utc, Weekday};
+use chrono_tz::Tz;
+use comfy_table::Table;
+use config::Config;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use notify::{RecursiveMode, Watcher};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use serde_json;
-use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io;
-use std::path::Path;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::env;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use uuid::Uuid;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+// Shortest UUID prefix accepted in place of a numeric task ID.
+const MIN_UUID_PREFIX_LEN: usize = 4;
+// Environment variable holding the optional application-wide password pepper.
+const PEPPER_ENV_VAR: &str = "TASKS_PASSWORD_PEPPER";
+// How long tasks.json must stay quiet before watch mode reloads it.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+// Per-request timeout for webhook deliveries.
+#[cfg(feature = "webhooks")]
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+// Delay before the first webhook retry; doubled after every failed attempt.
+#[cfg(feature = "webhooks")]
+const WEBHOOK_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+// Jaro-Winkler score at which an existing task counts as a near-duplicate.
+const SIMILARITY_THRESHOLD: f64 = 0.9;
+// Per-request timeout for CalDAV sync.
+#[cfg(feature = "caldav")]
+const CALDAV_TIMEOUT: Duration = Duration::from_secs(30);
+// Delay before the first save retry; doubled after every failed attempt.
+const SAVE_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 struct Task {
     id: u32,
+    #[serde(default = "Uuid::new_v4")]
+    uuid: Uuid,
     description: String,
     completed: bool,
+    #[serde(default = "Utc::now")]
+    created_at: DateTime<Utc>,
+    #[serde(default)]
+    completed_at: Option<DateTime<Utc>>,
+    /// Set when work on the task starts; the task is in progress until completed.
+    #[serde(default)]
+    started_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    due_date: Option<NaiveDate>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    priority: Priority,
+    #[serde(default = "Utc::now")]
+    last_modified_at: DateTime<Utc>,
+    /// Related web page or ticket; always an http(s) URL.
+    #[serde(default)]
+    link: Option<String>,
+    /// IDs of tasks that must be completed before this one.
+    #[serde(default)]
+    depends_on: Vec<u32>,
+    #[serde(default)]
+    estimated_story_points: Option<u32>,
+    /// Name of the sprint the task is planned for, e.g. "2024-W12".
+    #[serde(default)]
+    sprint: Option<String>,
+    /// Project milestone the task contributes to, e.g. "v1.0".
+    #[serde(default)]
+    milestone: Option<String>,
+    /// 256-color palette index the task is shown in; overrides the status color.
+    #[serde(default)]
+    color_tag: Option<u8>,
+    /// Percentage done, 0-100. Completing a task sets it to 100.
+    #[serde(default)]
+    progress: u8,
+    /// Locked tasks can't be edited, completed or removed until unlocked.
+    #[serde(default)]
+    locked: bool,
+    /// GTD-style context where the task can be done, e.g. "@office".
+    #[serde(default)]
+    context: Option<String>,
+    /// When the stored priority was raised because the task became overdue;
+    /// see `Config::persist_overdue_escalation`. Cleared when the due date
+    /// changes.
+    #[serde(default)]
+    escalated_at: Option<DateTime<Utc>>,
+    /// Step of the owner's custom workflow, e.g. "Review". Only trusted while
+    /// it agrees with `completed`; see `workflow_position`.
+    #[serde(default)]
+    workflow_status: Option<String>,
+    /// Bumped each time `AppData::edit_task` replaces the description, so an
+    /// edit based on an older description can be refused instead of silently
+    /// overwriting a newer one. Other changes leave it as is.
+    #[serde(default)]
+    version: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct User {
-    username: String,
-    password: String,
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+enum Priority {
+    Low,
+    #[default]
+    Medium,
+    High,
+    Critical,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct TaskList {
-    username: String,
-    tasks: Vec<Task>,
+impl Priority {
+    const ALL: [Priority; 4] = [Priority::Critical, Priority::High, Priority::Medium, Priority::Low];
+
+    fn label(self) -> &'static str {
+        match self {
+            Priority::Low => "Low",
+            Priority::Medium => "Medium",
+            Priority::High => "High",
+            Priority::Critical => "Critical",
+        }
+    }
+
+    /// One level up, but never to Critical: that is only set by hand.
+    fn raised(self) -> Self {
+        match self {
+            Priority::Low => Priority::Medium,
+            Priority::Medium | Priority::High => Priority::High,
+            Priority::Critical => Priority::Critical,
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        Priority::ALL
+            .into_iter()
+            .find(|priority| priority.label().eq_ignore_ascii_case(value.trim()))
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct AppData {
-    task_lists: Vec<TaskList>,
-    users: HashMap<String, User>,
+/// The basic 8 ANSI terminal colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TermColor {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
 }
 
-impl AppData {
-    fn new() -> Self {
-        AppData {
-            task_lists: Vec::new(),
-            users: HashMap::new(),
+impl TermColor {
+    const ALL: [TermColor; 8] = [
+        TermColor::Black,
+        TermColor::Red,
+        TermColor::Green,
+        TermColor::Yellow,
+        TermColor::Blue,
+        TermColor::Magenta,
+        TermColor::Cyan,
+        TermColor::White,
+    ];
+
+    fn parse(value: &str) -> Option<Self> {
+        TermColor::ALL.into_iter().find(|color| format!("{:?}", color).eq_ignore_ascii_case(value.trim()))
+    }
+
+    fn ansi_code(self) -> String {
+        format!("\x1b[{}m", 30 + self as u8)
+    }
+}
+
+/// Escape sequence selecting `color` from the 256-color palette, or an empty
+/// string for no color.
+fn ansi_256_color(color: Option<u8>) -> String {
+    match color {
+        Some(color) => format!("\x1b[38;5;{}m", color),
+        None => String::new(),
+    }
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+
+/// Color scheme of the task list, chosen with "theme" in config.json.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+enum Theme {
+    #[default]
+    Default,
+    /// Bold, bright colors.
+    HighContrast,
+    /// No colors at all, not even per-task color tags.
+    Mono,
+}
+
+impl Theme {
+    /// Escape sequence that starts the color of a task in `status`, or an
+    /// empty string for none.
+    fn status_color(self, status: Status) -> String {
+        match self {
+            Theme::Default => status.color().ansi_code(),
+            Theme::HighContrast => format!("\x1b[1;{}m", 90 + status.color() as u8),
+            Theme::Mono => String::new(),
         }
     }
 
-    fn add_task(&mut self, username: &str, description: String) {
-        let task_list = self.task_lists.iter_mut().find(|list| list.username == username);
-        match task_list {
-            Some(list) => {
-                let id = list.tasks.len() as u32 + 1;
-                let task = Task {
-                    id,
-                    description,
-                    completed: false,
-                };
-                list.tasks.push(task);
-            },
-            None => {
-                let task_list = TaskList {
-                    username: username.to_string(),
-                    tasks: vec![Task {
-                        id: 1,
-                        description,
-                        completed: false,
-                    }],
-                };
-                self.task_lists.push(task_list);
+    /// Escape sequence that highlights the priority of a task, or an empty
+    /// string to leave it in the status color.
+    fn priority_color(self, priority: Priority) -> String {
+        match (self, priority) {
+            (Theme::Default, Priority::High) => TermColor::Red.ansi_code(),
+            (Theme::Default, Priority::Critical) => format!("\x1b[1;{}m", 30 + TermColor::Red as u8),
+            (Theme::HighContrast, Priority::High | Priority::Critical) => {
+                format!("\x1b[1;{}m", 90 + TermColor::Red as u8)
             }
+            (Theme::HighContrast, Priority::Low) => format!("\x1b[1;{}m", 90 + TermColor::White as u8),
+            _ => String::new(),
         }
     }
+}
 
-    fn remove_task(&mut self, username: &str, task_id: u32) {
-        if let Some(list) = self.task_lists.iter_mut().find(|list| list.username == username) {
-            list.tasks.retain(|task| task.id != task_id);
+/// Alphabetical order for user-visible text. Not full Unicode collation:
+/// letters are compared case- and accent-insensitively (NFKD with combining
+/// marks dropped), with tailorings for the few locales whose alphabets
+/// order differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Collation {
+    /// German, English and most other languages: "Äpfel" sorts with "Apfel".
+    Root,
+    /// Swedish and Finnish: å, ä and ö are letters of their own after z.
+    Swedish,
+    /// Turkish and Azerbaijani: dotless ı comes before i, and ç, ğ, ö, ş, ü
+    /// each follow their base letter.
+    Turkish,
+}
+
+impl Collation {
+    /// From a locale such as "sv", "tr_TR" or "de-DE.UTF-8"; anything
+    /// without a tailoring gets `Root`.
+    fn for_locale(locale: &str) -> Self {
+        let language = locale.split(['_', '-', '.']).next().unwrap_or_default().to_ascii_lowercase();
+        match language.as_str() {
+            "sv" | "fi" => Collation::Swedish,
+            "tr" | "az" => Collation::Turkish,
+            _ => Collation::Root,
         }
     }
 
-    fn edit_task(&mut self, username: &str, task_id: u32, new_description: String) {
-        if let Some(list) = self.task_lists.iter_mut().find(|list| list.username == username) {
-            if let Some(task) = list.tasks.iter_mut().find(|task| task.id == task_id) {
-                task.description = new_description;
+    /// Primary weight of a letter this collation places specially, if any.
+    /// Ordinary characters weigh four times their code point, so a tailored
+    /// letter can slot in right after its neighbour.
+    fn tailored_weight(self, c: char) -> Option<u32> {
+        let after = |base: char, offset: u32| Some(base as u32 * 4 + offset);
+        match (self, c) {
+            (Collation::Swedish, 'å') => after('z', 1),
+            (Collation::Swedish, 'ä' | 'æ') => after('z', 2),
+            (Collation::Swedish, 'ö' | 'ø') => after('z', 3),
+            (Collation::Swedish, 'ü') => after('y', 0),
+            (Collation::Turkish, 'ı') => after('h', 1),
+            (Collation::Turkish, 'ç') => after('c', 1),
+            (Collation::Turkish, 'ğ') => after('g', 1),
+            (Collation::Turkish, 'ö') => after('o', 1),
+            (Collation::Turkish, 'ş') => after('s', 1),
+            (Collation::Turkish, 'ü') => after('u', 1),
+            _ => None,
+        }
+    }
+
+    /// Lowercases `text`, with the Turkish dotted/dotless i rules.
+    fn fold_case(self, text: &str) -> String {
+        let text: String = text.nfc().collect();
+        match self {
+            Collation::Turkish => text
+                .chars()
+                .map(|c| match c {
+                    'I' => "ı".to_string(),
+                    'İ' => "i".to_string(),
+                    c => c.to_lowercase().collect(),
+                })
+                .collect(),
+            _ => text.to_lowercase(),
+        }
+    }
+
+    /// Primary weights of `text`: case and accents ignored except where the
+    /// collation treats an accented letter as a letter of its own.
+    fn primary_weights(self, text: &str) -> Vec<u32> {
+        let mut weights = Vec::with_capacity(text.len());
+        for c in self.fold_case(text).chars() {
+            if let Some(weight) = self.tailored_weight(c) {
+                weights.push(weight);
+                continue;
+            }
+            for c in c.nfkd().filter(|c| !is_combining_mark(*c)) {
+                match c {
+                    'ß' => weights.extend([u32::from('s') * 4; 2]),
+                    c => weights.push(c as u32 * 4),
+                }
             }
         }
+        weights
     }
 
-    fn mark_completed(&mut self, username: &str, task_id: u32) {
-        if let Some(list) = self.task_lists.iter_mut().find(|list| list.username == username) {
-            if let Some(task) = list.tasks.iter_mut().find(|task| task.id == task_id) {
-                task.completed = true;
+    /// Text folded to primary strength, e.g. "Äpfel" becomes "apfel" in
+    /// `Root`. For fuzzy comparisons that shouldn't care about case or
+    /// accents.
+    fn fold(self, text: &str) -> String {
+        let mut folded = String::with_capacity(text.len());
+        for c in self.fold_case(text).chars() {
+            match self.tailored_weight(c) {
+                Some(_) => folded.push(c),
+                None => folded.extend(c.nfkd().filter(|c| !is_combining_mark(*c))),
             }
         }
+        folded
     }
 
-    fn save(&self) -> io::Result<()> {
-        let task_file = OpenOptions::new().create(true).write(true).open("tasks.json")?;
-        serde_json::to_writer(task_file, &self.task_lists)?;
+    /// Byte string whose order is this collation's order of `text`. Ties at
+    /// the letter level are broken by accents, then by case, so distinct
+    /// strings never compare equal.
+    fn collate_key(self, text: &str) -> Vec<u8> {
+        // Every weight is offset by one so that the level separator, 0,
+        // sorts before any character and shorter strings come first.
+        let mut key = Vec::with_capacity(text.len() * 12 + 8);
+        let mut push_level = |weights: &mut dyn Iterator<Item = u32>| {
+            for weight in weights {
+                key.extend_from_slice(&(weight + 1).to_be_bytes());
+            }
+            key.extend_from_slice(&0u32.to_be_bytes());
+        };
+        push_level(&mut self.primary_weights(text).into_iter());
+        push_level(&mut self.fold_case(text).chars().map(u32::from));
+        push_level(&mut text.nfc().map(u32::from));
+        key
+    }
+}
 
-        let user_file = OpenOptions::new().create(true).write(true).open("users.json")?;
-        let users: Vec<User> = self.users.values().cloned().collect();
-        serde_json::to_writer(user_file, &users)?;
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pending,
+    InProgress,
+    Completed,
+}
 
-        Ok(())
-    }
+impl Status {
+    const ALL: [Status; 3] = [Status::Pending, Status::InProgress, Status::Completed];
 
-    fn load() -> io::Result<Self> {
-        let mut app_data = AppData::new();
+    fn color(self) -> TermColor {
+        match self {
+            Status::Pending => TermColor::Yellow,
+            Status::InProgress => TermColor::Cyan,
+            Status::Completed => TermColor::Green,
+        }
+    }
 
-        let path = Path::new("tasks.json");
-        if path.exists() {
-            let file = File::open(path)?;
-            app_data.task_lists = serde_json::from_reader(file)?;
+    fn label(self) -> &'static str {
+        match self {
+            Status::Pending => "Pending",
+            Status::InProgress => "In progress",
+            Status::Completed => "Completed",
         }
+    }
 
-        let path = Path::new("users.json");
-        if path.exists() {
-            let file = File::open(path)?;
-            let users: Vec<User> = serde_json::from_reader(file)?;
-            for user in users {
-                app_data.users.insert(user.username.clone(), user);
-            }
+    fn parse(value: &str) -> Option<Self> {
+        Status::ALL
+            .into_iter()
+            .find(|status| status.label().eq_ignore_ascii_case(value.trim()))
+    }
+}
+
+/// Criteria for selecting tasks in bulk operations. Unset fields match
+/// every task.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct Filter {
+    /// Case-insensitive substring of the description.
+    text: Option<String>,
+    status: Option<Status>,
+    priority: Option<Priority>,
+    tag: Option<String>,
+}
+
+impl Filter {
+    fn matches(&self, task: &Task) -> bool {
+        self.text
+            .as_ref()
+            .is_none_or(|text| task.description.to_lowercase().contains(&text.to_lowercase()))
+            && self.status.is_none_or(|status| task.status() == status)
+            && self.priority.is_none_or(|priority| task.priority == priority)
+            && self.tag.as_ref().is_none_or(|tag| task.tags.iter().any(|t| t.to_lowercase() == tag.to_lowercase()))
+    }
+}
+
+/// Eisenhower matrix quadrant. Urgent tasks are overdue or due within
+/// `Config::due_soon_days`; important ones have High priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Quadrant {
+    UrgentImportant,
+    Important,
+    Urgent,
+    Neither,
+}
+
+impl Quadrant {
+    const ALL: [Quadrant; 4] = [Quadrant::UrgentImportant, Quadrant::Important, Quadrant::Urgent, Quadrant::Neither];
+
+    fn of(task: &Task, now: DateTime<Utc>, config: &Config) -> Self {
+        let today = config.display_timezone().date(now);
+        let due_soon = today + Days::new(u64::from(config.due_soon_days));
+        let urgent = task.due_date.is_some_and(|due| due <= due_soon);
+        match (urgent, effective_priority(task, now, config) >= Priority::High) {
+            (true, true) => Quadrant::UrgentImportant,
+            (false, true) => Quadrant::Important,
+            (true, false) => Quadrant::Urgent,
+            (false, false) => Quadrant::Neither,
         }
+    }
 
-        Ok(app_data)
+    /// Key in the `tasks matrix --json` output.
+    fn key(self) -> &'static str {
+        match self {
+            Quadrant::UrgentImportant => "urgent_important",
+            Quadrant::Important => "important",
+            Quadrant::Urgent => "urgent",
+            Quadrant::Neither => "neither",
+        }
     }
 
-    fn register_user(&mut self, username: String, password: String) -> io::Result<()> {
-        if self.users.contains_key(&username) {
-            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "User already exists"));
+    fn label(self) -> &'static str {
+        match self {
+            Quadrant::UrgentImportant => "Urgent and important",
+            Quadrant::Important => "Important, not urgent",
+            Quadrant::Urgent => "Urgent, not important",
+            Quadrant::Neither => "Neither urgent nor important",
         }
+    }
+}
 
-        let hashed_password = hash(password, DEFAULT_COST).unwrap();
-        let user = User { username, password: hashed_password };
-        self.users.insert(user.username.clone(), user);
+impl Task {
+    fn new(id: u32, description: String) -> Self {
+        Task {
+            id,
+            uuid: Uuid::new_v4(),
+            description,
+            completed: false,
+            created_at: Utc::now(),
+            completed_at: None,
+            started_at: None,
+            due_date: None,
+            tags: Vec::new(),
+            priority: Priority::default(),
+            last_modified_at: Utc::now(),
+            link: None,
+            depends_on: Vec::new(),
+            estimated_story_points: None,
+            sprint: None,
+            milestone: None,
+            color_tag: None,
+            progress: 0,
+            locked: false,
+            context: None,
+            escalated_at: None,
+            workflow_status: None,
+            version: 0,
+        }
+    }
 
-        Ok(())
+    /// ANSI escape that starts this task's color: its `color_tag` if set,
+    /// otherwise the theme's color for its status. Empty in the mono theme.
+    fn ansi_color(&self, theme: Theme) -> String {
+        match self.color_tag {
+            Some(_) if theme == Theme::Mono => String::new(),
+            Some(color) => ansi_256_color(Some(color)),
+            None => theme.status_color(self.status()),
+        }
     }
 
-    fn authenticate(&self, username: &str, password: &str) -> bool {
-        if let Some(user) = self.users.get(username) {
-            verify(password, &user.password).unwrap_or(false)
+    fn status(&self) -> Status {
+        if self.completed {
+            Status::Completed
+        } else if self.started_at.is_some() {
+            Status::InProgress
         } else {
-            false
+            Status::Pending
         }
     }
+}
 
-    fn display_tasks(&self, username: &str) {
-        if let Some(list) = self.task_lists.iter().find(|list| list.username == username) {
-            println!("Tasks for {}:", username);
-            for task in &list.tasks {
-                let status = if task.completed { "Completed" } else { "Pending" };
-                println!("ID: {}, Description: {}, Status: {}", task.id, task.description, status);
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct User {
+    username: String,
+    password: String,
+    #[serde(default)]
+    role: Role,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    display_name: Option<String>,
+    #[serde(default)]
+    settings: UserSettings,
+    /// Custom task statuses in order; empty for the built-in Pending → In
+    /// progress → Completed.
+    #[serde(default)]
+    workflow: Vec<WorkflowStatus>,
+}
+
+/// A user's own display settings. Unset ones fall back to config.json, and
+/// then to the built-in defaults; see `Config::with_user_settings`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+struct UserSettings {
+    default_list: Option<String>,
+    date_format: Option<String>,
+    due_soon_days: Option<u32>,
+    /// `Some(false)` shows listings in the mono theme.
+    color: Option<bool>,
+    page_size: Option<usize>,
+    timezone: Option<String>,
+}
+
+impl UserSettings {
+    const KEYS: [&str; 6] = ["default_list", "date_format", "due_soon_days", "color", "page_size", "timezone"];
+
+    fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "default_list" => self.default_list.clone(),
+            "date_format" => self.date_format.clone(),
+            "due_soon_days" => self.due_soon_days.map(|days| days.to_string()),
+            "color" => self.color.map(|color| if color { "on" } else { "off" }.to_string()),
+            "page_size" => self.page_size.map(|size| size.to_string()),
+            "timezone" => self.timezone.clone(),
+            _ => None,
+        }
+    }
+
+    /// Sets `key` from user input after checking it, or unsets it for `None`.
+    fn set(&mut self, key: &str, value: Option<&str>) -> Result<(), AppError> {
+        let invalid = |expected: &str| AppError::InvalidSetting(format!("{} must be {}", key, expected));
+        match key {
+            "default_list" => {
+                if value.is_some_and(|name| name.trim().is_empty()) {
+                    return Err(invalid("a list name"));
+                }
+                self.default_list = value.map(|name| name.trim().to_string());
+            }
+            "date_format" => {
+                if let Some(format) = value {
+                    validate_date_format(format).map_err(|_| invalid("a valid strftime format, e.g. %d.%m.%Y %H:%M"))?;
+                }
+                self.date_format = value.map(str::to_string);
+            }
+            "due_soon_days" => {
+                self.due_soon_days = value.map(str::parse).transpose().map_err(|_| invalid("a number of days"))?;
+            }
+            "color" => {
+                self.color = match value.map(str::to_lowercase).as_deref() {
+                    None => None,
+                    Some("on") => Some(true),
+                    Some("off") => Some(false),
+                    Some(_) => return Err(invalid("on or off")),
+                };
+            }
+            "page_size" => {
+                self.page_size =
+                    value.map(str::parse).transpose().map_err(|_| invalid("a number of tasks, 0 for all"))?;
+            }
+            "timezone" => {
+                if value.is_some_and(|timezone| DisplayTimezone::parse(timezone).is_none()) {
+                    return Err(invalid("a zone name like Europe/Kyiv or an offset like +02:00"));
+                }
+                self.timezone = value.map(str::to_string);
+            }
+            _ => {
+                return Err(AppError::InvalidSetting(format!(
+                    "unknown setting '{}', expected one of {}",
+                    key,
+                    UserSettings::KEYS.join(", ")
+                )))
             }
-        } else {
-            println!("No tasks found for {}", username);
         }
+        Ok(())
     }
 }
 
-fn main() {
-    let mut app_data = AppData::load().unwrap_or_else(|_| AppData::new());
+/// Fails unless `format` is a non-empty strftime format chrono understands.
+fn validate_date_format(format: &str) -> Result<(), AppError> {
+    if format.is_empty() || StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(AppError::InvalidConfig(format!("date_format '{}' is not a valid strftime format", format)));
+    }
+    Ok(())
+}
 
-    println!("Enter 1 to register a new user or anything else to log in: ");
-    let mut choice = String::new();
-    io::stdin().read_line(&mut choice).unwrap();
-    let choice = choice.trim();
+/// One step of a user's workflow. Tasks in the terminal step count as
+/// completed everywhere else, stats included.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct WorkflowStatus {
+    name: String,
+    #[serde(default)]
+    terminal: bool,
+}
 
-    if choice == "1" {
-        println!("Enter username for new user: ");
-        let mut new_user_username = String::new();
-        io::stdin().read_line(&mut new_user_username).unwrap();
-        let new_user_username = new_user_username.trim();
+impl WorkflowStatus {
+    /// The built-in statuses as a workflow, for users without their own.
+    fn default_workflow() -> Vec<WorkflowStatus> {
+        Status::ALL
+            .into_iter()
+            .map(|status| WorkflowStatus { name: status.label().to_string(), terminal: status == Status::Completed })
+            .collect()
+    }
 
-        println!("Enter password: ");
-        let mut new_user_password = String::new();
-        io::stdin().read_line(&mut new_user_password).unwrap();
-        let new_user_password = new_user_password.trim();
+    /// Parses "Backlog, Ready, Doing, Review, Done*": status names in order,
+    /// with a `*` after the terminal one. Without a `*` the last one is
+    /// terminal.
+    fn parse_workflow(input: &str) -> Result<Vec<WorkflowStatus>, AppError> {
+        let mut statuses: Vec<WorkflowStatus> = input
+            .split(',')
+            .map(|name| match name.trim().strip_suffix('*') {
+                Some(name) => WorkflowStatus { name: name.trim().to_string(), terminal: true },
+                None => WorkflowStatus { name: name.trim().to_string(), terminal: false },
+            })
+            .collect();
+        if !statuses.iter().any(|status| status.terminal) {
+            if let Some(last) = statuses.last_mut() {
+                last.terminal = true;
+            }
+        }
+        validate_workflow(&statuses)?;
+        Ok(statuses)
+    }
+}
 
-        if let Err(e) = app_data.register_user(new_user_username.to_string(), new_user_password.to_string()) {
-            println!("Error: {}", e);
-        } else {
-            println!("User successfully registered!");
+/// The priority a task is shown and sorted with. With
+/// `escalate_priorities` on, pending tasks due within `escalate_within_days`
+/// days of `now` (or overdue) rank one level above their stored priority,
+/// unless that was already raised for being overdue.
+fn effective_priority(task: &Task, now: DateTime<Utc>, config: &Config) -> Priority {
+    let today = config.display_timezone().date(now);
+    let due_soon = today + Days::new(u64::from(config.escalate_within_days));
+    let escalate = config.escalate_priorities
+        && !task.completed
+        && task.escalated_at.is_none()
+        && task.due_date.is_some_and(|due| due <= due_soon);
+    if escalate {
+        task.priority.raised()
+    } else {
+        task.priority
+    }
+}
+
+/// How strongly a task asks for attention right now; higher is more urgent.
+/// Adds up the effective priority (10 to 40), the due date (30 plus a point
+/// per day overdue up to 10 more, 25 for today, 15 within `due_soon_days`),
+/// 5 for work already in progress and a point per untouched week, up to 5.
+fn compute_focus_score(task: &Task, now: DateTime<Utc>, config: &Config) -> u32 {
+    let mut score = match effective_priority(task, now, config) {
+        Priority::Critical => 40,
+        Priority::High => 30,
+        Priority::Medium => 20,
+        Priority::Low => 10,
+    };
+    let today = config.display_timezone().date(now);
+    if let Some(due) = task.due_date {
+        let days_left = (due - today).num_days();
+        score += match days_left {
+            ..0 => 30 + days_left.unsigned_abs().min(10) as u32,
+            0 => 25,
+            days if days <= i64::from(config.due_soon_days) => 15,
+            _ => 0,
+        };
+    }
+    if task.status() == Status::InProgress {
+        score += 5;
+    }
+    let weeks_untouched = (now - task.last_modified_at).num_weeks().clamp(0, 5);
+    score + weeks_untouched as u32
+}
+
+/// Lowercases a tag, rejecting empty ones and any with whitespace or commas,
+/// which would break tag filters and the comma-separated exports.
+fn normalize_tag(tag: &str) -> Result<String, AppError> {
+    let tag = tag.trim();
+    if tag.is_empty() || tag.contains(|c: char| c.is_whitespace() || c == ',') {
+        return Err(AppError::InvalidTag(tag.to_string()));
+    }
+    Ok(tag.to_lowercase())
+}
+
+/// A workflow needs at least two uniquely named statuses and exactly one
+/// terminal status, which can't be the first: new tasks start there.
+fn validate_workflow(statuses: &[WorkflowStatus]) -> Result<(), AppError> {
+    let invalid = |message: &str| Err(AppError::InvalidWorkflow(message.to_string()));
+    if statuses.len() < 2 {
+        return invalid("a workflow needs at least two statuses");
+    }
+    if statuses.iter().any(|status| status.name.trim().is_empty()) {
+        return invalid("status names can't be empty");
+    }
+    for (index, status) in statuses.iter().enumerate() {
+        if statuses[..index].iter().any(|earlier| earlier.name.eq_ignore_ascii_case(&status.name)) {
+            return Err(AppError::InvalidWorkflow(format!("'{}' appears more than once", status.name)));
         }
     }
+    if statuses.iter().filter(|status| status.terminal).count() != 1 {
+        return invalid("exactly one status must be terminal");
+    }
+    if statuses[0].terminal {
+        return invalid("the first status can't be terminal");
+    }
+    Ok(())
+}
 
-    println!("Enter username: ");
-    let mut username = String::new();
-    io::stdin().read_line(&mut username).unwrap();
-    let username = username.trim();
+/// Index of the task's step in `workflow`. A status set with `set_status`
+/// is used while it still agrees with whether the task is completed, which
+/// other commands may have changed since. Otherwise the built-in status
+/// maps onto the workflow: completed tasks to the terminal step, started
+/// ones to the second step and the rest to the first.
+fn workflow_position(task: &Task, workflow: &[WorkflowStatus]) -> usize {
+    let explicit = task.workflow_status.as_deref().and_then(|name| {
+        workflow.iter().position(|status| status.name.eq_ignore_ascii_case(name) && status.terminal == task.completed)
+    });
+    explicit.unwrap_or_else(|| match task.status() {
+        Status::Completed => workflow.iter().position(|status| status.terminal).unwrap_or(workflow.len() - 1),
+        Status::InProgress if !workflow[1].terminal => 1,
+        _ => 0,
+    })
+}
 
-    println!("Enter password: ");
-    let mut password = String::new();
-    io::stdin().read_line(&mut password).unwrap();
-    let password = password.trim();
+/// Moves the task to step `index` of `workflow`, keeping the built-in
+/// status in line: the terminal step completes it, the first step leaves it
+/// pending and anything in between marks it in progress.
+fn move_to_step(task: &mut Task, workflow: &[WorkflowStatus], index: usize) {
+    let step = &workflow[index];
+    let now = Utc::now();
+    task.workflow_status = Some(step.name.clone());
+    if step.terminal {
+        if !task.completed {
+            task.completed = true;
+            task.completed_at = Some(now);
+            task.progress = 100;
+        }
+    } else {
+        if task.completed {
+            task.completed = false;
+            task.completed_at = None;
+            task.progress = 0;
+        }
+        if index == 0 {
+            task.started_at = None;
+        } else if task.started_at.is_none() {
+            task.started_at = Some(now);
+        }
+    }
+    task.last_modified_at = now;
+}
 
-    if app_data.authenticate(username, password) {
-        println!("Authentication successful!");
+/// There is no command to promote a user; admins are designated by setting
+/// `"role": "Admin"` in users.json.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Role {
+    #[default]
+    User,
+    Admin,
+}
 
-        loop {
-            println!("\nMenu:");
-            println!("1. View tasks");
-            println!("2. Add task");
-            println!("3. Remove task");
-            println!("4. Edit task");
-            println!("5. Mark task as completed");
-            println!("6. Save and exit");
-
-            let mut choice = String::new();
-            io::stdin().read_line(&mut choice).unwrap();
-            let choice = choice.trim();
+#[derive(Debug, Default)]
+struct MergeReport {
+    tasks_moved: usize,
+    sessions_merged: usize,
+}
 
-            match choice {
-                "1" => {
-                    app_data.display_tasks(username);
-                }
-                "2" => {
-                    println!("Enter task description:");
-                    let mut description = String::new();
-                    io::stdin().read_line(&mut description).unwrap();
-                    app_data.add_task(username, description.trim().to_string());
-                }
-                "3" => {
-                    println!("Enter task ID to remove:");
-                    let mut task_id_str = String::new();
-                    io::stdin().read_line(&mut task_id_str).unwrap();
-                    let task_id: u32 = task_id_str.trim().parse().unwrap();
-                    app_data.remove_task(username, task_id);
-                }
-                "4" => {
-                    println!("Enter task ID to edit:");
-                    let mut task_id_str = String::new();
-                    io::stdin().read_line(&mut task_id_str).unwrap();
-                    let task_id: u32 = task_id_str.trim().parse().unwrap();
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct TaskList {
+    username: String,
+    #[serde(default = "default_list_name")]
+    name: String,
+    /// Other users allowed to see this list.
+    #[serde(default)]
+    shared_with: Vec<String>,
+    tasks: Vec<Task>,
+    /// Tasks deleted from this list, so that syncing with another copy can
+    /// tell a deletion here from an addition there.
+    #[serde(default)]
+    deleted: Vec<Tombstone>,
+    /// Removed tasks that can still be restored, oldest first.
+    #[serde(default)]
+    trash: Vec<Task>,
+    /// Tasks put away by `AppData::archive_stale`, oldest first.
+    #[serde(default)]
+    archived: Vec<Task>,
+    /// When the list was last synced with the CalDAV calendar.
+    #[serde(default)]
+    caldav_synced_at: Option<DateTime<Utc>>,
+}
 
-                    println!("Enter new task description:");
-                    let mut new_description = String::new();
-                    io::stdin().read_line(&mut new_description).unwrap();
-                    app_data.edit_task(username, task_id, new_description.trim().to_string());
-                }
-                "5" => {
-                    println!("Enter task ID to mark as completed:");
-                    let mut task_id_str = String::new();
-                    io::stdin().read_line(&mut task_id_str).unwrap();
-                    let task_id: u32 = task_id_str.trim().parse().unwrap();
-                    app_data.mark_completed(username, task_id);
+/// Layout of tasks.json.
+#[derive(Serialize, Deserialize, Debug)]
+struct TasksFile<'a> {
+    #[serde(default)]
+    last_synced_at: Option<DateTime<Utc>>,
+    task_lists: Cow<'a, [TaskList]>,
+}
+
+impl TasksFile<'_> {
+    /// Also accepts the older layout, a bare array of task lists. `path` is
+    /// only used in error messages.
+    fn parse(path: &Path, bytes: &[u8]) -> Result<Self, AppError> {
+        // Picking the layout up front, rather than trying both, keeps the
+        // line and column of a syntax error.
+        if bytes.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'[') {
+            return Ok(TasksFile {
+                last_synced_at: None,
+                task_lists: Cow::Owned(parse_json_file(path, bytes)?),
+            });
+        }
+        parse_json_file(path, bytes)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Tombstone {
+    uuid: Uuid,
+    deleted_at: DateTime<Utc>,
+}
+
+fn default_list_name() -> String {
+    "default".to_string()
+}
+
+impl TaskList {
+    fn is_visible_to(&self, username: &str) -> bool {
+        self.username == username || self.shared_with.iter().any(|user| user == username)
+    }
+
+    /// Moves the tasks to the trash, see `take_tasks`.
+    fn remove_tasks(&mut self, task_ids: &[u32]) {
+        let removed = self.take_tasks(task_ids);
+        self.trash.extend(removed);
+    }
+
+    /// Takes the tasks out of the list, leaving tombstones, and drops
+    /// dependencies on them.
+    fn take_tasks(&mut self, task_ids: &[u32]) -> Vec<Task> {
+        let now = Utc::now();
+        let (removed, kept): (Vec<Task>, Vec<Task>) =
+            std::mem::take(&mut self.tasks).into_iter().partition(|task| task_ids.contains(&task.id));
+        self.tasks = kept;
+        for task in &removed {
+            self.deleted.push(Tombstone { uuid: task.uuid, deleted_at: now });
+        }
+        for task in &mut self.tasks {
+            task.depends_on.retain(|id| !task_ids.contains(id));
+        }
+        removed
+    }
+
+    /// When the task with `uuid` was deleted from this list, if it was.
+    fn deleted_at(&self, uuid: Uuid) -> Option<DateTime<Utc>> {
+        self.deleted.iter().filter(|tombstone| tombstone.uuid == uuid).map(|tombstone| tombstone.deleted_at).max()
+    }
+}
+
+#[derive(Debug)]
+enum AppError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// A JSON file that couldn't be read, with the offending line and a
+    /// caret under the column where parsing stopped.
+    Parse { path: PathBuf, error: serde_json::Error, pointer: String },
+    Csv(csv::Error),
+    Hash(bcrypt::BcryptError),
+    InvalidConfig(String),
+    UserExists,
+    TaskNotFound(String),
+    TaskLocked(u32),
+    VersionConflict { expected: u32, actual: u32 },
+    AmbiguousTaskId(String, Vec<u32>),
+    TaskLimitReached(usize),
+    ChecksumMismatch(PathBuf),
+    InvalidLink(String),
+    InvalidSetting(String),
+    InvalidContext(String),
+    InvalidWorkflow(String),
+    InvalidTag(String),
+    UnknownStatus(String),
+    ReadOnly,
+    InvalidDependency(String),
+    InvalidSchedule(String),
+    AuthenticationFailed,
+    ModifiedExternally(PathBuf),
+    NothingToUndo,
+    PermissionDenied,
+    UserNotFound(String),
+    Watch(notify::Error),
+    #[cfg(any(feature = "import", feature = "webhooks", feature = "caldav"))]
+    Http(reqwest::Error),
+    /// The CalDAV server's response or a calendar object couldn't be used.
+    #[cfg(feature = "caldav")]
+    CalDav(String),
+    #[cfg(feature = "pdf")]
+    Pdf(printpdf::Error),
+    Secret(String),
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AppError::Io(e) => write!(f, "{}", e),
+            AppError::Json(e) => write!(f, "Invalid data file: {}", e),
+            AppError::Parse { path, error, pointer } => {
+                // serde_json appends the position itself; it is reworded below.
+                let message = error.to_string();
+                let position = format!(" at line {} column {}", error.line(), error.column());
+                let message = message.strip_suffix(&position).unwrap_or(&message);
+                write!(f, "Could not parse {}", path.display())?;
+                if error.line() > 0 {
+                    write!(f, " at line {}, column {}", error.line(), error.column())?;
                 }
-                "6" => {
-                    app_data.save().unwrap();
-                    println!("Data saved. Exiting...");
-                    break;
+                write!(f, ": {}", message)?;
+                if !pointer.is_empty() {
+                    write!(f, "\n{}", pointer)?;
                 }
-                _ => println!("Invalid choice, please try again."),
+                Ok(())
+            }
+            AppError::Csv(e) => write!(f, "Could not write CSV: {}", e),
+            AppError::Hash(e) => write!(f, "Password hashing failed: {}", e),
+            AppError::InvalidConfig(message) => write!(f, "Invalid configuration: {}", message),
+            AppError::UserExists => write!(f, "User already exists"),
+            AppError::TaskNotFound(input) => write!(f, "No task with ID '{}'", input),
+            AppError::TaskLocked(task_id) => write!(f, "Task #{} is locked; unlock it first", task_id),
+            AppError::VersionConflict { expected, actual } => write!(
+                f,
+                "Task was changed in the meantime (expected version {}, found {}); reload and try again",
+                expected, actual
+            ),
+            AppError::AmbiguousTaskId(input, ids) => {
+                let ids: Vec<String> = ids.iter().map(|id| format!("#{}", id)).collect();
+                write!(f, "ID prefix '{}' is ambiguous (matches {})", input, ids.join(", "))
+            }
+            AppError::TaskLimitReached(max) => write!(f, "Task limit of {} reached", max),
+            AppError::ChecksumMismatch(path) => write!(f, "Checksum mismatch for {}", path.display()),
+            AppError::ReadOnly => write!(f, "read-only mode"),
+            AppError::InvalidDependency(message) => write!(f, "Invalid dependency: {}", message),
+            AppError::InvalidSchedule(message) => write!(f, "Invalid schedule: {}", message),
+            AppError::AuthenticationFailed => write!(f, "Wrong username or password"),
+            AppError::NothingToUndo => write!(f, "Nothing to undo"),
+            AppError::PermissionDenied => write!(f, "Only admins can do that"),
+            AppError::UserNotFound(username) => write!(f, "No user named '{}'", username),
+            AppError::Watch(e) => write!(f, "Cannot watch for changes: {}", e),
+            AppError::ModifiedExternally(path) => {
+                write!(f, "{} was changed by another program since it was loaded", path.display())
+            }
+            #[cfg(any(feature = "import", feature = "webhooks", feature = "caldav"))]
+            AppError::Http(e) => write!(f, "Request failed: {}", e),
+            #[cfg(feature = "caldav")]
+            AppError::CalDav(message) => write!(f, "CalDAV sync failed: {}", message),
+            AppError::Secret(message) => write!(f, "Secret storage failed: {}", message),
+            #[cfg(feature = "pdf")]
+            AppError::Pdf(e) => write!(f, "Could not create PDF: {}", e),
+            AppError::InvalidLink(link) => write!(f, "Invalid link '{}': must start with http:// or https://", link),
+            AppError::InvalidSetting(message) => write!(f, "Invalid setting: {}", message),
+            AppError::InvalidContext(context) => {
+                write!(f, "Invalid context '{}': must be @ followed by letters and digits", context)
             }
+            AppError::InvalidWorkflow(message) => write!(f, "Invalid workflow: {}", message),
+            AppError::InvalidTag(tag) => {
+                write!(f, "Invalid tag '{}': tags can't be empty or contain spaces or commas", tag)
+            }
+            AppError::UnknownStatus(status) => write!(f, "'{}' is not a status in your workflow", status),
         }
-    } else {
-        println!("Authentication failed.");
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<io::Error> for AppError {
+    fn from(e: io::Error) -> Self {
+        AppError::Io(e)
+    }
+}
+
+impl From<notify::Error> for AppError {
+    fn from(e: notify::Error) -> Self {
+        AppError::Watch(e)
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        AppError::Json(e)
+    }
+}
+
+impl From<csv::Error> for AppError {
+    fn from(e: csv::Error) -> Self {
+        AppError::Csv(e)
+    }
+}
+
+#[cfg(feature = "pdf")]
+impl From<printpdf::Error> for AppError {
+    fn from(e: printpdf::Error) -> Self {
+        AppError::Pdf(e)
+    }
+}
+
+#[cfg(any(feature = "import", feature = "webhooks", feature = "caldav"))]
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        AppError::Http(e)
+    }
+}
+
+impl From<bcrypt::BcryptError> for AppError {
+    fn from(e: bcrypt::BcryptError) -> Self {
+        AppError::Hash(e)
+    }
+}
+
+enum DisplayTimezone {
+    Named(Tz),
+    Fixed(FixedOffset),
+}
+
+impl DisplayTimezone {
+    fn parse(value: &str) -> Option<Self> {
+        if let Ok(tz) = value.parse::<Tz>() {
+            return Some(DisplayTimezone::Named(tz));
+        }
+        value.parse::<FixedOffset>().ok().map(DisplayTimezone::Fixed)
+    }
+
+    fn format(&self, timestamp: DateTime<Utc>, format: &str) -> String {
+        match self {
+            DisplayTimezone::Named(tz) => timestamp.with_timezone(tz).format(format).to_string(),
+            DisplayTimezone::Fixed(offset) => timestamp.with_timezone(offset).format(format).to_string(),
+        }
+    }
+
+    fn date(&self, timestamp: DateTime<Utc>) -> NaiveDate {
+        match self {
+            DisplayTimezone::Named(tz) => timestamp.with_timezone(tz).date_naive(),
+            DisplayTimezone::Fixed(offset) => timestamp.with_timezone(offset).date_naive(),
+        }
+    }
+
+    fn weekday(&self, timestamp: DateTime<Utc>) -> Weekday {
+        match self {
+            DisplayTimezone::Named(tz) => timestamp.with_timezone(tz).weekday(),
+            DisplayTimezone::Fixed(offset) => timestamp.with_timezone(offset).weekday(),
+        }
+    }
+}
+
+/// One finished Pomodoro focus interval spent on a task.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct PomodoroSession {
+    username: String,
+    task_id: u32,
+    started_at: DateTime<Utc>,
+    completed_at: DateTime<Utc>,
+}
+
+/// A data mutation, as recorded in the journal before it is applied.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "op", content = "payload", rename_all = "snake_case")]
+enum Op {
+    AddUser { user: User },
+    /// `password` is the new bcrypt hash, never the plain-text password.
+    ChangePassword { password: String },
+    /// Sets one of the user's `UserSettings`, or unsets it for `None`.
+    SetUserSetting { key: String, value: Option<String> },
+    SetWorkflow { statuses: Vec<WorkflowStatus> },
+    SetStatus { task_id: u32, status: String },
+    StepStatus { task_id: u32, forward: bool },
+    AddTask { description: String },
+    RemoveTask { task_id: u32 },
+    RemoveTasks { task_ids: Vec<u32> },
+    RestoreFromTrash { task_id: u32 },
+    EmptyTrash,
+    EditTask {
+        task_id: u32,
+        description: String,
+        #[serde(default)]
+        expected_version: Option<u32>,
+    },
+    MarkCompleted { task_id: u32 },
+    MarkInProgress { task_id: u32 },
+    Reopen { task_id: u32 },
+    LockTask { task_id: u32 },
+    UnlockTask { task_id: u32 },
+    SetProgress { task_id: u32, progress: u8 },
+    SetDueDate { task_id: u32, due_date: Option<NaiveDate> },
+    AddTag { task_id: u32, tag: String },
+    SetPriority { task_id: u32, priority: Priority },
+    SetLink { task_id: u32, link: Option<String> },
+    SetStoryPoints { task_id: u32, points: Option<u32> },
+    SetSprint { task_id: u32, sprint: Option<String> },
+    SetMilestone { task_id: u32, milestone: Option<String> },
+    SetContext { task_id: u32, context: Option<String> },
+    SetColorTag { task_id: u32, color_tag: Option<u8> },
+    AddDependency { task_id: u32, depends_on: u32 },
+    RemoveDependency { task_id: u32, depends_on: u32 },
+    BulkSetTag { filter: Filter, tag: String },
+    BulkRemoveTag { filter: Filter, tag: String },
+    SetPriorityMatching { query: String, priority: Priority },
+    BulkAssignSprint { filter: Filter, sprint: String },
+    RenumberByDue,
+    SmartSchedule { tasks_per_day: usize, start_date: NaiveDate, skip_weekends: bool },
+    CompleteOverdue { today: NaiveDate },
+    RebalancePriorities,
+    FindReplace { find: String, replace: String },
+    EscalateOverdue { now: DateTime<Utc> },
+    ArchiveStale { older_than_days: u32, now: DateTime<Utc> },
+    RecordPomodoro { session: PomodoroSession },
+    ImportTasks { tasks: Vec<Task> },
+    /// Tasks created or changed on the CalDAV server, matched by UUID.
+    #[cfg_attr(not(feature = "caldav"), allow(dead_code))]
+    CalDavPull { tasks: Vec<Task>, synced_at: DateTime<Utc> },
+    ImportUser { user: User, task_lists: Vec<TaskList>, decision: ImportDecision },
+    MergeUsers { keep: String, merge_from: String },
+    /// Decisions from a review session, applied all or nothing.
+    ApplyReview { decisions: Vec<(u32, ReviewDecision)> },
+    /// Reverts the user's most recent undoable op.
+    Undo,
+    /// Journal only: the entry before this one, with the same user and
+    /// timestamp, failed to apply and must not be replayed.
+    Aborted,
+}
+
+impl Op {
+    /// Ops that only change the acting user's own task list, which is all
+    /// an undo step restores.
+    fn is_undoable(&self) -> bool {
+        !matches!(
+            self,
+            Op::AddUser { .. }
+                | Op::ChangePassword { .. }
+                | Op::SetUserSetting { .. }
+                | Op::SetWorkflow { .. }
+                | Op::RecordPomodoro { .. }
+                | Op::ImportUser { .. }
+                | Op::MergeUsers { .. }
+                | Op::Undo
+        )
+    }
+
+    /// Ops that rewrite other users' task lists. Restoring an older list
+    /// after one of them would bring back a state that no longer fits, so
+    /// they end the undo history.
+    fn clears_undo(&self) -> bool {
+        matches!(self, Op::ImportUser { .. } | Op::MergeUsers { .. })
+    }
+}
+
+/// A user's task list as it was before an op, for undo.
+#[derive(Debug, Clone)]
+struct UndoStep {
+    username: String,
+    /// `None` when the user had no task list yet.
+    list: Option<TaskList>,
+}
+
+/// What to do with an imported user whose name is already taken.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+enum ImportDecision {
+    /// Leave the existing user and their tasks untouched.
+    Skip,
+    /// Replace the existing user's password and task lists.
+    Overwrite,
+    /// Keep the existing password and add the imported tasks that aren't
+    /// there yet (matched by UUID).
+    Merge,
+}
+
+/// What to do with a task walked through in review mode.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ReviewDecision {
+    Complete,
+    /// Leave the task open as it is.
+    Keep,
+    /// Replace the description.
+    Edit(String),
+    Delete,
+}
+
+impl ImportDecision {
+    /// Interprets the answer to the conflict prompt. Anything unrecognised,
+    /// including no answer at all (EOF), means skip.
+    fn from_answer(answer: &str) -> Self {
+        match answer.trim().to_lowercase().as_str() {
+            "o" | "overwrite" => ImportDecision::Overwrite,
+            "m" | "merge" => ImportDecision::Merge,
+            _ => ImportDecision::Skip,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct JournalEntry {
+    #[serde(flatten)]
+    op: Op,
+    user: String,
+    timestamp: DateTime<Utc>,
+}
+
+/// Append-only log of mutations made since the last save, one JSON object
+/// per line. Replayed on startup after a crash and emptied by every save.
+#[derive(Debug)]
+struct Journal {
+    path: PathBuf,
+    fsync: bool,
+}
+
+impl Journal {
+    fn append(&self, entry: &JournalEntry) -> Result<(), AppError> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(&line)?;
+        if self.fsync {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Entries written after `snapshot`, the time the data files were last
+    /// saved. Older entries were already saved and are skipped, as are
+    /// entries for ops that failed.
+    fn entries_since(&self, snapshot: Option<SystemTime>) -> Result<Vec<JournalEntry>, AppError> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let snapshot: Option<DateTime<Utc>> = snapshot.map(DateTime::from);
+        let mut entries = Vec::new();
+        for line in fs::read_to_string(&self.path)?.lines() {
+            // A crash mid-append can leave a torn last line; ignore it.
+            let Ok(entry) = serde_json::from_str::<JournalEntry>(line) else {
+                continue;
+            };
+            if snapshot.is_some_and(|snapshot| entry.timestamp <= snapshot) {
+                continue;
+            }
+            if matches!(entry.op, Op::Aborted) {
+                let failed = |last: &JournalEntry| last.timestamp == entry.timestamp && last.user == entry.user;
+                if entries.last().is_some_and(failed) {
+                    entries.pop();
+                }
+                continue;
+            }
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    fn truncate(&self) -> Result<(), AppError> {
+        if self.path.exists() {
+            File::create(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "import")]
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+#[cfg(feature = "import")]
+#[derive(Deserialize)]
+struct GithubIssue {
+    title: String,
+    html_url: String,
+    state: String,
+    closed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    labels: Vec<GithubLabel>,
+    milestone: Option<GithubMilestone>,
+    /// Present when the "issue" is actually a pull request.
+    pull_request: Option<serde_json::Value>,
+}
+
+#[cfg(feature = "import")]
+#[derive(Deserialize)]
+struct GithubLabel {
+    name: String,
+}
+
+#[cfg(feature = "import")]
+#[derive(Deserialize)]
+struct GithubMilestone {
+    title: String,
+    due_on: Option<DateTime<Utc>>,
+}
+
+/// What a destructive operation is about to do, computed without touching
+/// any data so it can be shown (for --dry-run) or applied.
+#[derive(Debug, Default)]
+struct ChangePlan {
+    username: String,
+    /// IDs of the tasks that would be deleted.
+    remove: Vec<u32>,
+}
+
+impl ChangePlan {
+    fn is_empty(&self) -> bool {
+        self.remove.is_empty()
+    }
+
+    /// E.g. "would delete 2 tasks: #3, #7".
+    fn describe(&self, verb: &str) -> String {
+        let ids: Vec<String> = self.remove.iter().map(|id| format!("#{}", id)).collect();
+        let noun = if self.remove.len() == 1 { "task" } else { "tasks" };
+        format!("{} {} {}: {}", verb, self.remove.len(), noun, ids.join(", "))
+    }
+}
+
+/// How a data file looked when it was last loaded or saved, so that a rewrite
+/// by another program (or a sync tool) can be noticed before clobbering it.
+#[derive(Debug, Clone)]
+struct FileStamp {
+    modified: SystemTime,
+    sha256: String,
+}
+
+impl FileStamp {
+    fn new(path: &Path, bytes: &[u8]) -> Result<Self, AppError> {
+        Ok(FileStamp {
+            modified: fs::metadata(path)?.modified()?,
+            sha256: sha256_hex(bytes),
+        })
+    }
+
+    /// Whether the file no longer matches the stamp. The hash is only
+    /// computed when the modification time differs.
+    fn is_stale(&self, path: &Path) -> Result<bool, AppError> {
+        if fs::metadata(path)?.modified()? == self.modified {
+            return Ok(false);
+        }
+        Ok(sha256_hex(&fs::read(path)?) != self.sha256)
+    }
+}
+
+/// Stamps of the three data files, see `FileStamp`.
+#[derive(Debug, Clone)]
+struct FileStamps {
+    tasks: Option<FileStamp>,
+    users: Option<FileStamp>,
+    pomodoros: Option<FileStamp>,
+}
+
+/// The data as an autosave wrote it, for the UI thread to take as its new
+/// base, see `AppData::collect_autosave`.
+#[derive(Debug)]
+struct Autosaved {
+    generation: u64,
+    last_synced_at: Option<DateTime<Utc>>,
+    task_lists: Vec<TaskList>,
+    usernames: Vec<String>,
+}
+
+/// What the last write put on disk, shared by the UI thread and the
+/// `Autosaver`. The lock is held for the whole of every write, so the two
+/// never write at the same time.
+#[derive(Debug, Default)]
+struct DiskState {
+    /// `AppData::generation` of the newest data written to tasks.json.
+    generation: u64,
+    /// `None` until something was written this session.
+    stamps: Option<FileStamps>,
+    /// Set by an autosave until the UI thread picks it up.
+    autosaved: Option<Autosaved>,
+}
+
+fn lock_disk(disk: &Mutex<DiskState>) -> MutexGuard<'_, DiskState> {
+    disk.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A copy of the data files' contents taken right after a change, for the
+/// `Autosaver`.
+#[derive(Debug)]
+struct AutosaveSnapshot {
+    task_lists: Vec<TaskList>,
+    users: HashMap<String, User>,
+    pomodoros: Vec<PomodoroSession>,
+    generation: u64,
+    taken_at: SystemTime,
+}
+
+/// Writes unsaved changes to disk every `interval` on a background thread.
+/// Only the newest snapshot sent in an interval is written, and one older
+/// than what is already on disk is skipped, so a late autosave can't undo an
+/// explicit save. Dropping it stops the thread without writing; anything
+/// unsaved is still in the journal.
+#[derive(Debug)]
+struct Autosaver {
+    sender: Option<mpsc::Sender<AutosaveSnapshot>>,
+    disk: Arc<Mutex<DiskState>>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl Autosaver {
+    /// `config` only decides where the data files are written.
+    fn start(interval: Duration, config: Config) -> Self {
+        let (sender, receiver) = mpsc::channel::<AutosaveSnapshot>();
+        let disk = Arc::new(Mutex::new(DiskState::default()));
+        let shared = Arc::clone(&disk);
+        let handle = thread::spawn(move || {
+            let mut pending = None;
+            let mut deadline = Instant::now() + interval;
+            loop {
+                match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(snapshot) => {
+                        pending = Some(snapshot);
+                        continue;
+                    }
+                    Err(mpsc::RecvTimeoutError::Timeout) => deadline = Instant::now() + interval,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+                let Some(snapshot) = pending.take() else {
+                    continue;
+                };
+                let (generation, taken_at) = (snapshot.generation, snapshot.taken_at);
+                let mut data = AppData::new();
+                data.config = config.clone();
+                data.task_lists = snapshot.task_lists;
+                data.users = snapshot.users;
+                data.pomodoros = snapshot.pomodoros;
+
+                let mut disk = lock_disk(&shared);
+                if generation <= disk.generation {
+                    continue;
+                }
+                if let Some(stamps) = &disk.stamps {
+                    data.set_file_stamps(stamps.clone());
+                }
+                match data.autosave(taken_at) {
+                    Ok(()) => {
+                        disk.generation = generation;
+                        disk.stamps = Some(data.file_stamps());
+                        disk.autosaved = Some(Autosaved {
+                            generation,
+                            last_synced_at: data.last_synced_at,
+                            task_lists: data.task_lists,
+                            usernames: data.base_usernames,
+                        });
+                    }
+                    Err(e) => println!("Warning: autosave failed: {}", e),
+                }
+            }
+        });
+        Autosaver { sender: Some(sender), disk, handle: Some(handle) }
+    }
+
+    fn send(&self, snapshot: AutosaveSnapshot) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(snapshot);
+        }
+    }
+}
+
+impl Drop for Autosaver {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// A task that was changed both in memory and on disk, found by `AppData::merge`.
+#[derive(Debug)]
+struct MergeConflict {
+    username: String,
+    task_id: u32,
+    description: String,
+    resolution: &'static str,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: #{} {} ({})", self.username, self.task_id, self.description, self.resolution)
+    }
+}
+
+/// Summary counts over one user's tasks, see `AppData::task_stats`.
+#[derive(Serialize, Debug, Default)]
+struct TaskStats {
+    total: usize,
+    pending: usize,
+    in_progress: usize,
+    completed: usize,
+    /// Open tasks whose due date has passed.
+    overdue: usize,
+    /// Open tasks only, like `AppData::priority_breakdown`.
+    per_priority: BTreeMap<Priority, usize>,
+    per_tag: BTreeMap<String, usize>,
+}
+
+impl TaskStats {
+    /// One line for the menu header, e.g. "[3 pending | 1 in progress | 1
+    /// overdue | 5 done]", or a plain sentence in accessible mode.
+    fn summary_line(&self, accessible: bool) -> String {
+        let counts = [
+            format!("{} pending", self.pending),
+            format!("{} in progress", self.in_progress),
+            format!("{} overdue", self.overdue),
+            format!("{} done", self.completed),
+        ];
+        if accessible {
+            format!("{}.", counts.join(", "))
+        } else {
+            format!("[{}]", counts.join(" | "))
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct SprintCapacity {
+    total_points: u32,
+    completed_points: u32,
+    remaining_points: u32,
+    /// Share of the total points already completed, 0 to 100.
+    completion_pct: f64,
+}
+
+/// One difference between two snapshots of the data, see `AppData::diff`.
+#[derive(Debug)]
+enum Change {
+    Added { username: String, task: Task },
+    Removed { username: String, task: Task },
+    Modified { username: String, old: Box<Task>, new: Box<Task> },
+    UserAdded { username: String },
+    UserRemoved { username: String },
+}
+
+impl Change {
+    fn username(&self) -> &str {
+        match self {
+            Change::Added { username, .. }
+            | Change::Removed { username, .. }
+            | Change::Modified { username, .. }
+            | Change::UserAdded { username }
+            | Change::UserRemoved { username } => username,
+        }
+    }
+
+    fn task_id(&self) -> Option<u32> {
+        match self {
+            Change::Added { task, .. } | Change::Removed { task, .. } => Some(task.id),
+            Change::Modified { new, .. } => Some(new.id),
+            Change::UserAdded { .. } | Change::UserRemoved { .. } => None,
+        }
+    }
+
+    /// The change as a sentence for accessible mode, e.g. "Task 4, call
+    /// dentist, completed."
+    fn sentence(&self) -> String {
+        match self {
+            Change::Added { task, .. } => format!("Task {}, {}, added.", task.id, task.description),
+            Change::Removed { task, .. } => format!("Task {}, {}, removed.", task.id, task.description),
+            Change::Modified { old, new, .. } if !old.completed && new.completed => {
+                format!("Task {}, {}, completed.", new.id, new.description)
+            }
+            Change::Modified { old, new, .. } if old.description != new.description => {
+                format!("Task {} renamed from {} to {}.", new.id, old.description, new.description)
+            }
+            Change::Modified { new, .. } => format!("Task {}, {}, updated.", new.id, new.description),
+            Change::UserAdded { username } => format!("User {} added.", username),
+            Change::UserRemoved { username } => format!("User {} removed.", username),
+        }
+    }
+}
+
+/// Compares task lists task by task. Tasks are matched by owner and UUID,
+/// so renumbering or moving a task between lists isn't a change.
+fn diff_task_lists(old: &[TaskList], new: &[TaskList]) -> Vec<Change> {
+    let tasks_by_owner = |lists: &[TaskList]| -> BTreeMap<(String, Uuid), Task> {
+        lists
+            .iter()
+            .flat_map(|list| list.tasks.iter().map(|task| ((list.username.clone(), task.uuid), task.clone())))
+            .collect()
+    };
+    let old_tasks = tasks_by_owner(old);
+    let mut new_tasks = tasks_by_owner(new);
+
+    let mut changes = Vec::new();
+    for ((username, uuid), old_task) in old_tasks {
+        match new_tasks.remove(&(username.clone(), uuid)) {
+            None => changes.push(Change::Removed { username, task: old_task }),
+            Some(new_task) if new_task != old_task => {
+                changes.push(Change::Modified { username, old: Box::new(old_task), new: Box::new(new_task) })
+            }
+            Some(_) => {}
+        }
+    }
+    changes.extend(new_tasks.into_iter().map(|((username, _), task)| Change::Added { username, task }));
+    // Matching went by UUID; list them the way the user sees the tasks.
+    changes.sort_by(|a, b| (a.username(), a.task_id()).cmp(&(b.username(), b.task_id())));
+    changes
+}
+
+/// Users added and removed between two sets of usernames, in name order.
+fn diff_usernames<'a>(
+    old: impl Iterator<Item = &'a String>,
+    new: impl Iterator<Item = &'a String>,
+) -> Vec<Change> {
+    let old: BTreeSet<&String> = old.collect();
+    let new: BTreeSet<&String> = new.collect();
+    let removed = old.difference(&new).map(|username| Change::UserRemoved { username: username.to_string() });
+    let added = new.difference(&old).map(|username| Change::UserAdded { username: username.to_string() });
+    removed.chain(added).collect()
+}
+
+/// One line per change, e.g. "+ added #12 'call dentist'" or "✓ #4 completed".
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added { task, .. } => write!(f, "+ added #{} '{}'", task.id, task.description),
+            Change::Removed { task, .. } => write!(f, "- removed #{} '{}'", task.id, task.description),
+            Change::Modified { old, new, .. } if !old.completed && new.completed => {
+                write!(f, "\u{2713} #{} completed", new.id)
+            }
+            Change::Modified { old, new, .. } if old.description != new.description => {
+                write!(f, "~ #{} renamed '{}' -> '{}'", new.id, old.description, new.description)
+            }
+            Change::Modified { new, .. } => write!(f, "~ #{} '{}' updated", new.id, new.description),
+            Change::UserAdded { username } => write!(f, "+ added user '{}'", username),
+            Change::UserRemoved { username } => write!(f, "- removed user '{}'", username),
+        }
+    }
+}
+
+/// Body of a webhook POST.
+#[cfg(feature = "webhooks")]
+#[derive(Serialize, Debug)]
+struct WebhookEvent {
+    /// "task_added", "task_completed" or "task_removed".
+    event: &'static str,
+    username: String,
+    task: Task,
+}
+
+/// Delivers webhook events on a background thread so the UI never waits on
+/// the network. Dropping it waits for queued events to be sent.
+#[cfg(feature = "webhooks")]
+#[derive(Debug)]
+struct WebhookWorker {
+    sender: Option<mpsc::Sender<WebhookEvent>>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+#[cfg(feature = "webhooks")]
+impl WebhookWorker {
+    fn start(urls: Vec<String>, secret: Option<String>) -> Result<Self, AppError> {
+        let client = reqwest::blocking::Client::builder().timeout(WEBHOOK_TIMEOUT).build()?;
+        let (sender, receiver) = mpsc::channel::<WebhookEvent>();
+        let handle = thread::spawn(move || {
+            for event in receiver {
+                for url in &urls {
+                    if let Err(e) = deliver_webhook(&client, url, secret.as_deref(), &event) {
+                        eprintln!("Webhook to {} failed: {}", url, e);
+                    }
+                }
+            }
+        });
+        Ok(WebhookWorker { sender: Some(sender), handle: Some(handle) })
+    }
+
+    fn send(&self, event: WebhookEvent) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(event);
+        }
+    }
+}
+
+#[cfg(feature = "webhooks")]
+impl Drop for WebhookWorker {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// POSTs `event` as JSON, signed with `secret` if there is one, making up to
+/// three attempts with exponential backoff.
+#[cfg(feature = "webhooks")]
+fn deliver_webhook(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    secret: Option<&str>,
+    event: &WebhookEvent,
+) -> Result<(), AppError> {
+    use hmac::{Hmac, Mac};
+
+    let body = serde_json::to_vec(event)?;
+    let signature = secret.map(|secret| {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(&body);
+        format!("sha256={:x}", mac.finalize().into_bytes())
+    });
+
+    let mut backoff = WEBHOOK_RETRY_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        let mut request = client.post(url).header("Content-Type", "application/json").body(body.clone());
+        if let Some(signature) = &signature {
+            request = request.header("X-Signature-256", signature);
+        }
+        match request.send().and_then(|response| response.error_for_status()) {
+            Ok(_) => return Ok(()),
+            Err(_) if attempt < 3 => {
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// The parts of a CalDAV VTODO that map onto a task. Everything else in the
+/// calendar object is kept verbatim, so pushing an update doesn't drop
+/// alarms, descriptions or time zones set by other clients.
+#[cfg(feature = "caldav")]
+#[derive(Debug, Clone, Default, PartialEq)]
+struct VTodo {
+    uid: String,
+    summary: String,
+    /// NEEDS-ACTION, IN-PROCESS, COMPLETED or CANCELLED.
+    status: Option<String>,
+    last_modified: Option<DateTime<Utc>>,
+    created: Option<DateTime<Utc>>,
+    completed: Option<DateTime<Utc>>,
+    due: Option<NaiveDate>,
+    /// 1 (highest) to 9 (lowest); 0 means undefined. 1 is Critical, 2 to 4
+    /// High, 5 Medium and 6 to 9 Low.
+    priority: Option<u8>,
+    percent_complete: Option<u8>,
+    categories: Vec<String>,
+    /// Unfolded VTODO lines not listed above, including nested VALARMs.
+    extra: Vec<String>,
+    /// Unfolded lines of the VCALENDAR outside the VTODO, e.g. VTIMEZONEs.
+    calendar_extra: Vec<String>,
+}
+
+#[cfg(feature = "caldav")]
+impl VTodo {
+    /// Parses an iCalendar object holding one VTODO.
+    fn parse(ics: &str) -> Result<Self, AppError> {
+        let mut lines: Vec<String> = Vec::new();
+        for line in ics.lines() {
+            match line.strip_prefix([' ', '\t']) {
+                Some(continuation) => {
+                    if let Some(last) = lines.last_mut() {
+                        last.push_str(continuation);
+                    }
+                }
+                None => lines.push(line.to_string()),
+            }
+        }
+
+        let mut todo = VTodo::default();
+        let mut found = false;
+        let mut in_todo = false;
+        // Depth of components nested inside the VTODO, such as VALARM.
+        let mut nested = 0;
+        for line in lines.into_iter().filter(|line| !line.is_empty()) {
+            if !in_todo {
+                if line == "BEGIN:VTODO" && !found {
+                    found = true;
+                    in_todo = true;
+                } else if !["BEGIN:VCALENDAR", "END:VCALENDAR", "VERSION:", "PRODID:"]
+                    .iter()
+                    .any(|prefix| line.starts_with(prefix))
+                {
+                    todo.calendar_extra.push(line);
+                }
+                continue;
+            }
+            if nested > 0 || line.starts_with("BEGIN:") {
+                if line.starts_with("BEGIN:") {
+                    nested += 1;
+                } else if line.starts_with("END:") {
+                    nested -= 1;
+                }
+                todo.extra.push(line);
+                continue;
+            }
+            if line == "END:VTODO" {
+                in_todo = false;
+                continue;
+            }
+
+            let Some((name, value)) = split_ics_property(&line) else {
+                todo.extra.push(line);
+                continue;
+            };
+            match name.to_ascii_uppercase().as_str() {
+                "UID" => todo.uid = value.to_string(),
+                "SUMMARY" => todo.summary = unescape_ics_text(value),
+                "STATUS" => todo.status = Some(value.to_ascii_uppercase()),
+                "LAST-MODIFIED" => todo.last_modified = parse_ics_datetime(value),
+                "CREATED" => todo.created = parse_ics_datetime(value),
+                "COMPLETED" => todo.completed = parse_ics_datetime(value),
+                "DUE" => todo.due = parse_ics_datetime(value).map(|due| due.date_naive()),
+                "PRIORITY" => todo.priority = value.parse().ok(),
+                "PERCENT-COMPLETE" => todo.percent_complete = value.parse().ok(),
+                "CATEGORIES" => todo.categories.extend(split_ics_list(value)),
+                // Regenerated on every write.
+                "DTSTAMP" => {}
+                _ => todo.extra.push(line),
+            }
+        }
+
+        if !found {
+            return Err(AppError::CalDav("calendar object has no VTODO".to_string()));
+        }
+        if todo.uid.is_empty() {
+            return Err(AppError::CalDav("VTODO without a UID".to_string()));
+        }
+        Ok(todo)
+    }
+
+    /// The VTODO for `task`, keeping whatever `base` (the server's current
+    /// copy, if any) has that tasks don't.
+    fn from_task(task: &Task, base: Option<&VTodo>) -> Self {
+        let mut todo = base.cloned().unwrap_or_default();
+        if todo.uid.is_empty() {
+            todo.uid = task.uuid.to_string();
+        }
+        todo.summary = task.description.clone();
+        todo.status = Some(
+            match task.status() {
+                Status::Pending => "NEEDS-ACTION",
+                Status::InProgress => "IN-PROCESS",
+                Status::Completed => "COMPLETED",
+            }
+            .to_string(),
+        );
+        todo.last_modified = Some(task.last_modified_at);
+        todo.created = Some(task.created_at);
+        todo.completed = task.completed_at;
+        todo.due = task.due_date;
+        todo.priority = Some(match task.priority {
+            Priority::Critical => 1,
+            Priority::High => 2,
+            Priority::Medium => 5,
+            Priority::Low => 9,
+        });
+        todo.percent_complete = (task.progress > 0).then_some(task.progress);
+        todo.categories = task.tags.clone();
+        todo
+    }
+
+    /// Copies the VTODO's fields onto `task`; UUID and ID are left alone.
+    fn apply_to(&self, task: &mut Task) {
+        let modified = self.last_modified.unwrap_or_else(Utc::now);
+        task.description = self.summary.clone();
+        match self.status.as_deref() {
+            Some("COMPLETED") | Some("CANCELLED") => {
+                task.completed = true;
+                task.completed_at = self.completed.or(Some(modified));
+            }
+            Some("IN-PROCESS") => {
+                task.completed = false;
+                task.completed_at = None;
+                task.started_at = task.started_at.or(Some(modified));
+            }
+            _ if self.completed.is_some() => {
+                task.completed = true;
+                task.completed_at = self.completed;
+            }
+            _ => {
+                task.completed = false;
+                task.completed_at = None;
+                task.started_at = None;
+            }
+        }
+        task.due_date = self.due;
+        match self.priority {
+            Some(1) => task.priority = Priority::Critical,
+            Some(2..=4) => task.priority = Priority::High,
+            Some(5) => task.priority = Priority::Medium,
+            Some(6..=9) => task.priority = Priority::Low,
+            _ => {}
+        }
+        task.progress = if task.completed { 100 } else { self.percent_complete.unwrap_or(0).min(100) };
+        task.tags = self.categories.clone();
+        task.last_modified_at = modified;
+    }
+
+    /// Serializes the VTODO as a complete iCalendar object, CRLF-terminated
+    /// and folded at 75 bytes as RFC 5545 requires.
+    fn to_ics(&self) -> String {
+        let format_datetime = |datetime: DateTime<Utc>| datetime.format("%Y%m%dT%H%M%SZ").to_string();
+
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            "PRODID:-//Lab_3//Tasks//EN".to_string(),
+        ];
+        lines.extend(self.calendar_extra.iter().cloned());
+        lines.push("BEGIN:VTODO".to_string());
+        lines.push(format!("UID:{}", self.uid));
+        lines.push(format!("DTSTAMP:{}", format_datetime(Utc::now())));
+        lines.push(format!("SUMMARY:{}", escape_ics_text(&self.summary)));
+        if let Some(status) = &self.status {
+            lines.push(format!("STATUS:{}", status));
+        }
+        if let Some(last_modified) = self.last_modified {
+            lines.push(format!("LAST-MODIFIED:{}", format_datetime(last_modified)));
+        }
+        if let Some(created) = self.created {
+            lines.push(format!("CREATED:{}", format_datetime(created)));
+        }
+        if let Some(completed) = self.completed {
+            lines.push(format!("COMPLETED:{}", format_datetime(completed)));
+        }
+        if let Some(due) = self.due {
+            lines.push(format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d")));
+        }
+        if let Some(priority) = self.priority {
+            lines.push(format!("PRIORITY:{}", priority));
+        }
+        if let Some(percent_complete) = self.percent_complete {
+            lines.push(format!("PERCENT-COMPLETE:{}", percent_complete));
+        }
+        if !self.categories.is_empty() {
+            let categories: Vec<String> = self.categories.iter().map(|category| escape_ics_text(category)).collect();
+            lines.push(format!("CATEGORIES:{}", categories.join(",")));
+        }
+        lines.extend(self.extra.iter().cloned());
+        lines.push("END:VTODO".to_string());
+        lines.push("END:VCALENDAR".to_string());
+
+        lines.iter().map(|line| fold_ics_line(line)).collect()
+    }
+}
+
+/// Splits "NAME;PARAM=x:value" into name and value, skipping colons inside
+/// quoted parameter values.
+#[cfg(feature = "caldav")]
+fn split_ics_property(line: &str) -> Option<(&str, &str)> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let name = line[..colon].split(';').next().unwrap_or_default();
+    Some((name, &line[colon + 1..]))
+}
+
+/// Accepts "20240301T120000Z", floating local times (taken as UTC) and
+/// plain dates (midnight UTC).
+#[cfg(feature = "caldav")]
+fn parse_ics_datetime(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim().trim_end_matches('Z');
+    if let Ok(datetime) = chrono::NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        return Some(datetime.and_utc());
+    }
+    NaiveDate::parse_from_str(value, "%Y%m%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)).map(|dt| dt.and_utc())
+}
+
+#[cfg(feature = "caldav")]
+fn unescape_ics_text(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n') | Some('N') => text.push('\n'),
+                Some(escaped) => text.push(escaped),
+                None => text.push('\\'),
+            },
+            (c, false) => text.push(c),
+        }
+    }
+    text
+}
+
+#[cfg(feature = "caldav")]
+fn escape_ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Splits a comma-separated TEXT list such as CATEGORIES, honouring "\,".
+#[cfg(feature = "caldav")]
+fn split_ics_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                items.push(unescape_ics_text(&value[start..i]));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(unescape_ics_text(&value[start..]));
+    items.retain(|item| !item.trim().is_empty());
+    items
+}
+
+/// Folds a content line into chunks of at most 75 bytes, each followed by
+/// CRLF and continuation lines starting with a space.
+#[cfg(feature = "caldav")]
+fn fold_ics_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// UUID of the task synced with the VTODO `uid`. Nextcloud and this app use
+/// UUIDs as UIDs; anything else gets a stable UUID derived from it.
+#[cfg(feature = "caldav")]
+fn caldav_uuid(uid: &str) -> Uuid {
+    Uuid::parse_str(uid).unwrap_or_else(|_| Uuid::new_v5(&Uuid::NAMESPACE_URL, uid.as_bytes()))
+}
+
+/// A VTODO as stored on the server. `href` is `None` for one that still has
+/// to be created.
+#[cfg(feature = "caldav")]
+#[derive(Debug, Clone)]
+struct RemoteTodo {
+    href: Option<String>,
+    etag: Option<String>,
+    todo: VTodo,
+}
+
+/// Text content of every element named `name` (in any namespace), in
+/// document order. Good enough for WebDAV multistatus responses, which
+/// don't nest elements of the same name.
+#[cfg(feature = "caldav")]
+fn xml_elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut elements = Vec::new();
+    let mut rest = xml;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[..tag_end];
+        let tag_name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or_default();
+        let local_name = tag_name.rsplit(':').next().unwrap_or_default();
+        if local_name != name || tag.starts_with(['/', '?', '!']) {
+            continue;
+        }
+        rest = &rest[tag_end + 1..];
+        if tag.ends_with('/') {
+            elements.push("");
+            continue;
+        }
+        let close = format!("</{}>", tag_name);
+        let Some(end) = rest.find(&close) else {
+            break;
+        };
+        elements.push(&rest[..end]);
+        rest = &rest[end + close.len()..];
+    }
+    elements
+}
+
+/// Decodes XML character data: entities, character references and CDATA.
+#[cfg(feature = "caldav")]
+fn xml_text(text: &str) -> String {
+    let text = text.trim();
+    if let Some(cdata) = text.strip_prefix("<![CDATA[").and_then(|text| text.strip_suffix("]]>")) {
+        return cdata.to_string();
+    }
+
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semicolon) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..semicolon];
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse()))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                decoded.push(c);
+                rest = &rest[semicolon + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// The VTODOs in a calendar-query REPORT response.
+#[cfg(feature = "caldav")]
+fn parse_multistatus(xml: &str) -> Result<Vec<RemoteTodo>, AppError> {
+    let mut todos = Vec::new();
+    for response in xml_elements(xml, "response") {
+        let Some(data) = xml_elements(response, "calendar-data").first().map(|data| xml_text(data)) else {
+            continue;
+        };
+        if data.is_empty() {
+            continue;
+        }
+        let href = xml_elements(response, "href").first().map(|href| xml_text(href));
+        let todo = VTodo::parse(&data)
+            .map_err(|e| AppError::CalDav(format!("{} ({})", e, href.as_deref().unwrap_or("no href"))))?;
+        todos.push(RemoteTodo {
+            href,
+            etag: xml_elements(response, "getetag").first().map(|etag| xml_text(etag)),
+            todo,
+        });
+    }
+    Ok(todos)
+}
+
+/// Counts for the summary printed after `sync caldav`.
+#[cfg(feature = "caldav")]
+#[derive(Debug, Default)]
+struct CalDavReport {
+    pulled_new: usize,
+    pulled_updated: usize,
+    pushed_new: usize,
+    pushed_updated: usize,
+    conflicts: Vec<MergeConflict>,
+}
+
+#[cfg(feature = "caldav")]
+impl fmt::Display for CalDavReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Pulled {} new and {} updated task(s), pushed {} new and {} updated; {} conflict(s).",
+            self.pulled_new,
+            self.pulled_updated,
+            self.pushed_new,
+            self.pushed_updated,
+            self.conflicts.len()
+        )
+    }
+}
+
+/// What `sync caldav` has to change on each side.
+#[cfg(feature = "caldav")]
+#[derive(Debug, Default)]
+struct CalDavPlan {
+    /// Local tasks to create or replace, matched by UUID.
+    pull: Vec<Task>,
+    /// VTODOs to upload.
+    push: Vec<RemoteTodo>,
+    report: CalDavReport,
+}
+
+/// Decides, per task and VTODO matched by UID, which side is newer by
+/// LAST-MODIFIED (to the second, the precision iCalendar keeps). Changes
+/// on both sides since the list's last sync are reported as conflicts and
+/// the newer one still wins. Tasks deleted locally aren't pulled again;
+/// deletions themselves aren't synced.
+#[cfg(feature = "caldav")]
+fn plan_caldav_sync(username: &str, list: Option<&TaskList>, remote: Vec<RemoteTodo>) -> CalDavPlan {
+    let tasks = list.map(|list| list.tasks.as_slice()).unwrap_or_default();
+    let synced_at = list.and_then(|list| list.caldav_synced_at);
+    let mut plan = CalDavPlan::default();
+    let mut matched = HashSet::new();
+
+    for remote in remote {
+        let uuid = caldav_uuid(&remote.todo.uid);
+        let Some(task) = tasks.iter().find(|task| task.uuid == uuid) else {
+            if list.is_some_and(|list| list.deleted_at(uuid).is_some()) {
+                continue;
+            }
+            let mut task = Task::new(0, String::new());
+            task.uuid = uuid;
+            task.created_at = remote.todo.created.unwrap_or(task.created_at);
+            remote.todo.apply_to(&mut task);
+            plan.pull.push(task);
+            plan.report.pulled_new += 1;
+            continue;
+        };
+        matched.insert(uuid);
+
+        let remote_modified = remote.todo.last_modified.unwrap_or(DateTime::UNIX_EPOCH);
+        if task.last_modified_at.timestamp() == remote_modified.timestamp() {
+            continue;
+        }
+        let both_changed =
+            synced_at.is_some_and(|synced_at| task.last_modified_at > synced_at && remote_modified > synced_at);
+        let conflict = |resolution| MergeConflict {
+            username: username.to_string(),
+            task_id: task.id,
+            description: task.description.clone(),
+            resolution,
+        };
+        if remote_modified.timestamp() > task.last_modified_at.timestamp() {
+            if both_changed {
+                plan.report.conflicts.push(conflict("changed on both sides, kept the newer CalDAV version"));
+            }
+            let mut task = task.clone();
+            remote.todo.apply_to(&mut task);
+            plan.pull.push(task);
+            plan.report.pulled_updated += 1;
+        } else {
+            if both_changed {
+                plan.report.conflicts.push(conflict("changed on both sides, kept the newer local version"));
+            }
+            plan.push.push(RemoteTodo { todo: VTodo::from_task(task, Some(&remote.todo)), ..remote });
+            plan.report.pushed_updated += 1;
+        }
+    }
+
+    for task in tasks.iter().filter(|task| !matched.contains(&task.uuid)) {
+        plan.push.push(RemoteTodo { href: None, etag: None, todo: VTodo::from_task(task, None) });
+        plan.report.pushed_new += 1;
+    }
+    plan
+}
+
+/// The network side of `sync caldav`: fetching and uploading VTODOs.
+#[cfg(feature = "caldav")]
+struct CalDavClient {
+    client: reqwest::blocking::Client,
+    /// The calendar collection, always ending in '/'.
+    url: reqwest::Url,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+#[cfg(feature = "caldav")]
+impl CalDavClient {
+    fn new(config: &Config) -> Result<Self, AppError> {
+        let url = config
+            .caldav_url
+            .as_deref()
+            .ok_or_else(|| AppError::CalDav("no caldav_url set in config.json".to_string()))?;
+        let mut url =
+            reqwest::Url::parse(url).map_err(|e| AppError::CalDav(format!("invalid caldav_url '{}': {}", url, e)))?;
+        if !url.path().ends_with('/') {
+            url.set_path(&format!("{}/", url.path()));
+        }
+        Ok(CalDavClient {
+            client: reqwest::blocking::Client::builder().timeout(CALDAV_TIMEOUT).build()?,
+            url,
+            username: config.caldav_username.clone(),
+            password: match &config.caldav_password {
+                Some(password) => Some(password.clone()),
+                None => secrets::get(&config.data_dir, secrets::CALDAV_PASSWORD)?,
+            },
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, url: reqwest::Url) -> reqwest::blocking::RequestBuilder {
+        let request = self.client.request(method, url);
+        match &self.username {
+            Some(username) => request.basic_auth(username, self.password.as_deref()),
+            None => request,
+        }
+    }
+
+    fn fetch_todos(&self) -> Result<Vec<RemoteTodo>, AppError> {
+        const QUERY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><d:getetag/><c:calendar-data/></d:prop>
+  <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VTODO"/></c:comp-filter></c:filter>
+</c:calendar-query>"#;
+
+        let report = reqwest::Method::from_bytes(b"REPORT").expect("REPORT is a valid method");
+        let response = self
+            .request(report, self.url.clone())
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(QUERY)
+            .send()?
+            .error_for_status()?;
+        parse_multistatus(&response.text()?)
+    }
+
+    /// Creates or updates a VTODO. Updates are conditional on the ETag seen
+    /// when fetching, so a concurrent edit on the server fails the sync
+    /// instead of being overwritten.
+    fn put_todo(&self, remote: &RemoteTodo) -> Result<(), AppError> {
+        let target = match &remote.href {
+            Some(href) => self.url.join(href),
+            None => self.url.join(&format!("{}.ics", remote.todo.uid)),
+        }
+        .map_err(|e| AppError::CalDav(e.to_string()))?;
+
+        let mut request = self
+            .request(reqwest::Method::PUT, target)
+            .header("Content-Type", "text/calendar; charset=utf-8")
+            .body(remote.todo.to_ics());
+        request = match (&remote.href, &remote.etag) {
+            (None, _) => request.header("If-None-Match", "*"),
+            (Some(_), Some(etag)) => request.header("If-Match", etag),
+            (Some(_), None) => request,
+        };
+        request.send()?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Hands out task IDs per user. IDs only ever go up, so a deleted task's ID
+/// is not given to the next new task.
+#[derive(Debug, Default, Clone)]
+struct IdGenerator {
+    next_ids: HashMap<String, u32>,
+}
+
+impl IdGenerator {
+    /// The next ID for `username`; never at or below `max_id`, the highest ID
+    /// currently in use, in case tasks were added without the generator.
+    fn next_id(&mut self, username: &str, max_id: u32) -> u32 {
+        let next = self.next_ids.entry(username.to_string()).or_insert(1);
+        let id = (*next).max(max_id + 1);
+        *next = id + 1;
+        id
+    }
+
+    fn reset(&mut self, task_lists: &[TaskList]) {
+        self.next_ids.clear();
+        for list in task_lists {
+            let max_id = list.tasks.iter().map(|task| task.id).max().unwrap_or(0);
+            let next = self.next_ids.entry(list.username.clone()).or_insert(1);
+            *next = (*next).max(max_id + 1);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct AppData {
+    task_lists: Vec<TaskList>,
+    /// When tasks.json was last saved, by this or any other device.
+    last_synced_at: Option<DateTime<Utc>>,
+    users: HashMap<String, User>,
+    pomodoros: Vec<PomodoroSession>,
+    /// `config` before the logged-in user's settings were applied.
+    #[serde(skip)]
+    file_config: Config,
+    #[serde(skip)]
+    config: Config,
+    /// Set when nothing may be modified or written back to disk.
+    #[serde(skip)]
+    read_only: bool,
+    /// Whether there are changes that haven't been saved yet.
+    #[serde(skip)]
+    dirty: bool,
+    /// Counts changes, so autosaves can tell which of them are on disk.
+    #[serde(skip)]
+    generation: u64,
+    /// Write-ahead journal; `None` when the data wasn't loaded from disk.
+    #[serde(skip)]
+    journal: Option<Journal>,
+    /// tasks.json as last loaded or saved; the common ancestor for `merge`.
+    #[serde(skip)]
+    base_task_lists: Vec<TaskList>,
+    /// Usernames in users.json as last loaded or saved.
+    #[serde(skip)]
+    base_usernames: Vec<String>,
+    /// `None` when tasks.json didn't exist at load time.
+    #[serde(skip)]
+    tasks_stamp: Option<FileStamp>,
+    #[serde(skip)]
+    users_stamp: Option<FileStamp>,
+    #[serde(skip)]
+    pomodoros_stamp: Option<FileStamp>,
+    #[serde(skip)]
+    id_generator: IdGenerator,
+    /// Undo history of unsaved changes, oldest first, at most
+    /// `config.undo_depth` long.
+    #[serde(skip)]
+    undo_stack: VecDeque<UndoStep>,
+    /// Runs only in interactive sessions, see `start_autosave`.
+    #[serde(skip)]
+    autosaver: Option<Autosaver>,
+    #[cfg(feature = "webhooks")]
+    #[serde(skip)]
+    webhooks: Option<WebhookWorker>,
+}
+
+/// A deep copy of the data, e.g. for a snapshot to roll back to. The copy is
+/// detached: it has no journal, autosaver or webhooks, so changing it leaves
+/// no trace until it is saved.
+impl Clone for AppData {
+    fn clone(&self) -> Self {
+        AppData {
+            task_lists: self.task_lists.clone(),
+            last_synced_at: self.last_synced_at,
+            users: self.users.clone(),
+            pomodoros: self.pomodoros.clone(),
+            file_config: self.file_config.clone(),
+            config: self.config.clone(),
+            read_only: self.read_only,
+            dirty: self.dirty,
+            generation: self.generation,
+            journal: None,
+            base_task_lists: self.base_task_lists.clone(),
+            base_usernames: self.base_usernames.clone(),
+            tasks_stamp: self.tasks_stamp.clone(),
+            users_stamp: self.users_stamp.clone(),
+            pomodoros_stamp: self.pomodoros_stamp.clone(),
+            id_generator: self.id_generator.clone(),
+            undo_stack: self.undo_stack.clone(),
+            autosaver: None,
+            #[cfg(feature = "webhooks")]
+            webhooks: None,
+        }
+    }
+}
+
+impl AppData {
+    fn new() -> Self {
+        AppData {
+            task_lists: Vec::new(),
+            last_synced_at: None,
+            users: HashMap::new(),
+            pomodoros: Vec::new(),
+            file_config: Config::default(),
+            config: Config::default(),
+            read_only: false,
+            dirty: false,
+            generation: 0,
+            journal: None,
+            base_task_lists: Vec::new(),
+            base_usernames: Vec::new(),
+            tasks_stamp: None,
+            users_stamp: None,
+            pomodoros_stamp: None,
+            id_generator: IdGenerator::default(),
+            undo_stack: VecDeque::new(),
+            autosaver: None,
+            #[cfg(feature = "webhooks")]
+            webhooks: None,
+        }
+    }
+
+    /// Records `op` in the journal and then applies it. All interactive
+    /// mutations go through here so a crash before saving loses nothing.
+    /// If the op fails, an `Op::Aborted` entry follows it so it isn't
+    /// replayed on the next start. Returns the number of tasks affected.
+    fn execute(&mut self, username: &str, op: Op) -> Result<usize, AppError> {
+        if self.read_only {
+            return Err(AppError::ReadOnly);
+        }
+        let entry = JournalEntry { op: op.clone(), user: username.to_string(), timestamp: Utc::now() };
+        if let Some(journal) = &self.journal {
+            journal.append(&entry)?;
+        }
+
+        #[cfg(feature = "webhooks")]
+        let removed: Vec<Task> = match &op {
+            Op::RemoveTask { task_id } => {
+                self.tasks(username).iter().filter(|task| task.id == *task_id).cloned().collect()
+            }
+            Op::RemoveTasks { task_ids } => {
+                self.tasks(username).iter().filter(|task| task_ids.contains(&task.id)).cloned().collect()
+            }
+            _ => Vec::new(),
+        };
+        #[cfg(feature = "webhooks")]
+        let event = match &op {
+            Op::AddTask { .. } => Some(("task_added", None)),
+            Op::MarkCompleted { task_id } => Some(("task_completed", Some(*task_id))),
+            _ => None,
+        };
+
+        let affected = match self.apply_recording_undo(username, op) {
+            Ok(affected) => affected,
+            Err(e) => {
+                if let Some(journal) = &self.journal {
+                    // Should this fail too, replaying the op on the next
+                    // start fails the same way and is skipped with a warning.
+                    let _ = journal.append(&JournalEntry { op: Op::Aborted, ..entry });
+                }
+                return Err(e);
+            }
+        };
+        self.mark_dirty();
+        if let Some(autosaver) = &self.autosaver {
+            autosaver.send(AutosaveSnapshot {
+                task_lists: self.task_lists.clone(),
+                users: self.users.clone(),
+                pomodoros: self.pomodoros.clone(),
+                generation: self.generation,
+                taken_at: SystemTime::now(),
+            });
+        }
+
+        #[cfg(feature = "webhooks")]
+        if let Some(webhooks) = &self.webhooks {
+            let tasks = self.tasks(username);
+            let mut events: Vec<(&'static str, Task)> =
+                removed.into_iter().map(|task| ("task_removed", task)).collect();
+            let task = match event {
+                Some((_, Some(task_id))) => tasks.iter().find(|task| task.id == task_id),
+                Some((_, None)) => tasks.last(),
+                None => None,
+            };
+            if let (Some((event, _)), Some(task)) = (event, task) {
+                events.push((event, task.clone()));
+            }
+            for (event, task) in events {
+                webhooks.send(WebhookEvent { event, username: username.to_string(), task });
+            }
+        }
+        Ok(affected)
+    }
+
+    /// `apply_op`, keeping the user's task list from before it on the undo
+    /// stack. Journal replay goes through here too, so a journaled `Undo`
+    /// finds the same history it had when it was recorded.
+    fn apply_recording_undo(&mut self, username: &str, op: Op) -> Result<usize, AppError> {
+        let step = op.is_undoable().then(|| UndoStep {
+            username: username.to_string(),
+            list: self.task_lists.iter().find(|list| list.username == username).cloned(),
+        });
+        let clears_undo = op.clears_undo();
+
+        let affected = self.apply_op(username, op)?;
+        if clears_undo {
+            self.undo_stack.clear();
+        }
+        if let Some(step) = step {
+            self.undo_stack.push_back(step);
+            while self.undo_stack.len() > self.config.undo_depth {
+                self.undo_stack.pop_front();
+            }
+        }
+        Ok(affected)
+    }
+
+    /// Puts back the user's task list from before their latest undoable op.
+    fn undo(&mut self, username: &str) -> Result<(), AppError> {
+        let step = self
+            .undo_stack
+            .iter()
+            .rposition(|step| step.username == username)
+            .and_then(|index| self.undo_stack.remove(index))
+            .ok_or(AppError::NothingToUndo)?;
+        self.task_lists.retain(|list| list.username != username);
+        self.task_lists.extend(step.list);
+        Ok(())
+    }
+
+    fn apply_op(&mut self, username: &str, op: Op) -> Result<usize, AppError> {
+        match op {
+            Op::AddUser { user } => {
+                if self.users.contains_key(&user.username) {
+                    return Err(AppError::UserExists);
+                }
+                self.users.insert(user.username.clone(), user);
+            }
+            Op::SetWorkflow { statuses } => self.set_workflow(username, statuses)?,
+            Op::SetStatus { task_id, status } => self.set_status(username, task_id, &status)?,
+            Op::StepStatus { task_id, forward } => self.step_status(username, task_id, forward)?,
+            Op::ChangePassword { password } => match self.users.get_mut(username) {
+                Some(user) => user.password = password,
+                None => return Err(AppError::AuthenticationFailed),
+            },
+            Op::SetUserSetting { key, value } => self.set_user_setting(username, &key, value.as_deref())?,
+            Op::AddTask { description } => self.add_task_force(username, description)?,
+            Op::RemoveTask { task_id } => self.remove_task(username, task_id)?,
+            Op::RestoreFromTrash { task_id } => self.restore_from_trash(username, task_id)?,
+            Op::EmptyTrash => return Ok(self.empty_trash(username)),
+            Op::RemoveTasks { task_ids } => {
+                let count = task_ids.len();
+                self.apply(ChangePlan { username: username.to_string(), remove: task_ids });
+                return Ok(count);
+            }
+            Op::EditTask { task_id, description, expected_version } => {
+                self.edit_task(username, task_id, description, expected_version)?
+            }
+            Op::MarkCompleted { task_id } => self.mark_completed(username, task_id)?,
+            Op::MarkInProgress { task_id } => self.mark_in_progress(username, task_id),
+            Op::Reopen { task_id } => self.reopen(username, task_id),
+            Op::LockTask { task_id } => self.lock_task(username, task_id),
+            Op::UnlockTask { task_id } => self.unlock_task(username, task_id),
+            Op::SetProgress { task_id, progress } => self.set_progress(username, task_id, progress),
+            Op::SetDueDate { task_id, due_date } => self.set_due_date(username, task_id, due_date),
+            Op::AddTag { task_id, tag } => self.add_tag(username, task_id, &tag)?,
+            Op::SetPriority { task_id, priority } => self.set_task_priority(username, task_id, priority),
+            Op::SetLink { task_id, link: Some(link) } => self.set_link(username, task_id, link)?,
+            Op::SetLink { task_id, link: None } => self.clear_link(username, task_id),
+            Op::SetStoryPoints { task_id, points } => self.set_story_points(username, task_id, points),
+            Op::SetSprint { task_id, sprint } => self.set_sprint(username, task_id, sprint),
+            Op::SetMilestone { task_id, milestone } => self.set_milestone(username, task_id, milestone),
+            Op::SetContext { task_id, context } => self.set_context(username, task_id, context)?,
+            Op::SetColorTag { task_id, color_tag } => self.set_color_tag(username, task_id, color_tag),
+            Op::AddDependency { task_id, depends_on } => self.add_dependency(username, task_id, depends_on)?,
+            Op::RemoveDependency { task_id, depends_on } => self.remove_dependency(username, task_id, depends_on),
+            Op::BulkSetTag { filter, tag } => return self.bulk_set_tag(username, &filter, &tag),
+            Op::BulkRemoveTag { filter, tag } => return Ok(self.bulk_remove_tag(username, &filter, &tag)),
+            Op::SetPriorityMatching { query, priority } => {
+                return Ok(self.set_priority_matching(username, &query, priority))
+            }
+            Op::RecordPomodoro { session } => self.pomodoros.push(session),
+            Op::ImportTasks { tasks } => return self.add_imported_tasks(username, tasks),
+            Op::BulkAssignSprint { filter, sprint } => return Ok(self.bulk_assign_sprint(username, &filter, sprint)),
+            Op::RenumberByDue => return Ok(self.renumber_by_due(username)),
+            Op::SmartSchedule { tasks_per_day, start_date, skip_weekends } => {
+                return self.smart_schedule(username, tasks_per_day, start_date, skip_weekends)
+            }
+            Op::CompleteOverdue { today } => return Ok(self.complete_overdue(username, today)),
+            Op::RebalancePriorities => return Ok(self.rebalance_priorities(username).len()),
+            Op::FindReplace { find, replace } => return Ok(self.find_replace(username, &find, &replace)),
+            Op::EscalateOverdue { now } => return Ok(self.escalate_overdue(username, now)),
+            Op::ArchiveStale { older_than_days, now } => return Ok(self.archive_stale(username, older_than_days, now)),
+            Op::CalDavPull { tasks, synced_at } => return self.pull_caldav_tasks(username, tasks, synced_at),
+            Op::ImportUser { user, task_lists, decision } => return self.import_user(user, task_lists, decision),
+            Op::MergeUsers { keep, merge_from } => return Ok(self.move_user_data(&keep, &merge_from)),
+            Op::ApplyReview { decisions } => return self.apply_review(username, decisions),
+            Op::Undo => self.undo(username)?,
+            Op::Aborted => return Ok(0),
+        }
+        Ok(1)
+    }
+
+    /// Re-applies journal entries that never made it into a save.
+    fn replay_journal(&mut self) -> Result<usize, AppError> {
+        let Some(journal) = &self.journal else {
+            return Ok(0);
+        };
+
+        let snapshot = fs::metadata(self.tasks_path())
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let entries = journal.entries_since(snapshot)?;
+        for entry in &entries {
+            if let Err(e) = self.apply_recording_undo(&entry.user, entry.op.clone()) {
+                println!("Warning: could not replay journaled change: {}", e);
+            }
+        }
+        if !entries.is_empty() {
+            self.dirty = true;
+        }
+        Ok(entries.len())
+    }
+
+    fn mark_dirty(&mut self) {
+        if !self.read_only {
+            self.dirty = true;
+            self.generation += 1;
+        }
+    }
+
+    /// Adds a task without looking for near-duplicates; the menu asks about
+    /// those before journaling the op (see `confirm_add_task`).
+    fn add_task_force(&mut self, username: &str, description: String) -> Result<(), AppError> {
+        let max_tasks = self.config.max_tasks;
+        let task_list = self.task_lists.iter_mut().find(|list| list.username == username);
+        match task_list {
+            Some(list) => {
+                if list.tasks.len() >= max_tasks {
+                    return Err(AppError::TaskLimitReached(max_tasks));
+                }
+                let max_id = list.tasks.iter().map(|task| task.id).max().unwrap_or(0);
+                let id = self.id_generator.next_id(username, max_id);
+                list.tasks.push(Task::new(id, description));
+            },
+            None => {
+                let task_list = TaskList {
+                    username: username.to_string(),
+                    name: self.default_list_for(username),
+                    shared_with: Vec::new(),
+                    tasks: vec![Task::new(self.id_generator.next_id(username, 0), description)],
+                    deleted: Vec::new(),
+                    trash: Vec::new(),
+                    archived: Vec::new(),
+                    caldav_synced_at: None,
+                };
+                self.task_lists.push(task_list);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Up to 5 distinct descriptions of the user's tasks, archived and
+    /// trashed ones included, that start with `prefix` ignoring case, newest
+    /// first.
+    fn autocomplete_description(&self, username: &str, prefix: &str) -> Vec<String> {
+        let prefix = prefix.to_lowercase();
+        let mut matches: Vec<&Task> = self
+            .task_lists
+            .iter()
+            .filter(|list| list.username == username)
+            .flat_map(|list| list.tasks.iter().chain(&list.archived).chain(&list.trash))
+            .filter(|task| task.description.to_lowercase().starts_with(&prefix))
+            .collect();
+        matches.sort_by_key(|task| Reverse(task.created_at));
+
+        let mut suggestions: Vec<String> = Vec::new();
+        for task in matches {
+            if !suggestions.contains(&task.description) {
+                suggestions.push(task.description.clone());
+            }
+            if suggestions.len() == 5 {
+                break;
+            }
+        }
+        suggestions
+    }
+
+    /// The user's tasks whose description scores at least `threshold` against
+    /// `description` (Jaro-Winkler, ignoring case and accents), most similar
+    /// first.
+    fn find_similar_tasks<'a>(&'a self, username: &str, description: &str, threshold: f64) -> Vec<&'a Task> {
+        let collation = self.config.collation();
+        let description = collation.fold(description);
+        let mut similar: Vec<(f64, &Task)> = self
+            .tasks(username)
+            .iter()
+            .map(|task| (strsim::jaro_winkler(&collation.fold(&task.description), &description), task))
+            .filter(|(score, _)| *score >= threshold)
+            .collect();
+        similar.sort_by(|a, b| b.0.total_cmp(&a.0));
+        similar.into_iter().map(|(_, task)| task).collect()
+    }
+
+    /// Rebuilds derived in-memory state (the task ID generator) from the task
+    /// lists, e.g. after loading or repairing data, and returns any integrity
+    /// problems found. The problems are only reported, nothing is fixed.
+    fn reindex(&mut self) -> Vec<String> {
+        self.id_generator.reset(&self.task_lists);
+        self.validate_integrity()
+    }
+
+    /// Looks for duplicate task IDs, dependencies on missing tasks and lists
+    /// owned by unknown users.
+    fn validate_integrity(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for list in &self.task_lists {
+            if !self.users.contains_key(&list.username) {
+                problems.push(format!("list '{}' belongs to unknown user '{}'", list.name, list.username));
+            }
+            for (i, task) in list.tasks.iter().enumerate() {
+                if list.tasks[..i].iter().any(|other| other.id == task.id) {
+                    problems.push(format!("{}: task ID #{} is used more than once", list.username, task.id));
+                }
+                for id in task.depends_on.iter().filter(|id| !list.tasks.iter().any(|other| other.id == **id)) {
+                    problems.push(format!("{}: task #{} depends on missing task #{}", list.username, task.id, id));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Appends tasks from an external source, giving them fresh IDs. Tasks
+    /// whose UUID is already present are skipped. Returns how many were added.
+    fn add_imported_tasks(&mut self, username: &str, tasks: Vec<Task>) -> Result<usize, AppError> {
+        let max_tasks = self.config.max_tasks;
+        if !self.task_lists.iter().any(|list| list.username == username) {
+            self.task_lists.push(TaskList {
+                username: username.to_string(),
+                name: self.default_list_for(username),
+                shared_with: Vec::new(),
+                tasks: Vec::new(),
+                deleted: Vec::new(),
+                trash: Vec::new(),
+                archived: Vec::new(),
+                caldav_synced_at: None,
+            });
+        }
+        let list = self.task_lists.iter_mut().find(|list| list.username == username).unwrap();
+
+        let max_id = list.tasks.iter().map(|task| task.id).max().unwrap_or(0);
+        let mut added = 0;
+        for mut task in tasks {
+            if list.tasks.iter().any(|existing| existing.uuid == task.uuid) {
+                continue;
+            }
+            if list.tasks.len() >= max_tasks {
+                return Err(AppError::TaskLimitReached(max_tasks));
+            }
+            task.id = self.id_generator.next_id(username, max_id);
+            list.tasks.push(task);
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    /// Replaces the user's tasks that share a UUID with one in `tasks` (keeping
+    /// their IDs) and adds the rest, then records `synced_at` on the list.
+    fn pull_caldav_tasks(&mut self, username: &str, tasks: Vec<Task>, synced_at: DateTime<Utc>) -> Result<usize, AppError> {
+        let max_tasks = self.config.max_tasks;
+        if !self.task_lists.iter().any(|list| list.username == username) {
+            self.task_lists.push(TaskList {
+                username: username.to_string(),
+                name: self.default_list_for(username),
+                shared_with: Vec::new(),
+                tasks: Vec::new(),
+                deleted: Vec::new(),
+                trash: Vec::new(),
+                archived: Vec::new(),
+                caldav_synced_at: None,
+            });
+        }
+        let list = self.task_lists.iter_mut().find(|list| list.username == username).unwrap();
+
+        let pulled = tasks.len();
+        for mut task in tasks {
+            if let Some(existing) = list.tasks.iter_mut().find(|existing| existing.uuid == task.uuid) {
+                task.id = existing.id;
+                *existing = task;
+                continue;
+            }
+            if list.tasks.len() >= max_tasks {
+                return Err(AppError::TaskLimitReached(max_tasks));
+            }
+            let max_id = list.tasks.iter().map(|task| task.id).max().unwrap_or(0);
+            task.id = self.id_generator.next_id(username, max_id);
+            list.tasks.push(task);
+        }
+        list.caldav_synced_at = Some(synced_at);
+        Ok(pulled)
+    }
+
+    /// Two-way sync of the user's tasks with the configured CalDAV calendar.
+    /// Local changes are pushed first, so a failed upload leaves the local
+    /// data untouched and the next sync simply tries again.
+    #[cfg(feature = "caldav")]
+    fn sync_caldav(&mut self, username: &str) -> Result<CalDavReport, AppError> {
+        let synced_at = Utc::now();
+        let client = CalDavClient::new(&self.config)?;
+        let remote = client.fetch_todos()?;
+        let list = self.task_lists.iter().find(|list| list.username == username);
+        let plan = plan_caldav_sync(username, list, remote);
+
+        for todo in &plan.push {
+            client.put_todo(todo)?;
+        }
+        self.execute(username, Op::CalDavPull { tasks: plan.pull, synced_at })?;
+        Ok(plan.report)
+    }
+
+    /// Adds a user exported from another data directory along with their task
+    /// lists. `decision` only matters when the username is already taken.
+    fn import_user(&mut self, user: User, task_lists: Vec<TaskList>, decision: ImportDecision) -> Result<usize, AppError> {
+        let username = user.username.clone();
+        let exists = self.users.contains_key(&username);
+        match decision {
+            ImportDecision::Skip if exists => Ok(0),
+            ImportDecision::Merge if exists => {
+                let tasks = task_lists.into_iter().flat_map(|list| list.tasks).collect();
+                self.add_imported_tasks(&username, tasks)
+            }
+            _ => {
+                self.users.insert(username.clone(), user);
+                self.task_lists.retain(|list| list.username != username);
+                let imported = task_lists.iter().map(|list| list.tasks.len()).sum();
+                self.task_lists.extend(task_lists.into_iter().map(|list| TaskList { username: username.clone(), ..list }));
+                Ok(imported)
+            }
+        }
+    }
+
+    /// Folds the account `merge_from` into `keep`, e.g. after a typo at
+    /// registration: tasks and Pomodoro sessions move over, email and display
+    /// name are copied if `keep` has none, lists shared with `merge_from` are
+    /// shared with `keep` instead, and `merge_from` is deleted. Admins only.
+    fn merge_users(&mut self, admin: &str, keep_username: &str, merge_from_username: &str) -> Result<MergeReport, AppError> {
+        self.ensure_admin(admin)?;
+        for username in [keep_username, merge_from_username] {
+            if !self.users.contains_key(username) {
+                return Err(AppError::UserNotFound(username.to_string()));
+            }
+        }
+        if keep_username == merge_from_username {
+            return Ok(MergeReport::default());
+        }
+
+        let report = MergeReport {
+            tasks_moved: self
+                .task_lists
+                .iter()
+                .filter(|list| list.username == merge_from_username)
+                .map(|list| list.tasks.len())
+                .sum(),
+            sessions_merged: self.pomodoro_sessions(merge_from_username).count(),
+        };
+        let op = Op::MergeUsers {
+            keep: keep_username.to_string(),
+            merge_from: merge_from_username.to_string(),
+        };
+        self.execute(admin, op)?;
+        Ok(report)
+    }
+
+    /// The unchecked part of `merge_users`. Returns the number of tasks moved.
+    fn move_user_data(&mut self, keep: &str, merge_from: &str) -> usize {
+        let Some(from) = self.users.remove(merge_from) else {
+            return 0;
+        };
+        if let Some(user) = self.users.get_mut(keep) {
+            user.email = user.email.take().or(from.email);
+            user.display_name = user.display_name.take().or(from.display_name);
+        }
+
+        let mut moved = 0;
+        let mut renumbered: HashMap<u32, u32> = HashMap::new();
+        let (from_lists, lists): (Vec<TaskList>, Vec<TaskList>) =
+            std::mem::take(&mut self.task_lists).into_iter().partition(|list| list.username == merge_from);
+        self.task_lists = lists;
+        for mut from_list in from_lists {
+            moved += from_list.tasks.len();
+            from_list.shared_with.retain(|username| username != keep);
+            let existing = self.task_lists.iter_mut().find(|list| list.username == keep && list.name == from_list.name);
+            let Some(list) = existing else {
+                from_list.username = keep.to_string();
+                self.task_lists.push(from_list);
+                continue;
+            };
+
+            // Renumber after the existing tasks, keeping dependencies pointing
+            // at the same tasks.
+            let max_id = list.tasks.iter().map(|task| task.id).max().unwrap_or(0);
+            let new_ids: HashMap<u32, u32> = from_list
+                .tasks
+                .iter()
+                .map(|task| (task.id, self.id_generator.next_id(keep, max_id)))
+                .collect();
+            for mut task in from_list.tasks {
+                task.id = new_ids[&task.id];
+                task.depends_on = task.depends_on.iter().filter_map(|id| new_ids.get(id).copied()).collect();
+                list.tasks.push(task);
+            }
+            renumbered.extend(new_ids);
+        }
+
+        for list in &mut self.task_lists {
+            if list.shared_with.iter().any(|username| username == merge_from) {
+                list.shared_with.retain(|username| username != merge_from && username != keep);
+                if list.username != keep {
+                    list.shared_with.push(keep.to_string());
+                }
+            }
+        }
+        for session in self.pomodoros.iter_mut().filter(|session| session.username == merge_from) {
+            session.username = keep.to_string();
+            if let Some(&task_id) = renumbered.get(&session.task_id) {
+                session.task_id = task_id;
+            }
+        }
+        moved
+    }
+
+    /// Gives open tasks without a due date one, `tasks_per_day` at a time on
+    /// consecutive days from `start_date`, highest priority and then oldest
+    /// first. Saturdays and Sundays are passed over if `skip_weekends`.
+    /// Returns the number of tasks scheduled.
+    fn smart_schedule(
+        &mut self,
+        username: &str,
+        tasks_per_day: usize,
+        start_date: NaiveDate,
+        skip_weekends: bool,
+    ) -> Result<usize, AppError> {
+        if tasks_per_day == 0 {
+            return Err(AppError::InvalidSchedule("tasks per day must be greater than 0".to_string()));
+        }
+
+        let mut unscheduled: Vec<&mut Task> =
+            self.tasks_mut(username).iter_mut().filter(|task| !task.completed && task.due_date.is_none()).collect();
+        unscheduled.sort_by_key(|task| (Reverse(task.priority), task.created_at));
+
+        let workday = |mut date: NaiveDate| {
+            while skip_weekends && matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+                date = date.succ_opt()?;
+            }
+            Some(date)
+        };
+        let mut date = workday(start_date);
+        let count = unscheduled.len();
+        for (index, task) in unscheduled.into_iter().enumerate() {
+            if index > 0 && index % tasks_per_day == 0 {
+                date = date.and_then(|date| date.succ_opt()).and_then(workday);
+            }
+            let Some(date) = date else {
+                return Err(AppError::InvalidSchedule("ran past the last representable date".to_string()));
+            };
+            task.due_date = Some(date);
+            task.last_modified_at = Utc::now();
+        }
+        Ok(count)
+    }
+
+    /// Orders the user's tasks by due date, tasks without one last, and
+    /// renumbers them 1, 2, 3... in that order. Dependencies and Pomodoro
+    /// sessions are updated to the new IDs. Returns the number of tasks
+    /// whose ID changed.
+    fn renumber_by_due(&mut self, username: &str) -> usize {
+        let Some(list) = self.task_lists.iter_mut().find(|list| list.username == username) else {
+            return 0;
+        };
+
+        // Stable, so tasks due the same day keep their relative order.
+        list.tasks.sort_by_key(|task| (task.due_date.is_none(), task.due_date));
+        let new_ids: HashMap<u32, u32> = list.tasks.iter().zip(1..).map(|(task, new_id)| (task.id, new_id)).collect();
+        let mut changed = 0;
+        for (task, new_id) in list.tasks.iter_mut().zip(1..) {
+            if task.id != new_id {
+                task.id = new_id;
+                changed += 1;
+            }
+            task.depends_on = task.depends_on.iter().filter_map(|id| new_ids.get(id).copied()).collect();
+        }
+        for session in self.pomodoros.iter_mut().filter(|session| session.username == username) {
+            if let Some(new_id) = new_ids.get(&session.task_id) {
+                session.task_id = *new_id;
+            }
+        }
+        self.id_generator.reset(&self.task_lists);
+        changed
+    }
+
+    /// Appends the tasks from every `*.json` file in `dir`, each holding an
+    /// array of tasks, to the user's list. Files that can't be read or parsed
+    /// are skipped with a warning. Tasks get new IDs; ones whose UUID is
+    /// already in the list are skipped. Returns the number of tasks added.
+    fn batch_import_from_dir(&mut self, dir: &Path, username: &str) -> Result<usize, AppError> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+
+        let mut tasks = Vec::new();
+        for path in paths {
+            let parsed = fs::read(&path)
+                .map_err(AppError::from)
+                .and_then(|bytes| parse_json_file::<Vec<Task>>(&path, &bytes));
+            match parsed {
+                Ok(file_tasks) => tasks.extend(file_tasks),
+                Err(e) => println!("Warning: skipping {}: {}", path.display(), e),
+            }
+        }
+        if tasks.is_empty() {
+            return Ok(0);
+        }
+        self.execute(username, Op::ImportTasks { tasks })
+    }
+
+    /// Adds a task for every `- [ ]` (pending) and `- [x]` (completed) line
+    /// of a Markdown file; everything else is ignored. Returns the number of
+    /// tasks added.
+    fn import_markdown(&mut self, username: &str, path: &Path) -> Result<usize, AppError> {
+        let tasks = parse_markdown_checklist(&fs::read_to_string(path)?);
+        if tasks.is_empty() {
+            return Ok(0);
+        }
+        self.execute(username, Op::ImportTasks { tasks })
+    }
+
+    /// Imports the issues of a GitHub repository as tasks: title becomes the
+    /// description, labels become tags, closed issues are completed and the
+    /// milestone's due date becomes the task's due date. Pull requests are
+    /// skipped. Re-importing the same issue doesn't duplicate it.
+    #[cfg(feature = "import")]
+    fn import_github_issues(&mut self, username: &str, owner: &str, repo: &str, token: &str) -> Result<usize, AppError> {
+        self.import_github_issues_from(GITHUB_API_URL, username, owner, repo, token)
+    }
+
+    #[cfg(feature = "import")]
+    fn import_github_issues_from(
+        &mut self,
+        api_url: &str,
+        username: &str,
+        owner: &str,
+        repo: &str,
+        token: &str,
+    ) -> Result<usize, AppError> {
+        let issues: Vec<GithubIssue> = reqwest::blocking::Client::new()
+            .get(format!("{}/repos/{}/{}/issues", api_url, owner, repo))
+            .query(&[("state", "all"), ("per_page", "100")])
+            .bearer_auth(token)
+            .header(reqwest::header::USER_AGENT, "Lab_3")
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        let tasks = issues
+            .into_iter()
+            .filter(|issue| issue.pull_request.is_none())
+            .map(|issue| {
+                let mut task = Task::new(0, issue.title);
+                // Derived from the issue URL so re-imports are recognised.
+                task.uuid = Uuid::new_v5(&Uuid::NAMESPACE_URL, issue.html_url.as_bytes());
+                task.tags = issue.labels.into_iter().map(|label| label.name).collect();
+                task.due_date = issue.milestone.as_ref().and_then(|m| m.due_on).map(|due| due.date_naive());
+                task.milestone = issue.milestone.map(|m| m.title);
+                if issue.state == "closed" {
+                    task.completed = true;
+                    task.completed_at = issue.closed_at.or(Some(Utc::now()));
+                }
+                task
+            })
+            .collect();
+
+        self.execute(username, Op::ImportTasks { tasks })
+    }
+
+    /// Plans deleting the given tasks, failing if any of them doesn't exist.
+    fn plan_remove(&self, username: &str, task_ids: &[u32]) -> Result<ChangePlan, AppError> {
+        let tasks = self.tasks(username);
+        let mut remove = Vec::new();
+        for &task_id in task_ids {
+            match tasks.iter().find(|task| task.id == task_id) {
+                None => return Err(AppError::TaskNotFound(task_id.to_string())),
+                Some(task) if task.locked => return Err(AppError::TaskLocked(task_id)),
+                Some(_) => {}
+            }
+            if !remove.contains(&task_id) {
+                remove.push(task_id);
+            }
+        }
+        Ok(ChangePlan { username: username.to_string(), remove })
+    }
+
+    /// Plans deleting every completed task that isn't locked.
+    fn plan_clear_completed(&self, username: &str) -> ChangePlan {
+        let remove =
+            self.tasks(username).iter().filter(|task| task.completed && !task.locked).map(|task| task.id).collect();
+        ChangePlan { username: username.to_string(), remove }
+    }
+
+    fn apply(&mut self, plan: ChangePlan) {
+        if let Some(list) = self.task_lists.iter_mut().find(|list| list.username == plan.username) {
+            list.remove_tasks(&plan.remove);
+        }
+        self.mark_dirty();
+    }
+
+    fn remove_task(&mut self, username: &str, task_id: u32) -> Result<(), AppError> {
+        self.ensure_unlocked(username, task_id)?;
+        if let Some(list) = self.task_lists.iter_mut().find(|list| list.username == username) {
+            list.remove_tasks(&[task_id]);
+        }
+        Ok(())
+    }
+
+    /// Moves the user's tasks that were last changed more than
+    /// `older_than_days` days before `now` out of the list and into its
+    /// archive. Locked tasks stay. Returns the number of tasks archived.
+    fn archive_stale(&mut self, username: &str, older_than_days: u32, now: DateTime<Utc>) -> usize {
+        let stale = self.archivable(username, older_than_days, now);
+        let Some(list) = self.task_lists.iter_mut().find(|list| list.username == username) else {
+            return 0;
+        };
+        let archived = list.take_tasks(&stale);
+        let count = archived.len();
+        list.archived.extend(archived);
+        count
+    }
+
+    /// IDs of the tasks `archive_stale` would archive.
+    fn archivable(&self, username: &str, older_than_days: u32, now: DateTime<Utc>) -> Vec<u32> {
+        self.tasks(username)
+            .iter()
+            .filter(|task| !task.locked && (now - task.last_modified_at).num_days() > i64::from(older_than_days))
+            .map(|task| task.id)
+            .collect()
+    }
+
+    fn list_archived(&self, username: &str) -> &[Task] {
+        self.task_lists
+            .iter()
+            .find(|list| list.username == username)
+            .map(|list| list.archived.as_slice())
+            .unwrap_or(&[])
+    }
+
+    fn list_trash(&self, username: &str) -> &[Task] {
+        self.task_lists
+            .iter()
+            .find(|list| list.username == username)
+            .map(|list| list.trash.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Puts a trashed task back in the list. It gets a new ID if its old one
+    /// has been reused meanwhile, and dependencies on tasks that no longer
+    /// exist are dropped.
+    fn restore_from_trash(&mut self, username: &str, task_id: u32) -> Result<(), AppError> {
+        let not_found = || AppError::TaskNotFound(task_id.to_string());
+        let list = self.task_lists.iter_mut().find(|list| list.username == username).ok_or_else(not_found)?;
+        let index = list.trash.iter().rposition(|task| task.id == task_id).ok_or_else(not_found)?;
+
+        let mut task = list.trash.remove(index);
+        let max_id = list.tasks.iter().map(|task| task.id).max().unwrap_or(0);
+        if list.tasks.iter().any(|other| other.id == task.id) {
+            task.id = self.id_generator.next_id(username, max_id);
+        }
+        task.depends_on.retain(|id| list.tasks.iter().any(|other| other.id == *id));
+        task.last_modified_at = Utc::now();
+        list.deleted.retain(|tombstone| tombstone.uuid != task.uuid);
+        list.tasks.push(task);
+        Ok(())
+    }
+
+    /// Permanently deletes everything in the user's trash. Returns how many
+    /// tasks were deleted.
+    fn empty_trash(&mut self, username: &str) -> usize {
+        self.task_lists
+            .iter_mut()
+            .filter(|list| list.username == username)
+            .map(|list| std::mem::take(&mut list.trash).len())
+            .sum()
+    }
+
+    /// Records that `task_id` can't be done before `depends_on_id`. Rejects
+    /// unknown tasks, self-references and anything that would form a cycle.
+    fn add_dependency(&mut self, username: &str, task_id: u32, depends_on_id: u32) -> Result<(), AppError> {
+        let tasks = self.tasks(username);
+        if !tasks.iter().any(|task| task.id == depends_on_id) {
+            return Err(AppError::TaskNotFound(depends_on_id.to_string()));
+        }
+        if task_id == depends_on_id {
+            return Err(AppError::InvalidDependency("a task cannot depend on itself".to_string()));
+        }
+        if self.depends_transitively(username, depends_on_id, task_id) {
+            return Err(AppError::InvalidDependency(format!(
+                "#{} already depends on #{}",
+                depends_on_id, task_id
+            )));
+        }
+
+        match self.task_mut(username, task_id) {
+            Some(task) => {
+                if !task.depends_on.contains(&depends_on_id) {
+                    task.depends_on.push(depends_on_id);
+                }
+                Ok(())
+            }
+            None => Err(AppError::TaskNotFound(task_id.to_string())),
+        }
+    }
+
+    fn remove_dependency(&mut self, username: &str, task_id: u32, depends_on_id: u32) {
+        if let Some(task) = self.task_mut(username, task_id) {
+            task.depends_on.retain(|&id| id != depends_on_id);
+        }
+    }
+
+    /// One task as pretty-printed JSON, in the same shape as in tasks.json.
+    fn task_json(&self, username: &str, task_id: u32) -> Result<String, AppError> {
+        let task = self.task(username, task_id).ok_or_else(|| AppError::TaskNotFound(task_id.to_string()))?;
+        Ok(serde_json::to_string_pretty(task)?)
+    }
+
+    /// Whether `from` depends on `to` directly or through other tasks.
+    fn depends_transitively(&self, username: &str, from: u32, to: u32) -> bool {
+        let tasks = self.tasks(username);
+        let mut stack = vec![from];
+        let mut visited = Vec::new();
+        while let Some(id) = stack.pop() {
+            if id == to {
+                return true;
+            }
+            if visited.contains(&id) {
+                continue;
+            }
+            visited.push(id);
+            if let Some(task) = tasks.iter().find(|task| task.id == id) {
+                stack.extend(&task.depends_on);
+            }
+        }
+        false
+    }
+
+    /// Writes everything known about one task, one field per line. Fields
+    /// that aren't set are left out.
+    fn get_task_detail(&self, username: &str, task_id: u32, out: &mut dyn Write) -> Result<(), AppError> {
+        let task = self.task(username, task_id).ok_or_else(|| AppError::TaskNotFound(task_id.to_string()))?;
+        write!(out, "{}", self.format_task_detail(username, task, Utc::now()))?;
+        Ok(())
+    }
+
+    /// The lines `get_task_detail` prints. Timestamps read like "3 days ago
+    /// (2024-03-05 14:30 UTC)", relative to `now`.
+    fn format_task_detail(&self, username: &str, task: &Task, now: DateTime<Utc>) -> String {
+        let timestamp =
+            |at: DateTime<Utc>| format!("{} ({})", format_elapsed(now - at), self.config.format_timestamp(at));
+        let mut lines = vec![format!("#{} {}", task.id, task.description), format!("  UUID:       {}", task.uuid)];
+        let workflow = self.workflow(username);
+        let step = &workflow[workflow_position(task, &workflow)].name;
+        let status = match task.status() {
+            Status::InProgress => format!("{} ({}%)", step, task.progress),
+            _ => step.clone(),
+        };
+        lines.push(format!("  Status:     {}", status));
+        if task.locked {
+            lines.push("  Locked:     yes".to_string());
+        }
+        match effective_priority(task, now, &self.config) {
+            priority if priority != task.priority => lines.push(format!(
+                "  Priority:   {} (raised from {}, due soon)",
+                priority.label(),
+                task.priority.label()
+            )),
+            priority => lines.push(format!("  Priority:   {}", priority.label())),
+        }
+        if let Some(escalated_at) = task.escalated_at {
+            lines.push(format!("  Escalated:  {}", timestamp(escalated_at)));
+        }
+        if !task.tags.is_empty() {
+            lines.push(format!("  Tags:       {}", task.tags.join(", ")));
+        }
+        if let Some(due_date) = task.due_date {
+            lines.push(format!("  Due:        {}", due_date));
+        }
+        if let Some(points) = task.estimated_story_points {
+            lines.push(format!("  Estimate:   {} story point(s)", points));
+        }
+        if let Some(sprint) = &task.sprint {
+            lines.push(format!("  Sprint:     {}", sprint));
+        }
+        if let Some(milestone) = &task.milestone {
+            lines.push(format!("  Milestone:  {}", milestone));
+        }
+        if let Some(context) = &task.context {
+            lines.push(format!("  Context:    {}", context));
+        }
+        if let Some(link) = &task.link {
+            lines.push(format!("  Link:       {}", link));
+        }
+        if !task.depends_on.is_empty() {
+            let ids: Vec<String> = task.depends_on.iter().map(|id| format!("#{}", id)).collect();
+            lines.push(format!("  Depends on: {}", ids.join(", ")));
+        }
+        lines.push(format!("  Pomodoros:  {}", self.pomodoro_count_for_task(username, task.id)));
+        lines.push(format!("  Created:    {}", timestamp(task.created_at)));
+        if let Some(started_at) = task.started_at {
+            lines.push(format!("  Started:    {}", timestamp(started_at)));
+        }
+        if let Some(completed_at) = task.completed_at {
+            lines.push(format!("  Completed:  {}", timestamp(completed_at)));
+        }
+        lines.push(format!("  Modified:   {}", timestamp(task.last_modified_at)));
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    /// Prints an N×N grid where cell (row, column) is `X` when the row task
+    /// depends on the column task. Only tasks taking part in at least one
+    /// dependency are shown. In accessible mode it is one sentence per
+    /// dependent task instead.
+    fn print_dependency_matrix(&self, username: &str, out: &mut dyn Write) -> io::Result<()> {
+        let tasks = self.tasks(username);
+        let mut ids: Vec<u32> = tasks
+            .iter()
+            .filter(|task| {
+                !task.depends_on.is_empty() || tasks.iter().any(|other| other.depends_on.contains(&task.id))
+            })
+            .map(|task| task.id)
+            .collect();
+        ids.sort_unstable();
+        if ids.is_empty() {
+            return writeln!(out, "No dependencies.");
+        }
+        if self.config.accessible {
+            // A grid is hard to follow with a screen reader; say it row by row.
+            for task in tasks.iter().filter(|task| !task.depends_on.is_empty()) {
+                let ids: Vec<String> = task.depends_on.iter().map(u32::to_string).collect();
+                let noun = if ids.len() == 1 { "task" } else { "tasks" };
+                writeln!(out, "Task {} depends on {} {}.", task.id, noun, join_words(&ids))?;
+            }
+            return Ok(());
+        }
+
+        let width = ids.iter().map(|id| id.to_string().len()).max().unwrap_or(1);
+        write!(out, "{:>width$}", "", width = width)?;
+        for id in &ids {
+            write!(out, " {:>width$}", id, width = width)?;
+        }
+        writeln!(out)?;
+
+        for row in &ids {
+            let depends_on = &tasks.iter().find(|task| task.id == *row).unwrap().depends_on;
+            write!(out, "{:>width$}", row, width = width)?;
+            for column in &ids {
+                let cell = if depends_on.contains(column) { "X" } else { "." };
+                write!(out, " {:>width$}", cell, width = width)?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Name for the user's first list: their "default_list" setting, or else
+    /// the one in config.json. Doesn't depend on who is logged in, so journal
+    /// replay names lists the same way.
+    fn default_list_for(&self, username: &str) -> String {
+        self.users
+            .get(username)
+            .and_then(|user| user.settings.default_list.clone())
+            .unwrap_or_else(|| self.file_config.default_list.clone())
+    }
+
+    fn tasks(&self, username: &str) -> &[Task] {
+        self.task_lists
+            .iter()
+            .find(|list| list.username == username)
+            .map(|list| list.tasks.as_slice())
+            .unwrap_or(&[])
+    }
+
+    fn tasks_mut(&mut self, username: &str) -> &mut [Task] {
+        self.task_lists
+            .iter_mut()
+            .find(|list| list.username == username)
+            .map(|list| list.tasks.as_mut_slice())
+            .unwrap_or(&mut [])
+    }
+
+    fn task(&self, username: &str, task_id: u32) -> Option<&Task> {
+        self.tasks(username).iter().find(|task| task.id == task_id)
+    }
+
+    /// Looks up a task for modification and stamps its `last_modified_at`.
+    /// Every single-task mutator goes through here.
+    fn task_mut(&mut self, username: &str, task_id: u32) -> Option<&mut Task> {
+        let task = self
+            .task_lists
+            .iter_mut()
+            .find(|list| list.username == username)
+            .and_then(|list| list.tasks.iter_mut().find(|task| task.id == task_id))?;
+        task.last_modified_at = Utc::now();
+        Some(task)
+    }
+
+    /// Replaces the task's description. With `expected_version`, the edit is
+    /// refused if the description was edited since that version was read.
+    fn edit_task(
+        &mut self,
+        username: &str,
+        task_id: u32,
+        new_description: String,
+        expected_version: Option<u32>,
+    ) -> Result<(), AppError> {
+        self.ensure_unlocked(username, task_id)?;
+        let actual = self.task(username, task_id).map(|task| task.version);
+        if let (Some(expected), Some(actual)) = (expected_version, actual) {
+            if expected != actual {
+                return Err(AppError::VersionConflict { expected, actual });
+            }
+        }
+        if let Some(task) = self.task_mut(username, task_id) {
+            task.description = new_description;
+            task.version += 1;
+        }
+        Ok(())
+    }
+
+    fn mark_completed(&mut self, username: &str, task_id: u32) -> Result<(), AppError> {
+        self.ensure_unlocked(username, task_id)?;
+        if let Some(task) = self.task_mut(username, task_id) {
+            task.completed = true;
+            task.completed_at = Some(Utc::now());
+            task.progress = 100;
+        }
+        Ok(())
+    }
+
+    /// Fails with `PermissionDenied` unless `username` is an admin.
+    fn ensure_admin(&self, username: &str) -> Result<(), AppError> {
+        match self.users.get(username) {
+            Some(user) if user.role == Role::Admin => Ok(()),
+            _ => Err(AppError::PermissionDenied),
+        }
+    }
+
+    /// Fails with `TaskLocked` if the task exists and is locked.
+    fn ensure_unlocked(&self, username: &str, task_id: u32) -> Result<(), AppError> {
+        match self.tasks(username).iter().find(|task| task.id == task_id) {
+            Some(task) if task.locked => Err(AppError::TaskLocked(task_id)),
+            _ => Ok(()),
+        }
+    }
+
+    fn lock_task(&mut self, username: &str, task_id: u32) {
+        if let Some(task) = self.task_mut(username, task_id) {
+            task.locked = true;
+        }
+    }
+
+    fn unlock_task(&mut self, username: &str, task_id: u32) {
+        if let Some(task) = self.task_mut(username, task_id) {
+            task.locked = false;
+        }
+    }
+
+    /// Undoes `mark_completed`. The task goes back to in progress if it had
+    /// been started, otherwise to pending; either way its progress restarts
+    /// from 0, since the percentage before completion isn't kept.
+    fn reopen(&mut self, username: &str, task_id: u32) {
+        if let Some(task) = self.task_mut(username, task_id) {
+            if task.completed {
+                task.completed = false;
+                task.completed_at = None;
+                task.progress = 0;
+            }
+        }
+    }
+
+    /// Sets the percentage done, clamped to 100. Does not change the status.
+    fn set_progress(&mut self, username: &str, task_id: u32, progress: u8) {
+        if let Some(task) = self.task_mut(username, task_id) {
+            task.progress = progress.min(100);
+        }
+    }
+
+    /// The user's workflow, or the built-in one if they haven't set their own.
+    fn workflow(&self, username: &str) -> Vec<WorkflowStatus> {
+        match self.users.get(username) {
+            Some(user) if !user.workflow.is_empty() => user.workflow.clone(),
+            _ => WorkflowStatus::default_workflow(),
+        }
+    }
+
+    /// Replaces the user's workflow; an empty list goes back to the built-in
+    /// one. Tasks keep their status if the new workflow has it, and are
+    /// mapped from their built-in status otherwise.
+    fn set_workflow(&mut self, username: &str, statuses: Vec<WorkflowStatus>) -> Result<(), AppError> {
+        if !statuses.is_empty() {
+            validate_workflow(&statuses)?;
+        }
+        match self.users.get_mut(username) {
+            Some(user) => user.workflow = statuses,
+            None => return Err(AppError::AuthenticationFailed),
+        }
+        Ok(())
+    }
+
+    /// Moves the task to the named step of the user's workflow, ignoring case.
+    fn set_status(&mut self, username: &str, task_id: u32, status: &str) -> Result<(), AppError> {
+        self.ensure_unlocked(username, task_id)?;
+        let workflow = self.workflow(username);
+        let index = workflow
+            .iter()
+            .position(|step| step.name.eq_ignore_ascii_case(status.trim()))
+            .ok_or_else(|| AppError::UnknownStatus(status.trim().to_string()))?;
+        let task = self.task_mut(username, task_id).ok_or_else(|| AppError::TaskNotFound(task_id.to_string()))?;
+        move_to_step(task, &workflow, index);
+        Ok(())
+    }
+
+    /// Moves the task one step forward or back in the user's workflow. Does
+    /// nothing at either end.
+    fn step_status(&mut self, username: &str, task_id: u32, forward: bool) -> Result<(), AppError> {
+        self.ensure_unlocked(username, task_id)?;
+        let workflow = self.workflow(username);
+        let task = self.task_mut(username, task_id).ok_or_else(|| AppError::TaskNotFound(task_id.to_string()))?;
+        let position = workflow_position(task, &workflow);
+        let target = if forward { (position + 1).min(workflow.len() - 1) } else { position.saturating_sub(1) };
+        if target != position {
+            move_to_step(task, &workflow, target);
+        }
+        Ok(())
+    }
+
+    /// The user's tasks grouped by workflow step, in workflow order.
+    fn tasks_by_workflow(&self, username: &str) -> Vec<(WorkflowStatus, Vec<&Task>)> {
+        let workflow = self.workflow(username);
+        let mut groups: Vec<(WorkflowStatus, Vec<&Task>)> =
+            workflow.iter().map(|status| (status.clone(), Vec::new())).collect();
+        for task in self.tasks(username) {
+            groups[workflow_position(task, &workflow)].1.push(task);
+        }
+        groups
+    }
+
+    fn mark_in_progress(&mut self, username: &str, task_id: u32) {
+        if let Some(task) = self.task_mut(username, task_id) {
+            if !task.completed && task.started_at.is_none() {
+                task.started_at = Some(Utc::now());
+            }
+        }
+    }
+
+    fn set_due_date(&mut self, username: &str, task_id: u32, due_date: Option<NaiveDate>) {
+        if let Some(task) = self.task_mut(username, task_id) {
+            if task.due_date != due_date {
+                task.escalated_at = None;
+            }
+            task.due_date = due_date;
+        }
+    }
+
+    /// Pending tasks that are overdue as of `now` and haven't had their
+    /// priority raised for it yet.
+    fn escalatable(&self, username: &str, now: DateTime<Utc>) -> Vec<&Task> {
+        let today = self.config.display_timezone().date(now);
+        self.tasks(username)
+            .iter()
+            .filter(|task| !task.completed && task.escalated_at.is_none())
+            .filter(|task| task.due_date.is_some_and(|due| due < today))
+            .collect()
+    }
+
+    /// Raises the stored priority of every `escalatable` task one level and
+    /// records when. Returns the number of tasks escalated.
+    fn escalate_overdue(&mut self, username: &str, now: DateTime<Utc>) -> usize {
+        let ids: Vec<u32> = self.escalatable(username, now).iter().map(|task| task.id).collect();
+        for task in self.tasks_mut(username).iter_mut().filter(|task| ids.contains(&task.id)) {
+            task.priority = task.priority.raised();
+            task.escalated_at = Some(now);
+            task.last_modified_at = now;
+        }
+        ids.len()
+    }
+
+    /// Adds the tag, lowercased, unless the task already has it.
+    fn add_tag(&mut self, username: &str, task_id: u32, tag: &str) -> Result<(), AppError> {
+        let tag = normalize_tag(tag)?;
+        if let Some(task) = self.task_mut(username, task_id) {
+            if !task.tags.contains(&tag) {
+                task.tags.push(tag);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds `tag`, lowercased, to every unlocked task matching `filter` that
+    /// doesn't have it yet. Returns the number of tasks changed.
+    fn bulk_set_tag(&mut self, username: &str, filter: &Filter, tag: &str) -> Result<usize, AppError> {
+        let tag = normalize_tag(tag)?;
+        let mut updated = 0;
+        for task in self.tasks_mut(username).iter_mut().filter(|task| !task.locked && filter.matches(task)) {
+            // Tags added before they were normalized may still be mixed case.
+            if !task.tags.iter().any(|t| t.to_lowercase() == tag) {
+                task.tags.push(tag.clone());
+                task.last_modified_at = Utc::now();
+                updated += 1;
+            }
+        }
+        Ok(updated)
+    }
+
+    /// Removes `tag` from every unlocked task matching `filter`. Returns the
+    /// number of tasks changed.
+    fn bulk_remove_tag(&mut self, username: &str, filter: &Filter, tag: &str) -> usize {
+        // Tags added before they were normalized may still be mixed case.
+        let tag = tag.trim().to_lowercase();
+        let mut updated = 0;
+        for task in self.tasks_mut(username).iter_mut().filter(|task| !task.locked && filter.matches(task)) {
+            if task.tags.iter().any(|t| t.to_lowercase() == tag) {
+                task.tags.retain(|t| t.to_lowercase() != tag);
+                task.last_modified_at = Utc::now();
+                updated += 1;
+            }
+        }
+        updated
+    }
+
+    /// Moves every unlocked task matching `filter` into `sprint`, or out of
+    /// any sprint when it is empty. Returns the number of tasks changed.
+    fn bulk_assign_sprint(&mut self, username: &str, filter: &Filter, sprint: String) -> usize {
+        let sprint = Some(sprint).filter(|sprint| !sprint.is_empty());
+        let mut updated = 0;
+        for task in self.tasks_mut(username).iter_mut().filter(|task| !task.locked && filter.matches(task)) {
+            if task.sprint != sprint {
+                task.sprint = sprint.clone();
+                task.last_modified_at = Utc::now();
+                updated += 1;
+            }
+        }
+        updated
+    }
+
+    /// The open task with the highest `compute_focus_score`, leaving out
+    /// locked tasks and ones waiting on an unfinished dependency. Ties go to
+    /// the lower ID. `None` when nothing is left to do.
+    fn suggest_next_task(&self, username: &str) -> Option<&Task> {
+        let tasks = self.tasks(username);
+        let is_done = |id: &u32| tasks.iter().any(|task| task.id == *id && task.completed);
+        let now = Utc::now();
+        tasks
+            .iter()
+            .filter(|task| !task.completed && !task.locked && task.depends_on.iter().all(is_done))
+            .max_by_key(|task| (compute_focus_score(task, now, &self.config), Reverse(task.id)))
+    }
+
+    /// Open, unlocked tasks matching `filter`, in list order, for review mode.
+    fn review_candidates(&self, username: &str, filter: &Filter) -> Vec<&Task> {
+        self.tasks(username)
+            .iter()
+            .filter(|task| !task.completed && !task.locked && filter.matches(task))
+            .collect()
+    }
+
+    /// Applies the decisions from a review session. If any of them fails, the
+    /// user's task list is put back as it was, so the batch is all or
+    /// nothing. Returns the number of tasks changed.
+    fn apply_review(&mut self, username: &str, decisions: Vec<(u32, ReviewDecision)>) -> Result<usize, AppError> {
+        let before = self.task_lists.iter().find(|list| list.username == username).cloned();
+        let mut changed = 0;
+        for (task_id, decision) in decisions {
+            let result = match decision {
+                ReviewDecision::Complete => self.mark_completed(username, task_id),
+                ReviewDecision::Keep => continue,
+                ReviewDecision::Edit(description) => self.edit_task(username, task_id, description, None),
+                ReviewDecision::Delete => self.remove_task(username, task_id),
+            };
+            if let Err(e) = result {
+                self.task_lists.retain(|list| list.username != username);
+                self.task_lists.extend(before);
+                return Err(e);
+            }
+            changed += 1;
+        }
+        Ok(changed)
+    }
+
+    /// Open tasks due before `today` that `complete_overdue` leaves alone:
+    /// locked ones and ones waiting on an unfinished dependency.
+    fn blocked_overdue(&self, username: &str, today: NaiveDate) -> Vec<&Task> {
+        let tasks = self.tasks(username);
+        let is_done = |id: &u32| tasks.iter().any(|task| task.id == *id && task.completed);
+        tasks
+            .iter()
+            .filter(|task| !task.completed && task.due_date.is_some_and(|due| due < today))
+            .filter(|task| task.locked || !task.depends_on.iter().all(is_done))
+            .collect()
+    }
+
+    /// Completes every open task due before `today`, except those listed by
+    /// `blocked_overdue`. Returns the number of tasks completed.
+    fn complete_overdue(&mut self, username: &str, today: NaiveDate) -> usize {
+        let blocked: Vec<u32> = self.blocked_overdue(username, today).iter().map(|task| task.id).collect();
+        let now = Utc::now();
+        let mut completed = 0;
+        for task in self.tasks_mut(username) {
+            if task.completed || task.due_date.is_none_or(|due| due >= today) || blocked.contains(&task.id) {
+                continue;
+            }
+            task.completed = true;
+            task.completed_at = Some(now);
+            task.progress = 100;
+            task.last_modified_at = now;
+            completed += 1;
+        }
+        completed
+    }
+
+    fn set_link(&mut self, username: &str, task_id: u32, link: String) -> Result<(), AppError> {
+        let link = link.trim();
+        if !is_valid_link(link) {
+            return Err(AppError::InvalidLink(link.to_string()));
+        }
+
+        if let Some(task) = self.task_mut(username, task_id) {
+            task.link = Some(link.to_string());
+        }
+        Ok(())
+    }
+
+    fn clear_link(&mut self, username: &str, task_id: u32) {
+        if let Some(task) = self.task_mut(username, task_id) {
+            task.link = None;
+        }
+    }
+
+    fn set_task_priority(&mut self, username: &str, task_id: u32, priority: Priority) {
+        if let Some(task) = self.task_mut(username, task_id) {
+            task.priority = priority;
+        }
+    }
+
+    fn set_story_points(&mut self, username: &str, task_id: u32, points: Option<u32>) {
+        if let Some(task) = self.task_mut(username, task_id) {
+            task.estimated_story_points = points;
+        }
+    }
+
+    fn set_sprint(&mut self, username: &str, task_id: u32, sprint: Option<String>) {
+        if let Some(task) = self.task_mut(username, task_id) {
+            task.sprint = sprint;
+        }
+    }
+
+    fn set_milestone(&mut self, username: &str, task_id: u32, milestone: Option<String>) {
+        if let Some(task) = self.task_mut(username, task_id) {
+            task.milestone = milestone;
+        }
+    }
+
+    /// Sets or clears the task's context, which must look like "@office".
+    fn set_context(&mut self, username: &str, task_id: u32, context: Option<String>) -> Result<(), AppError> {
+        if let Some(context) = &context {
+            let valid = context
+                .strip_prefix('@')
+                .is_some_and(|name| !name.is_empty() && name.chars().all(char::is_alphanumeric));
+            if !valid {
+                return Err(AppError::InvalidContext(context.clone()));
+            }
+        }
+        if let Some(task) = self.task_mut(username, task_id) {
+            task.context = context;
+        }
+        Ok(())
+    }
+
+    /// The user's tasks in `context`, ignoring case.
+    fn tasks_by_context<'a>(&'a self, username: &str, context: &str) -> Vec<&'a Task> {
+        self.tasks(username)
+            .iter()
+            .filter(|task| task.context.as_deref().is_some_and(|own| own.eq_ignore_ascii_case(context)))
+            .collect()
+    }
+
+    /// Every context the user's tasks are in, sorted and without duplicates.
+    /// Like `tasks_by_context` it ignores case; each is spelled as on the
+    /// first task that has it.
+    fn all_contexts(&self, username: &str) -> Vec<String> {
+        let mut contexts = BTreeMap::new();
+        for context in self.tasks(username).iter().filter_map(|task| task.context.as_ref()) {
+            contexts.entry(context.to_lowercase()).or_insert_with(|| context.clone());
+        }
+        contexts.into_values().collect()
+    }
+
+    fn task_stats(&self, username: &str, today: NaiveDate) -> TaskStats {
+        let tasks = self.tasks(username);
+        let mut stats = TaskStats {
+            total: tasks.len(),
+            per_priority: self.priority_breakdown(username),
+            ..TaskStats::default()
+        };
+        for task in tasks {
+            match task.status() {
+                Status::Pending => stats.pending += 1,
+                Status::InProgress => stats.in_progress += 1,
+                Status::Completed => stats.completed += 1,
+            }
+            if !task.completed && task.due_date.is_some_and(|due| due < today) {
+                stats.overdue += 1;
+            }
+            for tag in &task.tags {
+                *stats.per_tag.entry(tag.clone()).or_default() += 1;
+            }
+        }
+        stats
+    }
+
+    /// `task_stats` as of today in the display timezone, as pretty-printed
+    /// JSON for dashboards.
+    fn stats_json(&self, username: &str) -> Result<String, AppError> {
+        let stats = self.task_stats(username, self.config.display_timezone().date(Utc::now()));
+        Ok(serde_json::to_string_pretty(&stats)?)
+    }
+
+    /// Tasks completed per day over the last `days` days, today included, by
+    /// the display timezone's calendar. Days without completions are left
+    /// out.
+    fn task_completion_heatmap_data(&self, username: &str, days: u32) -> HashMap<NaiveDate, usize> {
+        let mut counts = HashMap::new();
+        let timezone = self.config.display_timezone();
+        let today = timezone.date(Utc::now());
+        let Some(first_day) = days.checked_sub(1).and_then(|back| today.checked_sub_days(Days::new(back.into()))) else {
+            return counts;
+        };
+        for completed_at in self.tasks(username).iter().filter_map(|task| task.completed_at) {
+            let day = timezone.date(completed_at);
+            if (first_day..=today).contains(&day) {
+                *counts.entry(day).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// Open tasks that some other open task depends on, the ones blocking
+    /// the most tasks first (ties by ID), since finishing those unblocks the
+    /// most work.
+    fn blockers(&self, username: &str) -> Vec<&Task> {
+        let tasks = self.tasks(username);
+        let blocked_count = |blocker: &Task| {
+            tasks.iter().filter(|task| !task.completed && task.depends_on.contains(&blocker.id)).count()
+        };
+        let mut blockers: Vec<(&Task, usize)> = tasks
+            .iter()
+            .filter(|task| !task.completed)
+            .map(|task| (task, blocked_count(task)))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        blockers.sort_by_key(|(task, count)| (Reverse(*count), task.id));
+        blockers.into_iter().map(|(task, _)| task).collect()
+    }
+
+    /// Prints one row of task counts per user, busiest first, with when each
+    /// last changed a task. Admins only.
+    fn print_summary_table(&self, admin_username: &str, out: &mut dyn Write) -> Result<(), AppError> {
+        self.ensure_admin(admin_username)?;
+
+        let today = self.config.display_timezone().date(Utc::now());
+        let mut rows: Vec<(&str, TaskStats)> =
+            self.users.keys().map(|username| (username.as_str(), self.task_stats(username, today))).collect();
+        rows.sort_by(|(a, a_stats), (b, b_stats)| b_stats.total.cmp(&a_stats.total).then_with(|| a.cmp(b)));
+
+        let mut table = Table::new();
+        table.set_header(["Username", "Total", "Pending", "In Progress", "Completed", "Overdue", "Last activity"]);
+        for (username, stats) in rows {
+            let last_activity = self.tasks(username).iter().map(|task| task.last_modified_at).max();
+            table.add_row([
+                username.to_string(),
+                stats.total.to_string(),
+                stats.pending.to_string(),
+                stats.in_progress.to_string(),
+                stats.completed.to_string(),
+                stats.overdue.to_string(),
+                last_activity.map_or_else(|| "never".to_string(), |at| self.config.format_timestamp(at)),
+            ]);
+        }
+        writeln!(out, "{}", table)?;
+        Ok(())
+    }
+
+    /// How many of the user's tasks were created on each day of the week,
+    /// Monday first, in the display timezone.
+    fn created_by_weekday(&self, username: &str) -> [usize; 7] {
+        let timezone = self.config.display_timezone();
+        let mut counts = [0; 7];
+        for task in self.tasks(username) {
+            counts[timezone.weekday(task.created_at).num_days_from_monday() as usize] += 1;
+        }
+        counts
+    }
+
+    /// Every tag on any user's tasks, sorted and without duplicates.
+    fn all_tags(&self) -> BTreeSet<String> {
+        self.task_lists.iter().flat_map(|list| &list.tasks).flat_map(|task| task.tags.iter().cloned()).collect()
+    }
+
+    /// The incomplete task, across all users, that was created longest before
+    /// `now`, with its owner.
+    fn most_neglected(&self, now: DateTime<Utc>) -> Option<(&str, &Task)> {
+        self.task_lists
+            .iter()
+            .flat_map(|list| list.tasks.iter().map(move |task| (list.username.as_str(), task)))
+            .filter(|(_, task)| !task.completed && task.created_at <= now)
+            .min_by_key(|(_, task)| task.created_at)
+    }
+
+    /// Number of open tasks due on each date.
+    fn due_counts(&self, username: &str) -> HashMap<NaiveDate, usize> {
+        let mut counts = HashMap::new();
+        for task in self.tasks(username).iter().filter(|task| !task.completed) {
+            if let Some(due_date) = task.due_date {
+                *counts.entry(due_date).or_default() += 1;
+            }
+        }
+        counts
+    }
+
+    /// Days whose open tasks add up to more than `config.daily_capacity`
+    /// points, with their totals, earliest first.
+    fn overbooked_days(&self, username: &str) -> Vec<(NaiveDate, u32)> {
+        let capacity = self.config.daily_capacity;
+        if capacity == 0 {
+            return Vec::new();
+        }
+        self.points_by_day(self.tasks(username))
+            .into_iter()
+            .filter(|(_, points)| *points > capacity)
+            .collect()
+    }
+
+    /// The day to postpone a task to: the first one after its due date (or
+    /// after `today`, if that is later) with room for its points.
+    fn postpone_date(&self, username: &str, task_id: u32, today: NaiveDate) -> Option<NaiveDate> {
+        let task = self.task(username, task_id)?;
+        let others = self.tasks(username).iter().filter(|other| other.id != task_id);
+        let load = self.points_by_day(others);
+        let after = task.due_date.map_or(today, |due| due.max(today));
+        let points = task.estimated_story_points.unwrap_or(self.config.default_task_points);
+        Some(self.next_free_day(&load, after, points))
+    }
+
+    /// Story points of the open tasks due on each day. Tasks without an
+    /// estimate count as `config.default_task_points`.
+    fn points_by_day<'a>(&self, tasks: impl IntoIterator<Item = &'a Task>) -> BTreeMap<NaiveDate, u32> {
+        let default_points = self.config.default_task_points;
+        let mut points = BTreeMap::new();
+        for task in tasks.into_iter().filter(|task| !task.completed) {
+            if let Some(due_date) = task.due_date {
+                *points.entry(due_date).or_default() += task.estimated_story_points.unwrap_or(default_points);
+            }
+        }
+        points
+    }
+
+    /// The first day after `after` where `points` more still fit within
+    /// `config.daily_capacity`, given the points already due each day in
+    /// `load`. A task too big for any day goes to the next day with nothing
+    /// due; a capacity of 0 means no limit.
+    fn next_free_day(&self, load: &BTreeMap<NaiveDate, u32>, after: NaiveDate, points: u32) -> NaiveDate {
+        let capacity = self.config.daily_capacity;
+        let mut day = after + Days::new(1);
+        loop {
+            let used = load.get(&day).copied().unwrap_or(0);
+            if capacity == 0 || used == 0 || used + points <= capacity {
+                return day;
+            }
+            day = day + Days::new(1);
+        }
+    }
+
+    fn set_color_tag(&mut self, username: &str, task_id: u32, color_tag: Option<u8>) {
+        if let Some(task) = self.task_mut(username, task_id) {
+            task.color_tag = color_tag;
+        }
+    }
+
+    /// Work breakdown structure of the user's tasks: a JSON object keyed by
+    /// milestone ("Unassigned" if none), then sprint ("Backlog" if none),
+    /// then priority, holding `{id, description, completed}` entries.
+    fn compute_work_breakdown_structure(&self, username: &str) -> serde_json::Value {
+        let mut wbs: BTreeMap<&str, BTreeMap<&str, BTreeMap<&str, Vec<serde_json::Value>>>> = BTreeMap::new();
+        for task in self.tasks(username) {
+            wbs.entry(task.milestone.as_deref().unwrap_or("Unassigned"))
+                .or_default()
+                .entry(task.sprint.as_deref().unwrap_or("Backlog"))
+                .or_default()
+                .entry(task.priority.label())
+                .or_default()
+                .push(serde_json::json!({
+                    "id": task.id,
+                    "description": task.description,
+                    "completed": task.completed,
+                }));
+        }
+        serde_json::json!(wbs)
+    }
+
+    /// Sums the story points of the user's tasks in `sprint`. Tasks without
+    /// an estimate count as zero points.
+    fn sprint_capacity(&self, username: &str, sprint: &str) -> SprintCapacity {
+        let mut capacity = SprintCapacity::default();
+        for task in self.tasks(username).iter().filter(|task| task.sprint.as_deref() == Some(sprint)) {
+            let points = task.estimated_story_points.unwrap_or(0);
+            capacity.total_points += points;
+            if task.completed {
+                capacity.completed_points += points;
+            }
+        }
+        capacity.remaining_points = capacity.total_points - capacity.completed_points;
+        if capacity.total_points > 0 {
+            capacity.completion_pct = capacity.completed_points as f64 * 100.0 / capacity.total_points as f64;
+        }
+        capacity
+    }
+
+    /// Sets `priority` on every unlocked task whose description contains
+    /// `query` (case-insensitive). Returns the number of tasks changed.
+    fn set_priority_matching(&mut self, username: &str, query: &str, priority: Priority) -> usize {
+        let query = query.to_lowercase();
+        let mut updated = 0;
+        for task in self.tasks_mut(username).iter_mut().filter(|task| !task.locked) {
+            if task.priority != priority && task.description.to_lowercase().contains(&query) {
+                task.priority = priority;
+                task.last_modified_at = Utc::now();
+                updated += 1;
+            }
+        }
+        updated
+    }
+
+    /// Replaces every occurrence of `find` in the user's task descriptions
+    /// with `replace`. Locked tasks are left alone. Returns the number of
+    /// tasks changed.
+    fn find_replace(&mut self, username: &str, find: &str, replace: &str) -> usize {
+        if find.is_empty() {
+            return 0;
+        }
+        let now = Utc::now();
+        let mut changed = 0;
+        for task in self.tasks_mut(username) {
+            if task.locked || !task.description.contains(find) {
+                continue;
+            }
+            task.description = task.description.replace(find, replace);
+            task.last_modified_at = now;
+            changed += 1;
+        }
+        changed
+    }
+
+    /// The priority changes `rebalance_priorities` would make, as task ID →
+    /// (old, new). Open, unlocked tasks are ranked by current priority, then
+    /// due date (undated last), then age, and split into equal quarters:
+    /// Critical, High, Medium and Low.
+    fn plan_rebalance(&self, username: &str) -> HashMap<u32, (Priority, Priority)> {
+        let mut open: Vec<&Task> =
+            self.tasks(username).iter().filter(|task| !task.completed && !task.locked).collect();
+        open.sort_by_key(|task| (Reverse(task.priority), task.due_date.is_none(), task.due_date, task.created_at));
+        let count = open.len();
+        open.into_iter()
+            .enumerate()
+            .filter_map(|(rank, task)| {
+                let priority = Priority::ALL[rank * Priority::ALL.len() / count];
+                (priority != task.priority).then_some((task.id, (task.priority, priority)))
+            })
+            .collect()
+    }
+
+    /// Applies `plan_rebalance` and returns the changes it made.
+    fn rebalance_priorities(&mut self, username: &str) -> HashMap<u32, (Priority, Priority)> {
+        let changes = self.plan_rebalance(username);
+        let now = Utc::now();
+        for task in self.tasks_mut(username) {
+            if let Some(&(_, priority)) = changes.get(&task.id) {
+                task.priority = priority;
+                task.last_modified_at = now;
+            }
+        }
+        changes
+    }
+
+    /// Locked tasks that `op`, a bulk operation, leaves alone although they
+    /// match it. Empty for other operations.
+    fn locked_skipped_by(&self, username: &str, op: &Op) -> Vec<&Task> {
+        let matches = |task: &Task| match op {
+            Op::BulkSetTag { filter, .. } | Op::BulkRemoveTag { filter, .. } | Op::BulkAssignSprint { filter, .. } => {
+                filter.matches(task)
+            }
+            Op::SetPriorityMatching { query, .. } => task.description.to_lowercase().contains(&query.to_lowercase()),
+            Op::RebalancePriorities => !task.completed,
+            Op::FindReplace { find, .. } => !find.is_empty() && task.description.contains(find.as_str()),
+            _ => false,
+        };
+        self.tasks(username).iter().filter(|task| task.locked && matches(task)).collect()
+    }
+
+    /// Counts the user's pending (not completed) tasks per priority. Every
+    /// priority level is present, with zero when nothing matches.
+    fn priority_breakdown(&self, username: &str) -> BTreeMap<Priority, usize> {
+        let mut breakdown: BTreeMap<Priority, usize> = Priority::ALL.into_iter().map(|p| (p, 0)).collect();
+        for task in self.tasks(username).iter().filter(|task| !task.completed) {
+            *breakdown.entry(task.priority).or_default() += 1;
+        }
+        breakdown
+    }
+
+    /// Maps a 1-based position in the user's listing back to the task's ID.
+    fn id_at_position(&self, username: &str, position: usize) -> Option<u32> {
+        position.checked_sub(1).and_then(|index| self.tasks(username).get(index)).map(|task| task.id)
+    }
+
+    /// Runs one Pomodoro interval on a task. `wait` blocks for the interval
+    /// and reports whether it was completed; only completed intervals are
+    /// added to the log. Returns whether the session was recorded.
+    fn start_pomodoro(
+        &mut self,
+        username: &str,
+        task_id: u32,
+        wait: impl FnOnce(Duration) -> bool,
+    ) -> Result<bool, AppError> {
+        if !self.tasks(username).iter().any(|task| task.id == task_id) {
+            return Err(AppError::TaskNotFound(task_id.to_string()));
+        }
+
+        let started_at = Utc::now();
+        if !wait(Duration::from_secs(u64::from(self.config.pomodoro_minutes) * 60)) {
+            return Ok(false);
+        }
+
+        let session = PomodoroSession {
+            username: username.to_string(),
+            task_id,
+            started_at,
+            completed_at: Utc::now(),
+        };
+        self.execute(username, Op::RecordPomodoro { session })?;
+        Ok(true)
+    }
+
+    fn pomodoro_sessions<'a>(&'a self, username: &'a str) -> impl Iterator<Item = &'a PomodoroSession> {
+        self.pomodoros.iter().filter(move |session| session.username == username)
+    }
+
+    fn pomodoro_count_for_task(&self, username: &str, task_id: u32) -> usize {
+        self.pomodoro_sessions(username).filter(|session| session.task_id == task_id).count()
+    }
+
+    /// Minutes spent in completed Pomodoro intervals across all tasks.
+    fn total_focus_minutes(&self, username: &str) -> u64 {
+        self.pomodoro_sessions(username)
+            .map(|session| (session.completed_at - session.started_at).num_minutes().max(0) as u64)
+            .sum()
+    }
+
+    /// The user's open tasks sorted into Eisenhower quadrants, in
+    /// `Quadrant::ALL` order.
+    fn eisenhower_matrix(&self, username: &str, now: DateTime<Utc>) -> Vec<(Quadrant, Vec<&Task>)> {
+        let open: Vec<&Task> = self.tasks(username).iter().filter(|task| !task.completed).collect();
+        Quadrant::ALL
+            .into_iter()
+            .map(|quadrant| {
+                let tasks = open.iter().copied().filter(|task| Quadrant::of(task, now, &self.config) == quadrant);
+                (quadrant, tasks.collect())
+            })
+            .collect()
+    }
+
+    /// The Eisenhower matrix as a 2×2 table, important tasks on top and
+    /// urgent ones on the left. Accessible mode lists the quadrants instead.
+    fn format_eisenhower_matrix(&self, username: &str, now: DateTime<Utc>) -> String {
+        let matrix = self.eisenhower_matrix(username, now);
+        if self.config.accessible {
+            let mut text = String::new();
+            for (quadrant, tasks) in matrix {
+                let noun = if tasks.len() == 1 { "task" } else { "tasks" };
+                text.push_str(&format!("{}: {} {}.\n", quadrant.label(), tasks.len(), noun));
+                for task in tasks {
+                    text.push_str(&format!("Task {}, {}.\n", task.id, task.description));
+                }
+            }
+            return text;
+        }
+
+        let cell = |quadrant: Quadrant| {
+            let tasks = &matrix.iter().find(|(other, _)| *other == quadrant).unwrap().1;
+            let lines: Vec<String> = tasks.iter().map(|task| format!("#{} {}", task.id, task.description)).collect();
+            if lines.is_empty() {
+                "-".to_string()
+            } else {
+                lines.join("\n")
+            }
+        };
+        let mut table = Table::new();
+        table.set_header(["", "Urgent", "Not urgent"]);
+        table.add_row(["Important".to_string(), cell(Quadrant::UrgentImportant), cell(Quadrant::Important)]);
+        table.add_row(["Not important".to_string(), cell(Quadrant::Urgent), cell(Quadrant::Neither)]);
+        format!("{}\n", table)
+    }
+
+    /// Pending tasks that have not been modified for more than
+    /// `stale_after_days` days, longest untouched first.
+    fn find_stale_tasks<'a>(&'a self, username: &str, stale_after_days: u32) -> Vec<&'a Task> {
+        let now = Utc::now();
+        let mut stale: Vec<&Task> = self
+            .tasks(username)
+            .iter()
+            .filter(|task| !task.completed && (now - task.last_modified_at).num_days() > i64::from(stale_after_days))
+            .collect();
+        stale.sort_by_key(|task| task.last_modified_at);
+        stale
+    }
+
+    /// Tasks created at or after `since`, newest first.
+    fn recently_added(&self, username: &str, since: DateTime<Utc>) -> Vec<&Task> {
+        let mut tasks: Vec<&Task> = self.tasks(username).iter().filter(|task| task.created_at >= since).collect();
+        tasks.sort_by_key(|task| Reverse(task.created_at));
+        tasks
+    }
+
+    /// Renders the user's tasks as an Emacs org-mode file: pending and
+    /// in-progress tasks become TODO headings, completed ones DONE.
+    fn export_orgmode(&self, username: &str) -> String {
+        let mut org = format!("#+TITLE: Tasks for {}\n\n", username);
+        for task in self.tasks(username) {
+            let keyword = if task.completed { "DONE" } else { "TODO" };
+            org.push_str(&format!("* {} {}", keyword, task.description));
+            if !task.tags.is_empty() {
+                org.push_str(&format!(" :{}:", task.tags.join(":")));
+            }
+            org.push('\n');
+
+            let mut planning = Vec::new();
+            if let Some(due_date) = task.due_date {
+                planning.push(format!("DEADLINE: <{}>", due_date.format("%Y-%m-%d %a")));
+            }
+            if let (Status::InProgress, Some(started_at)) = (task.status(), task.started_at) {
+                planning.push(format!("SCHEDULED: <{}>", started_at.format("%Y-%m-%d %a")));
+            }
+            if let Some(completed_at) = task.completed_at {
+                planning.push(format!("CLOSED: [{}]", completed_at.format("%Y-%m-%d %a %H:%M")));
+            }
+            if !planning.is_empty() {
+                org.push_str(&format!("  {}\n", planning.join(" ")));
+            }
+        }
+        org
+    }
+
+    /// The user's tasks as a JSON array that `task import` in Taskwarrior
+    /// accepts. Dates use Taskwarrior's `YYYYMMDDTHHMMSSZ` form; a due date
+    /// becomes midnight UTC on that day. Taskwarrior's highest priority is H,
+    /// which Critical tasks get too.
+    fn export_taskwarrior_json(&self, username: &str) -> serde_json::Value {
+        let tasks = self.tasks(username).iter().map(|task| {
+            let uuid = if task.uuid.is_nil() { Uuid::new_v4() } else { task.uuid };
+            let mut object = serde_json::json!({
+                "uuid": uuid.to_string(),
+                "description": task.description,
+                "status": if task.completed { "completed" } else { "pending" },
+                "entry": taskwarrior_date(task.created_at),
+                "modified": taskwarrior_date(task.last_modified_at),
+                "priority": match task.priority {
+                    Priority::Critical | Priority::High => "H",
+                    Priority::Medium => "M",
+                    Priority::Low => "L",
+                },
+                "tags": task.tags,
+            });
+            if let Some(due_date) = task.due_date {
+                object["due"] = taskwarrior_date(due_date.and_time(NaiveTime::MIN).and_utc()).into();
+            }
+            if let (Status::InProgress, Some(started_at)) = (task.status(), task.started_at) {
+                object["start"] = taskwarrior_date(started_at).into();
+            }
+            // Taskwarrior rejects completed tasks without an end date.
+            if task.completed {
+                object["end"] = taskwarrior_date(task.completed_at.unwrap_or(task.last_modified_at)).into();
+            }
+            object
+        });
+        serde_json::Value::Array(tasks.collect())
+    }
+
+    /// Writes the user's tasks as CSV for Notion's database import. Tasks
+    /// have no notes of their own, so the Notes column carries the link.
+    fn export_notion_csv(&self, username: &str, out: &mut dyn Write) -> Result<(), AppError> {
+        let mut writer = csv::Writer::from_writer(out);
+        writer.write_record(["Name", "Status", "Priority", "Tags", "Due date", "Notes"])?;
+        for task in self.tasks(username) {
+            let status = match task.status() {
+                Status::Pending => "Not started",
+                Status::InProgress => "In progress",
+                Status::Completed => "Done",
+            };
+            writer.write_record([
+                task.description.as_str(),
+                status,
+                task.priority.label(),
+                &task.tags.join(","),
+                &task.due_date.map(|date| date.format("%Y-%m-%d").to_string()).unwrap_or_default(),
+                task.link.as_deref().unwrap_or_default(),
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the user's tasks as CSV for Linear's issue import, with
+    /// priorities from 0 (Critical) to 3 (Low). The task's link becomes the
+    /// issue description.
+    fn export_linear_csv(&self, username: &str, out: &mut dyn Write) -> Result<(), AppError> {
+        let mut writer = csv::Writer::from_writer(out);
+        writer.write_record(["Title", "Description", "Status", "Priority", "Labels", "Estimate", "Due Date"])?;
+        for task in self.tasks(username) {
+            let status = match task.status() {
+                Status::Pending => "Todo",
+                Status::InProgress => "In Progress",
+                Status::Completed => "Done",
+            };
+            let priority = match task.priority {
+                Priority::Critical => "0",
+                Priority::High => "1",
+                Priority::Medium => "2",
+                Priority::Low => "3",
+            };
+            let estimate = task.estimated_story_points.map(|points| points.to_string()).unwrap_or_default();
+            let due_date = task.due_date.map(|date| date.format("%Y-%m-%d").to_string()).unwrap_or_default();
+            writer.write_record([
+                task.description.as_str(),
+                task.link.as_deref().unwrap_or_default(),
+                status,
+                priority,
+                &task.tags.join(";"),
+                &estimate,
+                &due_date,
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the user's tasks as CSV for Asana's task import. Tasks have no
+    /// assignee, so that column is left empty for Asana to fill in; the link
+    /// becomes the description. Asana has no level above High, so Critical
+    /// tasks are exported as High.
+    fn export_asana_csv(&self, username: &str, out: &mut dyn Write) -> Result<(), AppError> {
+        let mut writer = csv::Writer::from_writer(out);
+        writer.write_record(["Name", "Description", "Due Date", "Priority", "Tags", "Assignee", "Completed"])?;
+        for task in self.tasks(username) {
+            let due_date = task.due_date.map(|date| date.format("%m/%d/%Y").to_string()).unwrap_or_default();
+            let priority = match task.priority {
+                Priority::Critical => Priority::High,
+                priority => priority,
+            };
+            writer.write_record([
+                task.description.as_str(),
+                task.link.as_deref().unwrap_or_default(),
+                &due_date,
+                priority.label(),
+                &task.tags.join(","),
+                "",
+                if task.completed { "true" } else { "false" },
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the user's tasks as a Markdown checklist, one
+    /// `format_task_markdown` block per task.
+    fn export_markdown(&self, username: &str, out: &mut dyn Write) -> Result<(), AppError> {
+        writeln!(out, "# Tasks")?;
+        for task in self.tasks(username) {
+            write!(out, "\n{}", format_task_markdown(task))?;
+        }
+        Ok(())
+    }
+
+    /// Writes every user's tasks as one CSV, sorted by username and then
+    /// task ID. Admins only.
+    fn export_csv_all_users(&self, admin_username: &str, out: &mut dyn Write) -> Result<(), AppError> {
+        self.ensure_admin(admin_username)?;
+
+        let mut rows: Vec<(&str, &Task)> = self
+            .task_lists
+            .iter()
+            .flat_map(|list| list.tasks.iter().map(|task| (list.username.as_str(), task)))
+            .collect();
+        rows.sort_by_key(|(username, task)| (*username, task.id));
+
+        let mut writer = csv::Writer::from_writer(out);
+        writer.write_record(["username", "task_id", "description", "status", "priority", "due_date", "created_at", "tags"])?;
+        for (username, task) in rows {
+            writer.write_record([
+                username,
+                &task.id.to_string(),
+                &task.description,
+                task.status().label(),
+                task.priority.label(),
+                &task.due_date.map(|date| date.to_string()).unwrap_or_default(),
+                &task.created_at.to_rfc3339(),
+                &task.tags.join(","),
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes a plain-text standup update, ready to paste into a chat channel.
+    /// "Doing today" lists in-progress tasks and the three highest-priority
+    /// pending ones; "Blockers" lists open tasks tagged "blocked" or waiting
+    /// on an unfinished dependency. Empty sections say "None".
+    fn export_plaintext_standup(
+        &self,
+        username: &str,
+        since: DateTime<Utc>,
+        out: &mut dyn Write,
+    ) -> Result<(), AppError> {
+        let tasks = self.tasks(username);
+        let is_done = |id: &u32| tasks.iter().any(|task| task.id == *id && task.completed);
+
+        let done: Vec<&Task> =
+            tasks.iter().filter(|task| task.completed_at.is_some_and(|completed_at| completed_at >= since)).collect();
+
+        let mut pending: Vec<&Task> = tasks.iter().filter(|task| task.status() == Status::Pending).collect();
+        let now = Utc::now();
+        pending.sort_by_key(|task| {
+            (Reverse(effective_priority(task, now, &self.config)), task.due_date.is_none(), task.due_date, task.id)
+        });
+        let doing: Vec<&Task> = tasks
+            .iter()
+            .filter(|task| task.status() == Status::InProgress)
+            .chain(pending.into_iter().take(3))
+            .collect();
+
+        let blockers: Vec<&Task> = tasks
+            .iter()
+            .filter(|task| !task.completed)
+            .filter(|task| task.tags.iter().any(|tag| tag == "blocked") || !task.depends_on.iter().all(is_done))
+            .collect();
+
+        for (i, (heading, section)) in
+            [("Done yesterday", done), ("Doing today", doing), ("Blockers", blockers)].into_iter().enumerate()
+        {
+            if i > 0 {
+                writeln!(out)?;
+            }
+            writeln!(out, "{}:", heading)?;
+            if section.is_empty() {
+                writeln!(out, "- None")?;
+            }
+            for task in section {
+                writeln!(out, "- {}", task.description)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a one-page A4 PDF report: a "Task Report" title, a table of the
+    /// user's tasks (ID, description, status, priority) and a footer with the
+    /// generation date. Descriptions wrap at 60 characters; tasks that don't
+    /// fit on the page are summarised in a final "... and N more" line.
+    #[cfg(feature = "pdf")]
+    fn export_summary_pdf(&self, username: &str, path: &Path) -> Result<(), AppError> {
+        use printpdf::{BuiltinFont, Mm, PdfDocument};
+
+        const LINE_HEIGHT: f32 = 5.0;
+        const BOTTOM_MARGIN: f32 = 25.0;
+        let (doc, page, layer) = PdfDocument::new("Task Report", Mm(210.0), Mm(297.0), "Report");
+        let regular = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+        let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+        let layer = doc.get_page(page).get_layer(layer);
+
+        layer.use_text("Task Report", 18.0, Mm(20.0), Mm(275.0), &bold);
+        let columns = [("ID", 20.0), ("Description", 32.0), ("Status", 145.0), ("Priority", 172.0)];
+        for (heading, x) in columns {
+            layer.use_text(heading, 10.0, Mm(x), Mm(262.0), &bold);
+        }
+
+        let tasks = self.tasks(username);
+        let mut y = 262.0 - LINE_HEIGHT * 1.5;
+        for (i, task) in tasks.iter().enumerate() {
+            let lines = wrap_text(&task.description, 60);
+            if y - LINE_HEIGHT * (lines.len() as f32) < BOTTOM_MARGIN {
+                let more = format!("... and {} more task(s)", tasks.len() - i);
+                layer.use_text(more, 10.0, Mm(20.0), Mm(y), &regular);
+                break;
+            }
+            layer.use_text(task.id.to_string(), 10.0, Mm(20.0), Mm(y), &regular);
+            layer.use_text(task.status().label(), 10.0, Mm(145.0), Mm(y), &regular);
+            layer.use_text(task.priority.label(), 10.0, Mm(172.0), Mm(y), &regular);
+            for line in lines {
+                layer.use_text(line, 10.0, Mm(32.0), Mm(y), &regular);
+                y -= LINE_HEIGHT;
+            }
+        }
+
+        let footer = format!("Generated {} for {}", self.config.format_timestamp(Utc::now()), username);
+        layer.use_text(footer, 8.0, Mm(20.0), Mm(12.0), &regular);
+        doc.save(&mut io::BufWriter::new(File::create(path)?))?;
+        Ok(())
+    }
+
+    /// Resolves user input to a task ID. Accepts either the numeric ID or an
+    /// unambiguous prefix (at least 4 hex digits) of the task's UUID.
+    fn resolve_task_id(&self, username: &str, input: &str) -> Result<u32, AppError> {
+        let input = input.trim();
+        let tasks = self
+            .task_lists
+            .iter()
+            .find(|list| list.username == username)
+            .map(|list| list.tasks.as_slice())
+            .unwrap_or(&[]);
+
+        if let Ok(id) = input.parse::<u32>() {
+            if tasks.iter().any(|task| task.id == id) {
+                return Ok(id);
+            }
+        }
+
+        let prefix = input.replace('-', "").to_lowercase();
+        if prefix.len() < MIN_UUID_PREFIX_LEN || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(AppError::TaskNotFound(input.to_string()));
+        }
+
+        let matches: Vec<u32> = tasks
+            .iter()
+            .filter(|task| task.uuid.simple().to_string().starts_with(&prefix))
+            .map(|task| task.id)
+            .collect();
+        match matches.as_slice() {
+            [id] => Ok(*id),
+            [] => Err(AppError::TaskNotFound(input.to_string())),
+            ids => Err(AppError::AmbiguousTaskId(input.to_string(), ids.to_vec())),
+        }
+    }
+
+    fn tasks_path(&self) -> PathBuf {
+        self.config.data_file("tasks", "json")
+    }
+
+    fn users_path(&self) -> PathBuf {
+        self.config.data_file("users", "json")
+    }
+
+    fn pomodoros_path(&self) -> PathBuf {
+        self.config.data_file("pomodoros", "json")
+    }
+
+    /// The first data file rewritten by someone else since we loaded or last
+    /// saved it, if any. A deleted file doesn't count: there is nothing to lose.
+    fn modified_externally(&self) -> Result<Option<PathBuf>, AppError> {
+        let files = [
+            (self.tasks_path(), &self.tasks_stamp),
+            (self.users_path(), &self.users_stamp),
+            (self.pomodoros_path(), &self.pomodoros_stamp),
+        ];
+        for (path, stamp) in files {
+            let stale = match stamp {
+                _ if !path.exists() => false,
+                Some(stamp) => stamp.is_stale(&path)?,
+                None => true,
+            };
+            if stale {
+                return Ok(Some(path));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Three-way merge of `theirs` into the in-memory task lists, with `base`
+    /// as the common ancestor. Tasks are matched by UUID. When both sides
+    /// changed the same task the in-memory version wins and the task is
+    /// reported as a conflict.
+    fn merge(&mut self, base: &[TaskList], theirs: Vec<TaskList>) -> Vec<MergeConflict> {
+        let mut conflicts = Vec::new();
+        let mut merged = theirs;
+
+        for ours in std::mem::take(&mut self.task_lists) {
+            let same_list = |list: &&TaskList| list.username == ours.username && list.name == ours.name;
+            let base_list = base.iter().find(same_list);
+            let index = match merged.iter().position(|list| same_list(&list)) {
+                Some(index) => index,
+                None => {
+                    merged.push(TaskList {
+                        username: ours.username.clone(),
+                        name: ours.name.clone(),
+                        shared_with: Vec::new(),
+                        tasks: Vec::new(),
+                        deleted: Vec::new(),
+                        trash: Vec::new(),
+                        archived: Vec::new(),
+                        caldav_synced_at: None,
+                    });
+                    merged.len() - 1
+                }
+            };
+            let list = &mut merged[index];
+            if base_list.is_none_or(|base_list| base_list.shared_with != ours.shared_with) {
+                list.shared_with = ours.shared_with.clone();
+            }
+            for tombstone in &ours.deleted {
+                if !list.deleted.contains(tombstone) {
+                    list.deleted.push(tombstone.clone());
+                }
+            }
+            for task in &ours.trash {
+                if !list.trash.iter().any(|trashed| trashed.uuid == task.uuid) {
+                    list.trash.push(task.clone());
+                }
+            }
+            for task in &ours.archived {
+                if !list.archived.iter().any(|archived| archived.uuid == task.uuid) {
+                    list.archived.push(task.clone());
+                }
+            }
+
+            let base_tasks: &[Task] = base_list.map_or(&[], |base_list| &base_list.tasks);
+            let conflict = |task: &Task, resolution| MergeConflict {
+                username: ours.username.clone(),
+                task_id: task.id,
+                description: task.description.clone(),
+                resolution,
+            };
+
+            for task in &ours.tasks {
+                let base_task = base_tasks.iter().find(|base_task| base_task.uuid == task.uuid);
+                let theirs = list.tasks.iter().position(|their_task| their_task.uuid == task.uuid);
+                match (base_task, theirs) {
+                    (Some(base_task), _) if base_task == task => {}
+                    (Some(base_task), Some(i)) => {
+                        if list.tasks[i] != *base_task && list.tasks[i] != *task {
+                            conflicts.push(conflict(task, "changed on both sides, kept yours"));
+                        }
+                        list.tasks[i] = task.clone();
+                    }
+                    (None, Some(i)) => list.tasks[i] = task.clone(),
+                    (base_task, None) => {
+                        if base_task.is_some() {
+                            conflicts.push(conflict(task, "deleted elsewhere, kept yours"));
+                        }
+                        let mut task = task.clone();
+                        if list.tasks.iter().any(|their_task| their_task.id == task.id) {
+                            let max_id = list.tasks.iter().map(|their_task| their_task.id).max().unwrap_or(0);
+                            task.id = self.id_generator.next_id(&ours.username, max_id);
+                        }
+                        list.tasks.push(task);
+                    }
+                }
+            }
+
+            for base_task in base_tasks {
+                if ours.tasks.iter().any(|task| task.uuid == base_task.uuid) {
+                    continue;
+                }
+                if let Some(i) = list.tasks.iter().position(|task| task.uuid == base_task.uuid) {
+                    if list.tasks[i] == *base_task {
+                        list.tasks.remove(i);
+                    } else {
+                        conflicts.push(conflict(&list.tasks[i], "changed elsewhere, not deleted"));
+                    }
+                }
+            }
+        }
+
+        self.task_lists = merged;
+        self.mark_dirty();
+        conflicts
+    }
+
+    /// Compares two snapshots: users by name, then tasks as in
+    /// `diff_task_lists`.
+    fn diff(old: &AppData, new: &AppData) -> Vec<Change> {
+        let mut changes = diff_usernames(old.users.keys(), new.users.keys());
+        changes.extend(diff_task_lists(&old.task_lists, &new.task_lists));
+        changes
+    }
+
+    /// What this session changed compared to the data as loaded or last
+    /// saved, for the preview before saving.
+    fn session_changes(&self) -> Vec<Change> {
+        let mut changes = diff_usernames(self.base_usernames.iter(), self.users.keys());
+        changes.extend(diff_task_lists(&self.base_task_lists, &self.task_lists));
+        changes
+    }
+
+    /// Throws away the unsaved changes: the journal is emptied so they are
+    /// not recovered on the next start. The data in memory is left as is.
+    fn discard_changes(&mut self) -> Result<(), AppError> {
+        if let Some(journal) = &self.journal {
+            journal.truncate()?;
+        }
+        // Keep a pending autosave from writing what was just discarded.
+        if let Some(autosaver) = &self.autosaver {
+            lock_disk(&autosaver.disk).generation = self.generation;
+        }
+        self.undo_stack.clear();
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Reads a tasks.json snapshot on its own, without users or settings.
+    fn from_tasks_file(path: &Path) -> Result<Self, AppError> {
+        let mut app_data = AppData::new();
+        app_data.task_lists = TasksFile::parse(path, &fs::read(path)?)?.task_lists.into_owned();
+        Ok(app_data)
+    }
+
+    /// Merges another copy of the task lists, e.g. a sync tool's conflict
+    /// copy, without a common ancestor. Per task (matched by UUID) the later
+    /// `last_modified_at` wins and additions from both sides are kept. A
+    /// deletion on either side applies only if the task wasn't modified after
+    /// it. When both sides changed a task at the same instant, both versions
+    /// are kept and the other one is marked "(conflict)".
+    fn sync_resolve(&mut self, theirs: Vec<TaskList>) -> Vec<MergeConflict> {
+        let mut conflicts = Vec::new();
+
+        for their_list in theirs {
+            let index = self
+                .task_lists
+                .iter()
+                .position(|list| list.username == their_list.username && list.name == their_list.name);
+            let Some(index) = index else {
+                self.task_lists.push(their_list);
+                continue;
+            };
+            let list = &mut self.task_lists[index];
+            let conflict = |task: &Task, resolution| MergeConflict {
+                username: list.username.clone(),
+                task_id: task.id,
+                description: task.description.clone(),
+                resolution,
+            };
+
+            // Our tasks that the other side deleted.
+            let mut kept = Vec::new();
+            for task in std::mem::take(&mut list.tasks) {
+                if their_list.tasks.iter().any(|their_task| their_task.uuid == task.uuid) {
+                    kept.push(task);
+                    continue;
+                }
+                match their_list.deleted_at(task.uuid) {
+                    Some(deleted_at) if deleted_at >= task.last_modified_at => {}
+                    Some(_) => {
+                        conflicts.push(conflict(&task, "modified after it was deleted elsewhere, kept"));
+                        kept.push(task);
+                    }
+                    None => kept.push(task),
+                }
+            }
+            list.tasks = kept;
+
+            let max_id = list.tasks.iter().chain(&their_list.tasks).map(|task| task.id).max().unwrap_or(0);
+            for mut their_task in their_list.tasks {
+                let ours = list.tasks.iter().position(|task| task.uuid == their_task.uuid);
+                match ours {
+                    Some(i) if list.tasks[i] == their_task => {}
+                    Some(i) => match their_task.last_modified_at.cmp(&list.tasks[i].last_modified_at) {
+                        std::cmp::Ordering::Greater => {
+                            their_task.id = list.tasks[i].id;
+                            list.tasks[i] = their_task;
+                        }
+                        std::cmp::Ordering::Less => {}
+                        std::cmp::Ordering::Equal => {
+                            conflicts.push(conflict(&list.tasks[i], "changed on both sides at once, kept both"));
+                            their_task.uuid = Uuid::new_v4();
+                            their_task.id = self.id_generator.next_id(&their_list.username, max_id);
+                            their_task.description.push_str(" (conflict)");
+                            list.tasks.push(their_task);
+                        }
+                    },
+                    None => {
+                        match list.deleted_at(their_task.uuid) {
+                            Some(deleted_at) if deleted_at >= their_task.last_modified_at => continue,
+                            Some(_) => {
+                                conflicts.push(conflict(&their_task, "modified elsewhere after you deleted it, kept"))
+                            }
+                            None => {}
+                        }
+                        if list.tasks.iter().any(|task| task.id == their_task.id) {
+                            their_task.id = self.id_generator.next_id(&their_list.username, max_id);
+                        }
+                        list.tasks.push(their_task);
+                    }
+                }
+            }
+
+            for tombstone in their_list.deleted {
+                if !list.deleted.contains(&tombstone) {
+                    list.deleted.push(tombstone);
+                }
+            }
+        }
+
+        self.mark_dirty();
+        conflicts
+    }
+
+    /// E.g. "Last saved: 3 minutes ago (2024-03-01 14:02 CET)".
+    fn sync_info(&self) -> String {
+        match self.last_synced_at {
+            Some(saved_at) => format!(
+                "Last saved: {} ({})",
+                format_elapsed(Utc::now() - saved_at),
+                self.config.format_timestamp(saved_at)
+            ),
+            None => "Not saved yet.".to_string(),
+        }
+    }
+
+    /// Re-reads the data files and reapplies this session's changes on top.
+    /// Tasks are merged with `merge`; users registered elsewhere are added
+    /// (ones removed here stay removed) and Pomodoro sessions are combined.
+    fn reload_and_merge(&mut self) -> Result<Vec<MergeConflict>, AppError> {
+        // Whoever rewrote the files is unlikely to have updated their checksums.
+        let mut conflicts = Vec::new();
+        let path = self.tasks_path();
+        if path.exists() {
+            let bytes = read_data_file(&path, false)?;
+            let theirs = TasksFile::parse(&path, &bytes)?.task_lists.into_owned();
+            let stamp = FileStamp::new(&path, &bytes)?;
+
+            let base = std::mem::replace(&mut self.base_task_lists, theirs.clone());
+            conflicts = self.merge(&base, theirs);
+            self.tasks_stamp = Some(stamp);
+        }
+
+        let path = self.users_path();
+        if path.exists() {
+            let bytes = read_data_file(&path, false)?;
+            let theirs: Vec<User> = parse_json_file(&path, &bytes)?;
+            let stamp = FileStamp::new(&path, &bytes)?;
+
+            let their_usernames = theirs.iter().map(|user| user.username.clone()).collect();
+            let base = std::mem::replace(&mut self.base_usernames, their_usernames);
+            for user in theirs {
+                if !base.contains(&user.username) && !self.users.contains_key(&user.username) {
+                    self.users.insert(user.username.clone(), user);
+                }
+            }
+            self.users_stamp = Some(stamp);
+        }
+
+        let path = self.pomodoros_path();
+        if path.exists() {
+            let bytes = read_data_file(&path, false)?;
+            let theirs: Vec<PomodoroSession> = parse_json_file(&path, &bytes)?;
+            let stamp = FileStamp::new(&path, &bytes)?;
+
+            for session in theirs {
+                if !self.pomodoros.contains(&session) {
+                    self.pomodoros.push(session);
+                }
+            }
+            self.pomodoros_stamp = Some(stamp);
+        }
+
+        self.mark_dirty();
+        Ok(conflicts)
+    }
+
+    /// Saves, refusing with `ModifiedExternally` if a data file was changed
+    /// by another program since it was loaded.
+    fn save(&mut self) -> Result<(), AppError> {
+        self.collect_autosave();
+        if let Some(path) = self.modified_externally()? {
+            return Err(AppError::ModifiedExternally(path));
+        }
+        self.save_overwriting()
+    }
+
+    /// Saves without checking the data files for outside changes.
+    fn save_overwriting(&mut self) -> Result<(), AppError> {
+        self.save_as(&self.tasks_path())
+    }
+
+    /// Saves with the tasks written to `task_path` instead of tasks.json.
+    fn save_as(&mut self, task_path: &Path) -> Result<(), AppError> {
+        if self.read_only {
+            return Err(AppError::ReadOnly);
+        }
+        let disk = self.autosaver.as_ref().map(|autosaver| Arc::clone(&autosaver.disk));
+        let mut disk = disk.as_deref().map(lock_disk);
+        if let Some(disk) = disk.as_deref_mut() {
+            self.absorb_autosave(disk);
+        }
+
+        let last_synced_at = Some(Utc::now());
+        let bytes = serde_json::to_vec(&TasksFile {
+            last_synced_at,
+            task_lists: Cow::Borrowed(&self.task_lists),
+        })?;
+        write_data_file(task_path, &bytes)?;
+        self.last_synced_at = last_synced_at;
+        if task_path == self.tasks_path() {
+            self.tasks_stamp = Some(FileStamp::new(task_path, &bytes)?);
+            self.base_task_lists = self.task_lists.clone();
+        }
+
+        let user_path = self.users_path();
+        let users: Vec<User> = self.users.values().cloned().collect();
+        let bytes = serde_json::to_vec(&users)?;
+        write_data_file(&user_path, &bytes)?;
+        self.users_stamp = Some(FileStamp::new(&user_path, &bytes)?);
+        self.base_usernames = self.users.keys().cloned().collect();
+
+        let pomodoro_path = self.pomodoros_path();
+        let bytes = serde_json::to_vec(&self.pomodoros)?;
+        write_data_file(&pomodoro_path, &bytes)?;
+        self.pomodoros_stamp = Some(FileStamp::new(&pomodoro_path, &bytes)?);
+
+        if let Some(disk) = disk.as_deref_mut() {
+            disk.stamps = Some(self.file_stamps());
+            if task_path == self.tasks_path() {
+                disk.generation = self.generation;
+            }
+        }
+        if let Some(journal) = &self.journal {
+            journal.truncate()?;
+        }
+        // An undo recorded after this would have nothing to replay against
+        // once the journal is empty, so only unsaved changes can be undone.
+        self.undo_stack.clear();
+        Ok(())
+    }
+
+    /// Writes the data files like `save_overwriting`, for the `Autosaver`.
+    /// tasks.json is backdated to `taken_at` so that after a crash the
+    /// journal entries made since the snapshot are still replayed on top.
+    fn autosave(&mut self, taken_at: SystemTime) -> Result<(), AppError> {
+        if let Some(path) = self.modified_externally()? {
+            return Err(AppError::ModifiedExternally(path));
+        }
+        self.save_overwriting()?;
+        let path = self.tasks_path();
+        OpenOptions::new().write(true).open(&path)?.set_modified(taken_at)?;
+        self.tasks_stamp = Some(FileStamp::new(&path, &fs::read(&path)?)?);
+        Ok(())
+    }
+
+    /// Starts writing unsaved changes to disk every
+    /// `config.autosave_seconds`, unless that is 0 or nothing may be saved.
+    fn start_autosave(&mut self) {
+        if self.config.autosave_seconds > 0 && !self.read_only && self.journal.is_some() {
+            let interval = Duration::from_secs(self.config.autosave_seconds);
+            self.autosaver = Some(Autosaver::start(interval, self.file_config.clone()));
+        }
+    }
+
+    /// Takes the latest autosave, if there was one since the last call, as
+    /// the data last saved.
+    fn collect_autosave(&mut self) {
+        if let Some(autosaver) = &self.autosaver {
+            let disk = Arc::clone(&autosaver.disk);
+            self.absorb_autosave(&mut lock_disk(&disk));
+        }
+    }
+
+    fn absorb_autosave(&mut self, disk: &mut DiskState) {
+        let Some(autosaved) = disk.autosaved.take() else {
+            return;
+        };
+        if let Some(stamps) = &disk.stamps {
+            self.set_file_stamps(stamps.clone());
+        }
+        self.last_synced_at = autosaved.last_synced_at;
+        self.base_task_lists = autosaved.task_lists;
+        self.base_usernames = autosaved.usernames;
+        if autosaved.generation == self.generation {
+            self.dirty = false;
+        }
+    }
+
+    fn file_stamps(&self) -> FileStamps {
+        FileStamps {
+            tasks: self.tasks_stamp.clone(),
+            users: self.users_stamp.clone(),
+            pomodoros: self.pomodoros_stamp.clone(),
+        }
+    }
+
+    fn set_file_stamps(&mut self, stamps: FileStamps) {
+        self.tasks_stamp = stamps.tasks;
+        self.users_stamp = stamps.users;
+        self.pomodoros_stamp = stamps.pomodoros;
+    }
+
+    /// Saves, retrying transient I/O failures up to `attempts` times in total
+    /// with exponential backoff. Returns the last error if every attempt fails.
+    fn save_with_retry(&mut self, attempts: u32) -> Result<(), AppError> {
+        retry_with_backoff(attempts, SAVE_RETRY_BACKOFF, || self.save())
+    }
+
+    fn load(profile: Option<&str>) -> Result<Self, AppError> {
+        AppData::load_checked(profile, true)
+    }
+
+    /// Loads while skipping checksum verification, for when the user chose to
+    /// continue despite a mismatch.
+    fn load_unverified(profile: Option<&str>) -> Result<Self, AppError> {
+        AppData::load_checked(profile, false)
+    }
+
+    fn load_checked(profile: Option<&str>, verify: bool) -> Result<Self, AppError> {
+        AppData::load_with_config(Config::load(profile)?, verify)
+    }
+
+    /// Loads the data files in `config.data_dir` and replays the journal.
+    fn load_with_config(config: Config, verify: bool) -> Result<Self, AppError> {
+        let mut app_data = AppData::new();
+        app_data.file_config = config;
+        app_data.config = app_data.file_config.clone();
+
+        let path = app_data.tasks_path();
+        if path.exists() {
+            let bytes = read_data_file(&path, verify)?;
+            let file = TasksFile::parse(&path, &bytes)?;
+            app_data.task_lists = file.task_lists.into_owned();
+            app_data.last_synced_at = file.last_synced_at;
+            app_data.base_task_lists = app_data.task_lists.clone();
+            app_data.tasks_stamp = Some(FileStamp::new(&path, &bytes)?);
+        }
+
+        let path = app_data.users_path();
+        if path.exists() {
+            let bytes = read_data_file(&path, verify)?;
+            let users = dedup_users(parse_json_file(&path, &bytes)?, &path);
+            for user in users {
+                app_data.base_usernames.push(user.username.clone());
+                app_data.users.insert(user.username.clone(), user);
+            }
+            app_data.users_stamp = Some(FileStamp::new(&path, &bytes)?);
+        }
+
+        let path = app_data.pomodoros_path();
+        if path.exists() {
+            let bytes = read_data_file(&path, verify)?;
+            app_data.pomodoros = parse_json_file(&path, &bytes)?;
+            app_data.pomodoros_stamp = Some(FileStamp::new(&path, &bytes)?);
+        }
+
+        for problem in app_data.reindex() {
+            println!("Warning: {}", problem);
+        }
+
+        app_data.journal = Some(Journal {
+            path: app_data.config.data_file("journal", "jsonl"),
+            fsync: app_data.config.journal_fsync,
+        });
+        let replayed = app_data.replay_journal()?;
+        if replayed > 0 {
+            println!("Recovered {} unsaved change(s) from the journal.", replayed);
+        }
+
+        #[cfg(feature = "webhooks")]
+        if !app_data.config.webhooks.is_empty() {
+            let urls = app_data.config.webhooks.clone();
+            let secret = app_data.config.webhook_secret()?;
+            app_data.webhooks = Some(WebhookWorker::start(urls, secret)?);
+        }
+
+        Ok(app_data)
+    }
+
+    fn register_user(&mut self, username: String, password: String) -> Result<(), AppError> {
+        if self.users.contains_key(&username) {
+            return Err(AppError::UserExists);
+        }
+
+        let hashed_password = hash(self.config.peppered(&password), self.config.cost)?;
+        let user = User {
+            username,
+            password: hashed_password,
+            role: Role::default(),
+            email: None,
+            display_name: None,
+            settings: UserSettings::default(),
+            workflow: Vec::new(),
+        };
+        self.execute(&user.username.clone(), Op::AddUser { user })?;
+
+        Ok(())
+    }
+
+    fn authenticate(&self, username: &str, password: &str) -> bool {
+        if let Some(user) = self.users.get(username) {
+            verify(self.config.peppered(password), &user.password).unwrap_or(false)
+        } else {
+            false
+        }
+    }
+
+    fn change_password(&mut self, username: &str, old_password: &str, new_password: &str) -> Result<(), AppError> {
+        if !self.authenticate(username, old_password) {
+            return Err(AppError::AuthenticationFailed);
+        }
+
+        let password = hash(self.config.peppered(new_password), self.config.cost)?;
+        self.execute(username, Op::ChangePassword { password })?;
+        Ok(())
+    }
+
+    /// Switches `config` to the user's settings over the file config.
+    fn use_settings_of(&mut self, username: &str) {
+        let settings = self.users.get(username).map(|user| user.settings.clone()).unwrap_or_default();
+        self.config = self.file_config.with_user_settings(&settings);
+    }
+
+    fn set_user_setting(&mut self, username: &str, key: &str, value: Option<&str>) -> Result<(), AppError> {
+        let user = self.users.get_mut(username).ok_or(AppError::AuthenticationFailed)?;
+        user.settings.set(key, value)?;
+        self.use_settings_of(username);
+        Ok(())
+    }
+
+    /// Each setting with its value for the user and where that comes from:
+    /// "yours", "config.json" or "default".
+    fn settings_overview(&self, username: &str) -> Vec<(&'static str, String, &'static str)> {
+        let settings = self.users.get(username).map(|user| user.settings.clone()).unwrap_or_default();
+        let defaults = Config::default();
+        UserSettings::KEYS
+            .into_iter()
+            .map(|key| match settings.get(key) {
+                Some(value) => (key, value, "yours"),
+                None if self.file_config.setting_value(key) != defaults.setting_value(key) => {
+                    (key, self.file_config.setting_value(key), "config.json")
+                }
+                None => (key, defaults.setting_value(key), "default"),
+            })
+            .collect()
+    }
+
+    /// Case-insensitive description search over every list the user owns or
+    /// that has been shared with them. Returns (list name, task) pairs.
+    fn search_all(&self, username: &str, query: &str) -> Vec<(&str, &Task)> {
+        self.search_lists(query, |list| list.is_visible_to(username))
+    }
+
+    /// Like `search_all`, but limited to the lists the user owns.
+    fn search_own(&self, username: &str, query: &str) -> Vec<(&str, &Task)> {
+        self.search_lists(query, |list| list.username == username)
+    }
+
+    /// Case-insensitive description search over every user's tasks, returning
+    /// (owner, task) pairs sorted by owner. Admins only.
+    fn search_all_users(&self, admin_username: &str, query: &str) -> Result<Vec<(&str, &Task)>, AppError> {
+        self.ensure_admin(admin_username)?;
+        let query = query.to_lowercase();
+        let collation = self.config.collation();
+        let mut results: Vec<(&str, &Task)> = self
+            .task_lists
+            .iter()
+            .flat_map(|list| list.tasks.iter().map(move |task| (list.username.as_str(), task)))
+            .filter(|(_, task)| task.description.to_lowercase().contains(&query))
+            .collect();
+        results.sort_by_cached_key(|(owner, task)| (owner.to_string(), collation.collate_key(&task.description)));
+        Ok(results)
+    }
+
+    /// Matching tasks in alphabetical order of list name, then description,
+    /// so that lists with the same name are shown together.
+    fn search_lists(&self, query: &str, include: impl Fn(&TaskList) -> bool) -> Vec<(&str, &Task)> {
+        let query = query.to_lowercase();
+        let collation = self.config.collation();
+        let mut results: Vec<(&str, &Task)> = self
+            .task_lists
+            .iter()
+            .filter(|list| include(list))
+            .flat_map(|list| list.tasks.iter().map(move |task| (list.name.as_str(), task)))
+            .filter(|(_, task)| task.description.to_lowercase().contains(&query))
+            .collect();
+        results.sort_by_cached_key(|(list_name, task)| {
+            (collation.collate_key(list_name), collation.collate_key(&task.description))
+        });
+        results
+    }
+
+    /// Builds a standalone HTML report (inline CSS, no external assets) with
+    /// summary counts and tables of overdue, due-this-week and
+    /// completed-this-week tasks. Empty tables are left out.
+    fn export_html(&self, username: &str, today: NaiveDate) -> String {
+        let tasks = self.tasks(username);
+        let (week_start, week_end) = week_bounds(today);
+        let this_week = |date: NaiveDate| date >= week_start && date <= week_end;
+
+        let overdue: Vec<&Task> = tasks
+            .iter()
+            .filter(|task| !task.completed && task.due_date.is_some_and(|due| due < today))
+            .collect();
+        let due_this_week: Vec<&Task> = tasks
+            .iter()
+            .filter(|task| !task.completed && task.due_date.is_some_and(|due| due >= today && this_week(due)))
+            .collect();
+        let completed_this_week: Vec<&Task> = tasks
+            .iter()
+            .filter(|task| task.completed_at.is_some_and(|at| this_week(at.date_naive())))
+            .collect();
+        let completed = tasks.iter().filter(|task| task.completed).count();
+
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Task report</title>\n</head>\n\
+             <body style=\"font-family: sans-serif; margin: 2em; color: #222;\">\n",
+        );
+        html.push_str(&format!(
+            "<h1>Task report for {}</h1>\n<p style=\"color: #666;\">Week of {} to {}</p>\n",
+            html_escape(username),
+            week_start,
+            week_end
+        ));
+        html.push_str(&format!(
+            "<table style=\"border-collapse: collapse; margin-bottom: 1.5em;\"><tr>\
+             <td style=\"padding: 0.5em 1em; background: #eef;\">Total: <b>{}</b></td>\
+             <td style=\"padding: 0.5em 1em; background: #efe;\">Completed: <b>{}</b></td>\
+             <td style=\"padding: 0.5em 1em; background: #ffe;\">Pending: <b>{}</b></td>\
+             <td style=\"padding: 0.5em 1em; background: #fee;\">Overdue: <b>{}</b></td>\
+             </tr></table>\n",
+            tasks.len(),
+            completed,
+            tasks.len() - completed,
+            overdue.len()
+        ));
+        html.push_str(&html_task_table("Overdue", &overdue));
+        html.push_str(&html_task_table("Due this week", &due_this_week));
+        html.push_str(&html_task_table("Completed this week", &completed_this_week));
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    fn format_task(&self, task: &Task) -> String {
+        let short_uuid = &task.uuid.simple().to_string()[..8];
+        let status = match task.status() {
+            Status::InProgress => format!("{} ({}%)", Status::InProgress.label(), task.progress),
+            status => status.label().to_string(),
+        };
+        let marker = if task.completed {
+            ""
+        } else {
+            age_marker(task.last_modified_at, Utc::now(), self.config.age_marker_days)
+        };
+        let mut line = if marker.is_empty() { String::new() } else { format!("{} ", marker) };
+        line.push_str(&format!(
+            "ID: {} ({}), Description: {}, Status: {}, Created: {}",
+            task.id,
+            short_uuid,
+            self.list_description(&task.description),
+            status,
+            self.config.format_timestamp(task.created_at)
+        ));
+        line.push_str(&format!(", Priority: {}", effective_priority(task, Utc::now(), &self.config).label()));
+        if let Some(due_date) = task.due_date {
+            line.push_str(&format!(", Due: {}", due_date));
+        }
+        if !task.tags.is_empty() {
+            line.push_str(&format!(", Tags: {}", task.tags.join(", ")));
+        }
+        if let Some(link) = &task.link {
+            line.push_str(&format!(", 🔗 {}", link));
+        }
+        if let Some(points) = task.estimated_story_points {
+            line.push_str(&format!(", Points: {}", points));
+        }
+        if let Some(sprint) = &task.sprint {
+            line.push_str(&format!(", Sprint: {}", sprint));
+        }
+        if let Some(context) = &task.context {
+            line.push_str(&format!(", Context: {}", context));
+        }
+        if !task.depends_on.is_empty() {
+            let ids: Vec<String> = task.depends_on.iter().map(|id| format!("#{}", id)).collect();
+            line.push_str(&format!(", Depends on: {}", ids.join(", ")));
+        }
+        if let Some(completed_at) = task.completed_at {
+            line.push_str(&format!(", Completed: {}", self.config.format_timestamp(completed_at)));
+        }
+        if task.locked {
+            line.push_str(", Locked");
+        }
+        line
+    }
+
+    /// The task as a plain sentence for accessible mode, e.g. "Task 4, buy
+    /// milk, pending, due tomorrow." Medium priority, the default, is left
+    /// out to keep it short.
+    fn format_task_accessible(&self, task: &Task, today: NaiveDate) -> String {
+        let mut parts = vec![format!("Task {}", task.id), self.list_description(&task.description).into_owned()];
+        parts.push(match task.status() {
+            Status::InProgress => format!("in progress, {} percent done", task.progress),
+            status => status.label().to_lowercase(),
+        });
+        let untouched = (Utc::now() - task.last_modified_at).num_days();
+        if !task.completed && untouched >= i64::from(self.config.age_marker_days[0]) {
+            parts.push(format!("untouched for {} days", untouched));
+        }
+        let priority = effective_priority(task, Utc::now(), &self.config);
+        if priority != Priority::Medium {
+            parts.push(format!("{} priority", priority.label().to_lowercase()));
+        }
+        if let Some(due_date) = task.due_date {
+            parts.push(match (due_date - today).num_days() {
+                0 => "due today".to_string(),
+                1 => "due tomorrow".to_string(),
+                -1 => "due yesterday".to_string(),
+                _ => format!("due {}", due_date.format("%A, %B %-d, %Y")),
+            });
+        }
+        if !task.tags.is_empty() {
+            parts.push(format!("tagged {}", join_words(&task.tags)));
+        }
+        if !task.depends_on.is_empty() {
+            let ids: Vec<String> = task.depends_on.iter().map(u32::to_string).collect();
+            let noun = if ids.len() == 1 { "task" } else { "tasks" };
+            parts.push(format!("depends on {} {}", noun, join_words(&ids)));
+        }
+        format!("{}.", parts.join(", "))
+    }
+
+    /// `format_task` colored according to the configured theme; identical to
+    /// it in the mono theme.
+    fn format_task_colored(&self, task: &Task) -> String {
+        let theme = self.config.theme;
+        let line = self.format_task(task);
+        if theme == Theme::Mono {
+            return line;
+        }
+
+        let color = task.ansi_color(theme);
+        let effective = effective_priority(task, Utc::now(), &self.config);
+        let priority_color = theme.priority_color(effective);
+        let line = if priority_color.is_empty() {
+            line
+        } else {
+            let priority = format!("Priority: {}", effective.label());
+            // Back to the line's own color after the priority.
+            let highlighted = format!("{}{}{}{}", priority_color, priority, ANSI_RESET, color);
+            line.replacen(&priority, &highlighted, 1)
+        };
+        format!("{}{}{}", color, line, ANSI_RESET)
+    }
+
+    fn display_tasks(&self, username: &str) {
+        self.display_tasks_with_status(username, None);
+    }
+
+    /// `display_tasks` limited to tasks in `status`, or all of them for None.
+    fn display_tasks_with_status(&self, username: &str, status: Option<Status>) {
+        if let Some(list) = self.task_lists.iter().find(|list| list.username == username) {
+            let tasks: Vec<&Task> =
+                list.tasks.iter().filter(|task| status.is_none_or(|status| task.status() == status)).collect();
+            if self.config.accessible {
+                let count = tasks.len();
+                println!("{} has {} {}.", username, count, if count == 1 { "task" } else { "tasks" });
+                let today = Utc::now().date_naive();
+                for (index, task) in tasks.iter().enumerate() {
+                    println!("{}", self.format_task_accessible(task, today));
+                    if !self.continue_listing(index + 1, count) {
+                        break;
+                    }
+                }
+                return;
+            }
+
+            println!("Tasks for {}:", username);
+            let colored = io::stdout().is_terminal();
+            for (index, task) in tasks.iter().enumerate() {
+                if colored {
+                    println!("{}", self.format_task_colored(task));
+                } else {
+                    println!("{}", self.format_task(task));
+                }
+                if !self.continue_listing(index + 1, tasks.len()) {
+                    break;
+                }
+            }
+        } else {
+            println!("No tasks found for {}", username);
+        }
+    }
+
+    /// After every `Config::page_size` tasks of a listing on a terminal, waits
+    /// for Enter before going on. Returns false if the user chose to stop.
+    fn continue_listing(&self, shown: usize, total: usize) -> bool {
+        let page_size = self.config.page_size;
+        if page_size == 0 || !shown.is_multiple_of(page_size) || shown == total || !io::stdout().is_terminal() {
+            return true;
+        }
+        println!("-- {} more, press Enter to continue or q to stop --", total - shown);
+        !read_line().eq_ignore_ascii_case("q")
+    }
+
+    /// The description as shown in listings, cut to `Config::description_width`.
+    fn list_description<'a>(&self, description: &'a str) -> Cow<'a, str> {
+        let width = match self.config.description_width {
+            0 if io::stdout().is_terminal() => terminal::size().ok().map(|(columns, _)| usize::from(columns / 2)),
+            0 => None,
+            width => Some(width),
+        };
+        match width {
+            Some(width) => truncate_display(description, width.max(10)),
+            None => Cow::Borrowed(description),
+        }
+    }
+
+    /// Prints search results grouped by list, numbered so one can be picked.
+    fn display_search_results(&self, results: &[(&str, &Task)]) {
+        if results.is_empty() {
+            println!("No matching tasks.");
+            return;
+        }
+
+        let mut current_list = None;
+        for (index, (list_name, task)) in results.iter().enumerate() {
+            if current_list != Some(*list_name) {
+                println!("List '{}':", list_name);
+                current_list = Some(*list_name);
+            }
+            println!("  {}. #{} {}", index + 1, task.id, self.list_description(&task.description));
+        }
+    }
+}
+
+/// Shortens `text` to at most `max_cols` terminal columns, ending with "…"
+/// when anything was cut. Cuts fall between grapheme clusters only, so emoji
+/// sequences, wide CJK characters and letters with combining marks are
+/// never split.
+fn truncate_display(text: &str, max_cols: usize) -> Cow<'_, str> {
+    if text.width() <= max_cols {
+        return Cow::Borrowed(text);
+    }
+    let Some(budget) = max_cols.checked_sub(1) else {
+        return Cow::Borrowed("");
+    };
+    let mut width = 0;
+    let mut end = 0;
+    for (index, grapheme) in text.grapheme_indices(true) {
+        width += grapheme.width();
+        if width > budget {
+            break;
+        }
+        end = index + grapheme.len();
+    }
+    Cow::Owned(format!("{}…", &text[..end]))
+}
+
+/// Splits `text` into lines of at most `width` characters, breaking at spaces
+/// where possible and inside words longer than a line.
+#[cfg(feature = "pdf")]
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        if !line.is_empty() && line.chars().count() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        while word.len() > width {
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            lines.push(word.drain(..width).collect());
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.extend(word);
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Renders a month as a calendar grid, one week per row starting on
+/// `week_start`. Days with tasks due show the count, e.g. "14(2)", and today
+/// is bracketed, e.g. "[9]". Returns an empty string for an invalid month.
+fn format_month(
+    year: i32,
+    month: u32,
+    due_counts: &HashMap<NaiveDate, usize>,
+    today: NaiveDate,
+    week_start: Weekday,
+) -> String {
+    const CELL_WIDTH: usize = 7;
+    let Some(first) = NaiveDate::from_ymd_opt(year, month, 1) else {
+        return String::new();
+    };
+    let days_in_month = (first + Months::new(1) - first).num_days() as usize;
+
+    let mut lines = vec![format!("{:^width$}", first.format("%B %Y").to_string(), width = CELL_WIDTH * 7)];
+    let mut header = String::new();
+    let mut weekday = week_start;
+    for _ in 0..7 {
+        header.push_str(&format!("{:>width$}", weekday.to_string(), width = CELL_WIDTH));
+        weekday = weekday.succ();
+    }
+    lines.push(header);
+
+    let offset = first.weekday().days_since(week_start) as usize;
+    let mut week = " ".repeat(CELL_WIDTH * offset);
+    for (i, date) in first.iter_days().take(days_in_month).enumerate() {
+        let mut cell = if date == today { format!("[{}]", date.day()) } else { date.day().to_string() };
+        if let Some(count) = due_counts.get(&date).filter(|count| **count > 0) {
+            cell.push_str(&format!("({})", count));
+        }
+        week.push_str(&format!("{:>width$}", cell, width = CELL_WIDTH));
+        if (offset + i + 1).is_multiple_of(7) {
+            lines.push(std::mem::take(&mut week));
+        }
+    }
+    if !week.is_empty() {
+        lines.push(week);
+    }
+
+    let mut calendar: String = lines.iter().map(|line| format!("{}\n", line.trim_end())).collect();
+    calendar.truncate(calendar.trim_end().len());
+    calendar.push('\n');
+    calendar
+}
+
+/// Prints one month of the user's due dates and lets them list the tasks due
+/// on a chosen day.
+fn show_calendar(app_data: &AppData, username: &str, year: i32, month: u32) {
+    let today = app_data.config.display_timezone().date(Utc::now());
+    let due_counts = app_data.due_counts(username);
+    if app_data.config.accessible {
+        let mut days: Vec<(&NaiveDate, &usize)> =
+            due_counts.iter().filter(|(date, _)| date.year() == year && date.month() == month).collect();
+        days.sort();
+        if days.is_empty() {
+            println!("Nothing due this month.");
+        }
+        for (date, count) in days {
+            println!("{}: {} {} due.", date.format("%A, %B %-d"), count, if *count == 1 { "task" } else { "tasks" });
+        }
+    } else {
+        print!("{}", format_month(year, month, &due_counts, today, app_data.config.week_start));
+    }
+    for (date, points) in app_data.overbooked_days(username) {
+        if date.year() == year && date.month() == month {
+            println!("{}", overbooked_warning(date, points, app_data.config.daily_capacity));
+        }
+    }
+
+    println!("Enter a day to list the tasks due on it, or leave empty to go back:");
+    let input = read_line();
+    if input.is_empty() {
+        return;
+    }
+    let Some(date) = input.parse().ok().and_then(|day| NaiveDate::from_ymd_opt(year, month, day)) else {
+        println!("Invalid day.");
+        return;
+    };
+    let due: Vec<&Task> = app_data.tasks(username).iter().filter(|task| task.due_date == Some(date)).collect();
+    if due.is_empty() {
+        println!("Nothing due on {}.", date);
+    }
+    for task in due {
+        if app_data.config.accessible {
+            println!("{}", app_data.format_task_accessible(task, today));
+        } else {
+            println!("{}", app_data.format_task(task));
+        }
+    }
+}
+
+/// "Wed 2024-03-06 is overbooked: 13/8 points"
+fn overbooked_warning(date: NaiveDate, points: u32, capacity: u32) -> String {
+    format!("{} is overbooked: {}/{} points", date.format("%a %Y-%m-%d"), points, capacity)
+}
+
+/// "•", "••" or "•••" for a task untouched since `since`: one dot for each of
+/// the `thresholds` (in days) that has passed by `now`. Empty before the
+/// first one.
+fn age_marker(since: DateTime<Utc>, now: DateTime<Utc>, thresholds: [u32; 3]) -> &'static str {
+    let days = (now - since).num_days();
+    let passed = thresholds.iter().filter(|&&threshold| days >= i64::from(threshold)).count();
+    ["", "•", "••", "•••"][passed]
+}
+
+/// "Monday", "Tuesday" and so on; `Weekday` only displays as "Mon".
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+/// "1", "1 and 2", "1, 2 and 3".
+fn join_words(words: &[String]) -> String {
+    match words {
+        [] => String::new(),
+        [word] => word.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+/// The task as a Markdown checklist item with its details as sub-items, as
+/// written by `export --markdown` and copied by `tasks copy --full`. Tasks
+/// have no notes of their own; the link is the closest thing.
+fn format_task_markdown(task: &Task) -> String {
+    let mut block = format!("- [{}] {}\n", if task.completed { "x" } else { " " }, task.description);
+    block.push_str(&format!("  - Priority: {}\n", task.priority.label()));
+    if let Some(due_date) = task.due_date {
+        block.push_str(&format!("  - Due: {}\n", due_date));
+    }
+    if !task.tags.is_empty() {
+        block.push_str(&format!("  - Tags: {}\n", task.tags.join(", ")));
+    }
+    if let Some(context) = &task.context {
+        block.push_str(&format!("  - Context: {}\n", context));
+    }
+    if let Some(link) = &task.link {
+        block.push_str(&format!("  - Link: <{}>\n", link));
+    }
+    block
+}
+
+/// The checklist items of a Markdown document as tasks, in order. Items may
+/// be indented and start with `-`, `*` or `+`; `[x]` or `[X]` marks them
+/// completed. Items without text are skipped.
+fn parse_markdown_checklist(text: &str) -> Vec<Task> {
+    let now = Utc::now();
+    text.lines()
+        .filter_map(|line| {
+            let item = line.trim_start().strip_prefix(['-', '*', '+'])?.strip_prefix(' ')?;
+            let (completed, description) = match item.get(..3)? {
+                "[ ]" => (false, &item[3..]),
+                "[x]" | "[X]" => (true, &item[3..]),
+                _ => return None,
+            };
+            let description = description.trim();
+            if description.is_empty() {
+                return None;
+            }
+            let mut task = Task::new(0, description.to_string());
+            if completed {
+                task.completed = true;
+                task.completed_at = Some(now);
+                task.progress = 100;
+            }
+            Some(task)
+        })
+        .collect()
+}
+
+/// Puts `text` on the system clipboard. Needs the "clipboard" feature.
+#[cfg(feature = "clipboard")]
+fn set_clipboard(text: &str) -> Result<(), String> {
+    arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text)).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn set_clipboard(_text: &str) -> Result<(), String> {
+    Err("this build has no clipboard support".to_string())
+}
+
+/// Copies `text` to the clipboard, or prints it when there's no clipboard
+/// to use (SSH sessions, headless machines, builds without the feature).
+fn copy_text(text: &str) {
+    match set_clipboard(text) {
+        Ok(()) => println!("Copied to the clipboard."),
+        Err(e) => {
+            println!("Couldn't use the clipboard ({}); here is the text instead:", e);
+            println!("{}", text.trim_end());
+        }
+    }
+}
+
+/// Taskwarrior's date format, e.g. "20240305T143000Z".
+fn taskwarrior_date(timestamp: DateTime<Utc>) -> String {
+    timestamp.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// "just now", "1 minute ago", "5 hours ago", "3 days ago" and so on.
+fn format_elapsed(elapsed: TimeDelta) -> String {
+    let (count, unit) = if elapsed.num_days() > 0 {
+        (elapsed.num_days(), "day")
+    } else if elapsed.num_hours() > 0 {
+        (elapsed.num_hours(), "hour")
+    } else if elapsed.num_minutes() > 0 {
+        (elapsed.num_minutes(), "minute")
+    } else {
+        return "just now".to_string();
+    };
+    format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Writes a data file along with a `<file>.sha256` sidecar holding its
+/// checksum. The previous version of both is kept as `<file>.bak`.
+fn write_data_file(path: &Path, bytes: &[u8]) -> Result<(), AppError> {
+    let checksum_path = with_suffix(path, ".sha256");
+    if path.exists() {
+        fs::copy(path, with_suffix(path, ".bak"))?;
+        if checksum_path.exists() {
+            fs::copy(&checksum_path, with_suffix(path, ".bak.sha256"))?;
+        }
+    }
+
+    fs::write(path, bytes)?;
+    fs::write(checksum_path, sha256_hex(bytes))?;
+    Ok(())
+}
+
+/// Deserializes JSON read from `path`, pointing at the spot in the file
+/// where it fails to parse.
+fn parse_json_file<T: DeserializeOwned>(path: &Path, bytes: &[u8]) -> Result<T, AppError> {
+    // Saved files are a single line, so only show the bytes around the error.
+    const CONTEXT: usize = 40;
+
+    serde_json::from_slice(bytes).map_err(|error| {
+        let line = bytes.split(|&byte| byte == b'\n').nth(error.line().saturating_sub(1));
+        let pointer = match line {
+            Some(line) if error.line() > 0 => {
+                let column = error.column().saturating_sub(1).min(line.len());
+                let start = column.saturating_sub(CONTEXT);
+                let end = (column + CONTEXT).min(line.len());
+                let excerpt = String::from_utf8_lossy(&line[start..end]);
+                format!("{}\n{}^", excerpt.trim_end(), " ".repeat(column - start))
+            }
+            _ => String::new(),
+        };
+        AppError::Parse { path: path.to_path_buf(), error, pointer }
+    })
+}
+
+/// Reads a data file, checking it against its `.sha256` sidecar when
+/// `verify` is set. Files without a sidecar (older saves) are accepted.
+fn read_data_file(path: &Path, verify: bool) -> Result<Vec<u8>, AppError> {
+    let bytes = fs::read(path)?;
+    let checksum_path = with_suffix(path, ".sha256");
+    if verify && checksum_path.exists() {
+        let expected = fs::read_to_string(checksum_path)?;
+        if expected.trim() != sha256_hex(&bytes) {
+            return Err(AppError::ChecksumMismatch(path.to_path_buf()));
+        }
+    }
+    Ok(bytes)
+}
+
+/// Replaces a data file and its checksum with the copies kept by the last save.
+fn restore_backup(path: &Path) -> Result<(), AppError> {
+    fs::copy(with_suffix(path, ".bak"), path)?;
+    let backup_checksum = with_suffix(path, ".bak.sha256");
+    let checksum_path = with_suffix(path, ".sha256");
+    if backup_checksum.exists() {
+        fs::copy(backup_checksum, checksum_path)?;
+    } else if checksum_path.exists() {
+        // The backup predates checksums; the current sidecar doesn't describe it.
+        fs::remove_file(checksum_path)?;
+    }
+    Ok(())
+}
+
+/// Checks whether files can be created in `dir` by writing a probe file.
+fn is_dir_writable(dir: &Path) -> bool {
+    let probe = dir.join(".write_test");
+    let writable = File::create(&probe).is_ok();
+    if writable {
+        let _ = fs::remove_file(probe);
+    }
+    writable
+}
+
+/// Monday and Sunday of the week containing `date`.
+fn week_bounds(date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let start = date - Days::new(date.weekday().num_days_from_monday() as u64);
+    (start, start + Days::new(6))
+}
+
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders one report section, or nothing when `tasks` is empty.
+fn html_task_table(title: &str, tasks: &[&Task]) -> String {
+    if tasks.is_empty() {
+        return String::new();
+    }
+
+    const CELL: &str = "style=\"border: 1px solid #ccc; padding: 0.3em 0.8em; text-align: left;\"";
+    let mut html = format!(
+        "<h2>{}</h2>\n<table style=\"border-collapse: collapse; margin-bottom: 1.5em;\">\n\
+         <tr><th {cell}>ID</th><th {cell}>Description</th><th {cell}>Due</th><th {cell}>Status</th></tr>\n",
+        html_escape(title),
+        cell = CELL
+    );
+    for task in tasks {
+        let due = task.due_date.map(|due| due.to_string()).unwrap_or_default();
+        html.push_str(&format!(
+            "<tr><td {cell}>{}</td><td {cell}>{}</td><td {cell}>{}</td><td {cell}>{}</td></tr>\n",
+            task.id,
+            html_escape(&task.description),
+            due,
+            task.status().label(),
+            cell = CELL
+        ));
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+/// Opens `path` with the platform's default application.
+fn open_in_browser(path: &Path) -> io::Result<()> {
+    let program = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(target_os = "windows") {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    Command::new(program).arg(path).spawn().map(|_| ())
+}
+
+/// Whether `link` is an absolute http(s) URL without whitespace.
+fn is_valid_link(link: &str) -> bool {
+    let has_scheme = ["http://", "https://"]
+        .iter()
+        .any(|scheme| link.len() > scheme.len() && link[..scheme.len()].eq_ignore_ascii_case(scheme));
+    has_scheme && !link.contains(char::is_whitespace)
+}
+
+/// Opens `link` in the system browser, or prints it when links shouldn't be
+/// opened, there's no graphical session or the browser fails to start.
+fn open_link(config: &Config, link: &str) {
+    // On Linux and the BSDs a browser needs an X11 or Wayland session.
+    let has_display = cfg!(any(target_os = "windows", target_os = "macos"))
+        || env::var_os("DISPLAY").is_some()
+        || env::var_os("WAYLAND_DISPLAY").is_some();
+    if !config.open_links || !has_display {
+        println!("{}", link);
+        return;
+    }
+    match open::that(link) {
+        Ok(()) => println!("Opened {}", link),
+        Err(e) => println!("Couldn't start a browser ({}): {}", e, link),
+    }
+}
+
+fn print_rebalance_plan(plan: &HashMap<u32, (Priority, Priority)>) {
+    let mut ids: Vec<&u32> = plan.keys().collect();
+    ids.sort();
+    for id in ids {
+        let (old, new) = plan[id];
+        println!("#{}: {} -> {}", id, old.label(), new.label());
+    }
+}
+
+/// Runs `operation` until it succeeds or `attempts` runs are used up. Only I/O
+/// errors are retried; anything else is returned straight away.
+fn retry_with_backoff<T>(
+    attempts: u32,
+    initial_backoff: Duration,
+    mut operation: impl FnMut() -> Result<T, AppError>,
+) -> Result<T, AppError> {
+    let mut backoff = initial_backoff;
+    let mut attempt = 1;
+    loop {
+        match operation() {
+            Err(AppError::Io(e)) if attempt < attempts => {
+                println!("Attempt {} failed ({}), retrying in {} ms...", attempt, e, backoff.as_millis());
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn read_line() -> String {
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).unwrap();
+    input.trim().to_string()
+}
+
+/// The next trimmed line of `input`, or `None` once it runs out.
+fn next_line(input: &mut impl BufRead) -> Option<String> {
+    let mut line = String::new();
+    match input.read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim().to_string()),
+    }
+}
+
+/// Asks for a task ID (numeric or UUID prefix), reporting invalid input.
+fn prompt_task_id(app_data: &AppData, username: &str, message: &str) -> Option<u32> {
+    println!("{}", message);
+    match app_data.resolve_task_id(username, &read_line()) {
+        Ok(task_id) => Some(task_id),
+        Err(e) => {
+            println!("Error: {}", e);
+            None
+        }
+    }
+}
+
+/// Shows past descriptions starting with `prefix` and returns the one picked
+/// by number, or whatever else was typed instead.
+fn prompt_completion(app_data: &AppData, username: &str, prefix: &str) -> String {
+    let suggestions = app_data.autocomplete_description(username, prefix);
+    if suggestions.is_empty() {
+        println!("No earlier tasks start with '{}'. Enter task description:", prefix);
+        return read_line();
+    }
+
+    for (index, suggestion) in suggestions.iter().enumerate() {
+        println!("{}. {}", index + 1, suggestion);
+    }
+    println!("Enter a number to use a suggestion, or type the description:");
+    let input = read_line();
+    match input.parse::<usize>().ok().and_then(|n| suggestions.get(n.wrapping_sub(1))) {
+        Some(suggestion) => suggestion.clone(),
+        None => input,
+    }
+}
+
+/// Lists tasks similar to `description` and asks whether to add it anyway.
+/// Returns true straight away if there are none.
+fn confirm_add_task(app_data: &AppData, username: &str, description: &str) -> bool {
+    let similar = app_data.find_similar_tasks(username, description, SIMILARITY_THRESHOLD);
+    if similar.is_empty() {
+        return true;
+    }
+
+    println!("Similar tasks found:");
+    for task in similar {
+        println!("  #{} {}", task.id, task.description);
+    }
+    println!("Add it anyway? (y/n)");
+    read_line().eq_ignore_ascii_case("y")
+}
+
+/// Prints the locked tasks a bulk operation is about to skip.
+fn report_locked_skipped(app_data: &AppData, username: &str, op: &Op) {
+    for task in app_data.locked_skipped_by(username, op) {
+        println!("Skipping #{} {} (locked)", task.id, task.description);
+    }
+}
+
+/// Journals and applies an operation, reporting any error and the locked
+/// tasks it skips. Returns the number of tasks affected on success.
+fn run_op(app_data: &mut AppData, username: &str, op: Op) -> Option<usize> {
+    report_locked_skipped(app_data, username, &op);
+    match app_data.execute(username, op) {
+        Ok(affected) => Some(affected),
+        Err(e) => {
+            println!("Error: {}", e);
+            None
+        }
+    }
+}
+
+/// Asks for each filter criterion in turn; empty answers leave it unset.
+fn prompt_filter() -> Filter {
+    let optional = |value: String| if value.is_empty() { None } else { Some(value) };
+
+    println!("Description contains (empty for any):");
+    let text = optional(read_line());
+    println!("Status - Pending, In progress or Completed (empty for any):");
+    let status = Status::parse(&read_line());
+    println!("Priority - Critical, High, Medium or Low (empty for any):");
+    let priority = Priority::parse(&read_line());
+    println!("Has tag (empty for any):");
+    let tag = optional(read_line());
+
+    Filter { text, status, priority, tag }
+}
+
+/// Review mode: walks through the user's open tasks matching `filter`, then
+/// applies the decisions in one batch once the summary is confirmed.
+/// Answers are read from `input`; stopping early still offers what was
+/// decided so far, and declining or running out of input changes nothing.
+fn run_review(app_data: &mut AppData, username: &str, filter: &Filter, input: &mut impl BufRead) {
+    let tasks = app_data.review_candidates(username, filter);
+    if tasks.is_empty() {
+        println!("No open tasks to review.");
+        return;
+    }
+    let decisions = collect_review_decisions(&tasks, input);
+
+    let count = |wanted: fn(&ReviewDecision) -> bool| decisions.iter().filter(|(_, decision)| wanted(decision)).count();
+    let completed = count(|decision| *decision == ReviewDecision::Complete);
+    let edited = count(|decision| matches!(decision, ReviewDecision::Edit(_)));
+    let deleted = count(|decision| *decision == ReviewDecision::Delete);
+    let kept = count(|decision| *decision == ReviewDecision::Keep);
+    println!(
+        "Reviewed {} of {} task(s): {} to complete, {} to edit, {} to delete, {} kept open.",
+        decisions.len(),
+        tasks.len(),
+        completed,
+        edited,
+        deleted,
+        kept
+    );
+    if completed + edited + deleted == 0 {
+        println!("Nothing to change.");
+        return;
+    }
+    println!("Apply these changes? (y/n)");
+    if !next_line(input).is_some_and(|answer| answer.eq_ignore_ascii_case("y")) {
+        println!("No changes made.");
+        return;
+    }
+    if let Some(changed) = run_op(app_data, username, Op::ApplyReview { decisions }) {
+        println!("{} task(s) updated.", changed);
+    }
+}
+
+/// Walks through `tasks` asking y (complete), n (keep open), s (skip),
+/// e (edit) or d (delete) for each, reading answers from `input`.
+/// `q` or running out of input ends the walk early. Nothing is applied;
+/// skipped tasks get no decision.
+fn collect_review_decisions(tasks: &[&Task], input: &mut impl BufRead) -> Vec<(u32, ReviewDecision)> {
+    let mut decisions = Vec::new();
+    'tasks: for (index, task) in tasks.iter().enumerate() {
+        println!("({}/{}) #{} {}", index + 1, tasks.len(), task.id, task.description);
+        loop {
+            println!("Done? y/n, s to skip, e to edit, d to delete, q to stop:");
+            let Some(answer) = next_line(input) else {
+                break 'tasks;
+            };
+            let decision = match answer.to_lowercase().as_str() {
+                "y" => ReviewDecision::Complete,
+                "n" => ReviewDecision::Keep,
+                "s" => continue 'tasks,
+                "d" => ReviewDecision::Delete,
+                "q" => break 'tasks,
+                "e" => {
+                    println!("Enter new task description:");
+                    match next_line(input) {
+                        Some(description) if !description.is_empty() => ReviewDecision::Edit(description),
+                        Some(_) => continue,
+                        None => break 'tasks,
+                    }
+                }
+                _ => {
+                    println!("Invalid choice.");
+                    continue;
+                }
+            };
+            decisions.push((task.id, decision));
+            continue 'tasks;
+        }
+    }
+    decisions
+}
+
+/// Prompts for credentials and returns the username on success.
+fn log_in(app_data: &AppData) -> Option<String> {
+    println!("Enter username: ");
+    let username = read_line();
+
+    println!("Enter password: ");
+    let password = read_line();
+
+    if app_data.authenticate(&username, &password) {
+        Some(username)
+    } else {
+        None
+    }
+}
+
+// Exit codes for command-line mode.
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+// A destructive command (or its --dry-run) had nothing to change.
+const EXIT_NOTHING_TO_DO: i32 = 2;
+
+/// Usage and description of every command at the `> ` prompt, for `help`
+/// and for suggesting the closest one to a typo.
+const REPL_COMMANDS: &[(&str, &str)] = &[
+    ("add <description> [due:DATE]", "Add a task; DATE is YYYY-MM-DD, today, tomorrow or a weekday"),
+    ("done <id>...", "Mark tasks as completed"),
+    ("rm <id>...", "Remove tasks"),
+    ("ls [--pending|--in-progress|--completed]", "List tasks"),
+    ("undo", "Undo the last change"),
+    ("menu", "Switch to the numbered menu"),
+    ("help", "Show this list"),
+    ("quit", "Save and exit"),
+];
+
+/// A line typed at the `> ` prompt.
+#[derive(Debug, PartialEq)]
+enum ReplCommand {
+    Add { description: String, due_date: Option<NaiveDate>, link: Option<String> },
+    /// Task IDs as typed, numeric or UUID prefixes.
+    Done { task_ids: Vec<String> },
+    Remove { task_ids: Vec<String> },
+    List { status: Option<Status> },
+    Undo,
+    Help,
+    Menu,
+    Quit,
+    /// A blank line.
+    Empty,
+}
+
+impl ReplCommand {
+    fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            ReplCommand::Add { .. } | ReplCommand::Done { .. } | ReplCommand::Remove { .. } | ReplCommand::Undo
+        )
+    }
+}
+
+/// One word of a command line; `quoted` words are never taken as flags or
+/// `due:` attributes.
+#[derive(Debug, PartialEq)]
+struct Token {
+    text: String,
+    quoted: bool,
+}
+
+/// Splits a command line on whitespace. Single or double quotes group words,
+/// including spaces, and a backslash escapes the next character.
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', _) => {
+                let escaped = chars.next().ok_or("Nothing to escape after '\\' at the end of the line.")?;
+                current.get_or_insert(Token { text: String::new(), quoted: false }).text.push(escaped);
+            }
+            (c, Some(open)) if c == open => quote = None,
+            (c, Some(_)) => current.get_or_insert(Token { text: String::new(), quoted: true }).text.push(c),
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                current.get_or_insert(Token { text: String::new(), quoted: true }).quoted = true;
+            }
+            (c, None) if c.is_whitespace() => tokens.extend(current.take()),
+            (c, None) => current.get_or_insert(Token { text: String::new(), quoted: false }).text.push(c),
+        }
+    }
+    if let Some(open) = quote {
+        return Err(format!("Missing closing {}.", open));
+    }
+    tokens.extend(current);
+    Ok(tokens)
+}
+
+/// A due date typed at the prompt: YYYY-MM-DD, "today", "tomorrow", or a
+/// weekday name ("fri", "friday") meaning its next occurrence after today.
+fn parse_due_input(input: &str, today: NaiveDate) -> Option<NaiveDate> {
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Some(date);
+    }
+    match input.to_lowercase().as_str() {
+        "today" => Some(today),
+        "tomorrow" => today.succ_opt(),
+        input => {
+            let weekday: Weekday = input.parse().ok()?;
+            let days_ahead = (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday() - 1) % 7 + 1;
+            today.checked_add_days(Days::new(u64::from(days_ahead)))
+        }
+    }
+}
+
+/// Parses a line typed at the `> ` prompt. Errors are messages for the
+/// user, suggesting the closest command for an unknown one.
+fn parse_repl_line(line: &str, today: NaiveDate) -> Result<ReplCommand, String> {
+    let tokens = tokenize(line)?;
+    let Some((command, args)) = tokens.split_first() else {
+        return Ok(ReplCommand::Empty);
+    };
+    let words = || args.iter().map(|token| token.text.clone()).collect::<Vec<_>>();
+
+    match command.text.as_str() {
+        "add" => {
+            let mut due_date = None;
+            let mut link = None;
+            let mut description = Vec::new();
+            for token in args {
+                if !token.quoted && link.is_none() && is_valid_link(&token.text) {
+                    link = Some(token.text.clone());
+                    continue;
+                }
+                match token.text.strip_prefix("due:") {
+                    Some(input) if !token.quoted => {
+                        let Some(date) = parse_due_input(input, today) else {
+                            return Err(format!(
+                                "Invalid due date '{}', expected YYYY-MM-DD, today, tomorrow or a weekday.",
+                                input
+                            ));
+                        };
+                        due_date = Some(date);
+                    }
+                    _ => description.push(token.text.as_str()),
+                }
+            }
+            let description = description.join(" ");
+            if description.trim().is_empty() {
+                return Err("Usage: add <description> [due:DATE] [URL]".to_string());
+            }
+            Ok(ReplCommand::Add { description, due_date, link })
+        }
+        "done" => Ok(ReplCommand::Done { task_ids: words() }),
+        "rm" => Ok(ReplCommand::Remove { task_ids: words() }),
+        "ls" => {
+            let mut status = None;
+            for token in args {
+                status = Some(match token.text.as_str() {
+                    "--pending" => Status::Pending,
+                    "--in-progress" => Status::InProgress,
+                    "--completed" => Status::Completed,
+                    other => return Err(format!("Unknown option '{}' for ls.", other)),
+                });
+            }
+            Ok(ReplCommand::List { status })
+        }
+        "undo" => Ok(ReplCommand::Undo),
+        "help" => Ok(ReplCommand::Help),
+        "menu" => Ok(ReplCommand::Menu),
+        "quit" | "exit" => Ok(ReplCommand::Quit),
+        unknown => {
+            let closest = REPL_COMMANDS
+                .iter()
+                .map(|(usage, _)| usage.split(' ').next().unwrap_or(usage))
+                // Ties go to the command sharing the first letter, which typos rarely change.
+                .min_by_key(|name| (strsim::levenshtein(name, unknown), name.chars().next() != unknown.chars().next()))
+                .filter(|name| strsim::levenshtein(name, unknown) <= 2);
+            match closest {
+                Some(name) => Err(format!("Unknown command '{}'. Did you mean '{}'?", unknown, name)),
+                None => Err(format!("Unknown command '{}'. Type 'help' for the list of commands.", unknown)),
+            }
+        }
+    }
+}
+
+/// The `search --json` output: an array of `{"list": ..., "task": {...}}`.
+fn search_results_json(results: &[(&str, &Task)]) -> serde_json::Value {
+    results.iter().map(|(list_name, task)| serde_json::json!({ "list": list_name, "task": task })).collect()
+}
+
+/// Runs a single non-interactive command, e.g. `search "milk" --all-lists --json`,
+/// and returns the process exit code.
+fn run_command(app_data: &mut AppData, args: &[String]) -> i32 {
+    let Some(username) = log_in(app_data) else {
+        println!("Authentication failed.");
+        return EXIT_FAILURE;
+    };
+    app_data.use_settings_of(&username);
+
+    let flags: Vec<&str> = args[1..].iter().map(String::as_str).filter(|arg| arg.starts_with("--")).collect();
+    let positional: Vec<&str> = args[1..].iter().map(String::as_str).filter(|arg| !arg.starts_with("--")).collect();
+
+    match args[0].as_str() {
+        "search" => {
+            let query = positional.join(" ");
+            let results = if flags.contains(&"--all-lists") {
+                app_data.search_all(&username, &query)
+            } else {
+                app_data.search_own(&username, &query)
+            };
+
+            if flags.contains(&"--json") {
+                println!("{}", serde_json::to_string_pretty(&search_results_json(&results)).unwrap());
+            } else {
+                app_data.display_search_results(&results);
+            }
+            EXIT_OK
+        }
+        "sync-resolve" => {
+            let Some(other) = positional.first() else {
+                println!("Usage: sync-resolve <other tasks.json>");
+                return EXIT_FAILURE;
+            };
+            if app_data.read_only {
+                println!("Not available in read-only mode.");
+                return EXIT_FAILURE;
+            }
+
+            let theirs = fs::read(other)
+                .map_err(AppError::from)
+                .and_then(|bytes| Ok(TasksFile::parse(Path::new(other), &bytes)?.task_lists.into_owned()));
+            let conflicts = match theirs {
+                Ok(theirs) => app_data.sync_resolve(theirs),
+                Err(e) => {
+                    println!("Error: {}", e);
+                    return EXIT_FAILURE;
+                }
+            };
+            for conflict in &conflicts {
+                println!("Conflict: {}", conflict);
+            }
+            match save_app_data(app_data) {
+                Ok(()) => {
+                    println!("Synced with {} ({} conflict(s)).", other, conflicts.len());
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error saving data: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "cal" => {
+            let today = app_data.config.display_timezone().date(Utc::now());
+            let (year, month) = if flags.contains(&"--month") {
+                let month = positional
+                    .first()
+                    .and_then(|month| NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d").ok());
+                match month {
+                    Some(month) => (month.year(), month.month()),
+                    None => {
+                        println!("Usage: cal [--month YYYY-MM]");
+                        return EXIT_FAILURE;
+                    }
+                }
+            } else {
+                (today.year(), today.month())
+            };
+
+            let due_counts = app_data.due_counts(&username);
+            print!("{}", format_month(year, month, &due_counts, today, app_data.config.week_start));
+            for (date, points) in app_data.overbooked_days(&username) {
+                if date.year() == year && date.month() == month {
+                    println!("{}", overbooked_warning(date, points, app_data.config.daily_capacity));
+                }
+            }
+            EXIT_OK
+        }
+        "tags" => {
+            if app_data.users.get(&username).is_none_or(|user| user.role != Role::Admin) {
+                println!("Error: {}", AppError::PermissionDenied);
+                return EXIT_FAILURE;
+            }
+
+            let tags = app_data.all_tags();
+            if tags.is_empty() {
+                println!("No tags in use.");
+                return EXIT_NOTHING_TO_DO;
+            }
+            for tag in tags {
+                println!("{}", tag);
+            }
+            EXIT_OK
+        }
+        "most-neglected" => {
+            if app_data.users.get(&username).is_none_or(|user| user.role != Role::Admin) {
+                println!("Error: {}", AppError::PermissionDenied);
+                return EXIT_FAILURE;
+            }
+
+            let now = Utc::now();
+            match app_data.most_neglected(now) {
+                Some((owner, task)) => {
+                    let age = format_elapsed(now - task.created_at);
+                    println!("{} #{} '{}', created {}", owner, task.id, task.description, age);
+                    EXIT_OK
+                }
+                None => {
+                    println!("No open tasks.");
+                    EXIT_NOTHING_TO_DO
+                }
+            }
+        }
+        #[cfg(feature = "webhooks")]
+        "webhook" if positional.first() == Some(&"test") => {
+            let client = match reqwest::blocking::Client::builder().timeout(WEBHOOK_TIMEOUT).build() {
+                Ok(client) => client,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    return EXIT_FAILURE;
+                }
+            };
+            if app_data.config.webhooks.is_empty() {
+                println!("No webhooks configured.");
+                return EXIT_NOTHING_TO_DO;
+            }
+            let secret = match app_data.config.webhook_secret() {
+                Ok(secret) => secret,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    return EXIT_FAILURE;
+                }
+            };
+
+            let event = WebhookEvent {
+                event: "test",
+                username: username.clone(),
+                task: Task::new(0, "Sample task from 'webhook test'".to_string()),
+            };
+            let mut code = EXIT_OK;
+            for url in &app_data.config.webhooks {
+                match deliver_webhook(&client, url, secret.as_deref(), &event) {
+                    Ok(()) => println!("{}: delivered", url),
+                    Err(e) => {
+                        println!("{}: {}", url, e);
+                        code = EXIT_FAILURE;
+                    }
+                }
+            }
+            code
+        }
+        "secret" => {
+            if app_data.users.get(&username).is_none_or(|user| user.role != Role::Admin) {
+                println!("Error: {}", AppError::PermissionDenied);
+                return EXIT_FAILURE;
+            }
+            let data_dir = &app_data.config.data_dir;
+            match (positional.first(), positional.get(1)) {
+                (Some(&"set"), Some(name)) => {
+                    println!("Enter the value for '{}':", name);
+                    match secrets::set(data_dir, name, &read_line()) {
+                        Ok(secrets::Store::Keyring) => println!("Stored '{}' in the OS keyring.", name),
+                        Ok(secrets::Store::File(path)) => println!("Stored '{}' in {}.", name, path.display()),
+                        Err(e) => {
+                            println!("Error: {}", e);
+                            return EXIT_FAILURE;
+                        }
+                    }
+                    EXIT_OK
+                }
+                (Some(&"rm"), Some(name)) => match secrets::delete(data_dir, name) {
+                    Ok(true) => {
+                        println!("Removed '{}'.", name);
+                        EXIT_OK
+                    }
+                    Ok(false) => {
+                        println!("No secret named '{}'.", name);
+                        EXIT_NOTHING_TO_DO
+                    }
+                    Err(e) => {
+                        println!("Error: {}", e);
+                        EXIT_FAILURE
+                    }
+                },
+                _ => {
+                    println!("Usage: secret set <name> | secret rm <name>");
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "stats" if flags.contains(&"--heatmap-json") => {
+            let days = match positional.first().map(|days| days.parse::<u32>()) {
+                None => 365,
+                Some(Ok(days)) => days,
+                Some(Err(_)) => {
+                    println!("Usage: stats --heatmap-json [days]");
+                    return EXIT_FAILURE;
+                }
+            };
+            let heatmap: BTreeMap<NaiveDate, usize> =
+                app_data.task_completion_heatmap_data(&username, days).into_iter().collect();
+            println!("{}", serde_json::to_string_pretty(&heatmap).unwrap());
+            EXIT_OK
+        }
+        "stats" => {
+            if flags.contains(&"--json") {
+                return match app_data.stats_json(&username) {
+                    Ok(json) => {
+                        println!("{}", json);
+                        EXIT_OK
+                    }
+                    Err(e) => {
+                        println!("Error: {}", e);
+                        EXIT_FAILURE
+                    }
+                };
+            }
+
+            let stats = app_data.task_stats(&username, app_data.config.display_timezone().date(Utc::now()));
+            println!("Total: {}", stats.total);
+            println!("Pending: {}", stats.pending);
+            println!("In progress: {}", stats.in_progress);
+            println!("Completed: {}", stats.completed);
+            println!("Overdue: {}", stats.overdue);
+            for (priority, count) in stats.per_priority.iter().rev() {
+                println!("{} priority: {}", priority.label(), count);
+            }
+            let collation = app_data.config.collation();
+            let mut per_tag: Vec<_> = stats.per_tag.iter().collect();
+            per_tag.sort_by_cached_key(|(tag, _)| collation.collate_key(tag));
+            for (tag, count) in per_tag {
+                println!("Tag {}: {}", tag, count);
+            }
+            EXIT_OK
+        }
+        "diff" => {
+            let (Some(old_path), Some(new_path)) = (positional.first(), positional.get(1)) else {
+                println!("Usage: diff <old tasks.json> <new tasks.json>");
+                return EXIT_FAILURE;
+            };
+
+            let snapshots = AppData::from_tasks_file(Path::new(old_path))
+                .and_then(|old| Ok((old, AppData::from_tasks_file(Path::new(new_path))?)));
+            match snapshots {
+                Ok((old, new)) => {
+                    let changes: Vec<Change> =
+                        AppData::diff(&old, &new).into_iter().filter(|change| change.username() == username).collect();
+                    if changes.is_empty() {
+                        println!("No differences.");
+                        return EXIT_NOTHING_TO_DO;
+                    }
+                    for change in &changes {
+                        if app_data.config.accessible {
+                            println!("{}", change.sentence());
+                        } else {
+                            println!("{}", change);
+                        }
+                    }
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "postpone" => {
+            let Some(input) = positional.first() else {
+                println!("Usage: postpone <task id>");
+                return EXIT_FAILURE;
+            };
+            let today = Utc::now().date_naive();
+            let postponed = app_data.resolve_task_id(&username, input).and_then(|task_id| {
+                let due_date = app_data
+                    .postpone_date(&username, task_id, today)
+                    .ok_or_else(|| AppError::TaskNotFound(input.to_string()))?;
+                app_data.execute(&username, Op::SetDueDate { task_id, due_date: Some(due_date) })?;
+                save_app_data(app_data)?;
+                Ok((task_id, due_date))
+            });
+            match postponed {
+                Ok((task_id, due_date)) => {
+                    println!("Task #{} postponed to {}.", task_id, due_date.format("%a %Y-%m-%d"));
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "show" => {
+            let Some(input) = positional.first() else {
+                println!("Usage: show <task id> [--json]");
+                return EXIT_FAILURE;
+            };
+            if flags.contains(&"--json") {
+                let json = app_data.resolve_task_id(&username, input).and_then(|id| app_data.task_json(&username, id));
+                return match json {
+                    Ok(json) => {
+                        println!("{}", json);
+                        EXIT_OK
+                    }
+                    Err(e) => {
+                        println!("Error: {}", e);
+                        EXIT_FAILURE
+                    }
+                };
+            }
+            let task = app_data.resolve_task_id(&username, input).and_then(|task_id| {
+                app_data.task(&username, task_id).ok_or_else(|| AppError::TaskNotFound(input.to_string()))
+            });
+            match task {
+                Ok(task) => {
+                    print!("{}", app_data.format_task_detail(&username, task, Utc::now()));
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "tasks" if positional.first() == Some(&"list") => {
+            let tasks = if flags.contains(&"--archived") {
+                app_data.list_archived(&username).iter().collect()
+            } else if flags.contains(&"--stale") {
+                let days = match positional.get(1).map(|days| days.parse::<u32>()) {
+                    Some(Ok(days)) => days,
+                    None => app_data.config.stale_after_days,
+                    Some(Err(_)) => {
+                        println!("Usage: tasks list [--stale <days> | --archived]");
+                        return EXIT_FAILURE;
+                    }
+                };
+                app_data.find_stale_tasks(&username, days)
+            } else {
+                app_data.tasks(&username).iter().collect()
+            };
+            if tasks.is_empty() {
+                println!("No tasks.");
+                return EXIT_NOTHING_TO_DO;
+            }
+            let today = Utc::now().date_naive();
+            for task in tasks {
+                if app_data.config.accessible {
+                    println!("{}", app_data.format_task_accessible(task, today));
+                } else {
+                    println!("{}", app_data.format_task(task));
+                }
+            }
+            EXIT_OK
+        }
+        "tasks" if positional.first() == Some(&"admin") => match positional.get(1) {
+            Some(&"overview") => match app_data.print_summary_table(&username, &mut io::stdout()) {
+                Ok(()) => EXIT_OK,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            },
+            Some(&"search") => {
+                let query = positional[2..].join(" ");
+                let results = match app_data.search_all_users(&username, &query) {
+                    Ok(results) => results,
+                    Err(e) => {
+                        println!("Error: {}", e);
+                        return EXIT_FAILURE;
+                    }
+                };
+                if flags.contains(&"--json") {
+                    let json: Vec<_> = results
+                        .iter()
+                        .map(|(owner, task)| serde_json::json!({ "username": owner, "task": task }))
+                        .collect();
+                    println!("{}", serde_json::to_string_pretty(&json).unwrap());
+                } else if results.is_empty() {
+                    println!("No matching tasks.");
+                } else {
+                    for (owner, task) in results {
+                        println!("{}: #{} {}", owner, task.id, task.description);
+                    }
+                }
+                EXIT_OK
+            }
+            _ => {
+                println!("Usage: tasks admin overview | tasks admin search <text> [--json]");
+                EXIT_FAILURE
+            }
+        },
+        "tasks" if positional.first() == Some(&"matrix") => {
+            let now = Utc::now();
+            if flags.contains(&"--json") {
+                let json: serde_json::Map<String, serde_json::Value> = app_data
+                    .eisenhower_matrix(&username, now)
+                    .into_iter()
+                    .map(|(quadrant, tasks)| (quadrant.key().to_string(), serde_json::json!(tasks)))
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&json).unwrap());
+            } else {
+                print!("{}", app_data.format_eisenhower_matrix(&username, now));
+            }
+            EXIT_OK
+        }
+        "tasks" if positional.first() == Some(&"copy") => {
+            let Some(input) = positional.get(1) else {
+                println!("Usage: tasks copy <task id> [--full]");
+                return EXIT_FAILURE;
+            };
+            let task = app_data.resolve_task_id(&username, input).and_then(|task_id| {
+                app_data.task(&username, task_id).ok_or_else(|| AppError::TaskNotFound(input.to_string()))
+            });
+            match task {
+                Ok(task) if flags.contains(&"--full") => copy_text(&format_task_markdown(task)),
+                Ok(task) => copy_text(&task.description),
+                Err(e) => {
+                    println!("Error: {}", e);
+                    return EXIT_FAILURE;
+                }
+            }
+            EXIT_OK
+        }
+        "next" => match app_data.suggest_next_task(&username) {
+            Some(task) => {
+                println!("{}", app_data.format_task(task));
+                EXIT_OK
+            }
+            None => {
+                println!("Nothing to do right now.");
+                EXIT_NOTHING_TO_DO
+            }
+        },
+        "tasks" if positional.first() == Some(&"wbs") => {
+            let wbs = app_data.compute_work_breakdown_structure(&username);
+            println!("{}", serde_json::to_string_pretty(&wbs).unwrap());
+            EXIT_OK
+        }
+        "watch" => match watch_tasks(app_data, &username) {
+            Ok(()) => EXIT_OK,
+            Err(e) => {
+                println!("Error: {}", e);
+                EXIT_FAILURE
+            }
+        },
+        #[cfg(feature = "pdf")]
+        "export" if flags.contains(&"--pdf") => {
+            let Some(path) = positional.first() else {
+                println!("Usage: export --pdf <file>");
+                return EXIT_FAILURE;
+            };
+
+            match app_data.export_summary_pdf(&username, Path::new(path)) {
+                Ok(()) => {
+                    println!("Report written to {}", path);
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "export" if flags.contains(&"--taskwarrior") => {
+            let json = app_data.export_taskwarrior_json(&username);
+            let Some(path) = positional.first() else {
+                println!("{:#}", json);
+                return EXIT_OK;
+            };
+
+            match std::fs::write(path, format!("{:#}\n", json)) {
+                Ok(()) => {
+                    println!("Tasks written to {}, load them with 'task import {}'", path, path);
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "export" if flags.contains(&"--all-users") => {
+            let result = match positional.first() {
+                Some(path) => File::create(path)
+                    .map_err(AppError::from)
+                    .and_then(|mut file| app_data.export_csv_all_users(&username, &mut file)),
+                None => app_data.export_csv_all_users(&username, &mut io::stdout()),
+            };
+            match result {
+                Ok(()) => {
+                    if let Some(path) = positional.first() {
+                        println!("Tasks of all users written to {}", path);
+                    }
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "export" if flags.contains(&"--linear") => {
+            let result = match positional.first() {
+                Some(path) => File::create(path)
+                    .map_err(AppError::from)
+                    .and_then(|mut file| app_data.export_linear_csv(&username, &mut file)),
+                None => app_data.export_linear_csv(&username, &mut io::stdout()),
+            };
+            match result {
+                Ok(()) => {
+                    if let Some(path) = positional.first() {
+                        println!("Tasks written to {}, import it into Linear as CSV", path);
+                    }
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "export" if flags.contains(&"--asana") => {
+            let result = match positional.first() {
+                Some(path) => File::create(path)
+                    .map_err(AppError::from)
+                    .and_then(|mut file| app_data.export_asana_csv(&username, &mut file)),
+                None => app_data.export_asana_csv(&username, &mut io::stdout()),
+            };
+            match result {
+                Ok(()) => {
+                    if let Some(path) = positional.first() {
+                        println!("Tasks written to {}, import it into an Asana project as CSV", path);
+                    }
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "export" if flags.contains(&"--markdown") => {
+            let result = match positional.first() {
+                Some(path) => File::create(path)
+                    .map_err(AppError::from)
+                    .and_then(|mut file| app_data.export_markdown(&username, &mut file)),
+                None => app_data.export_markdown(&username, &mut io::stdout()),
+            };
+            match result {
+                Ok(()) => {
+                    if let Some(path) = positional.first() {
+                        println!("Tasks written to {}", path);
+                    }
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "export" if flags.contains(&"--notion") => {
+            let result = match positional.first() {
+                Some(path) => File::create(path)
+                    .map_err(AppError::from)
+                    .and_then(|mut file| app_data.export_notion_csv(&username, &mut file)),
+                None => app_data.export_notion_csv(&username, &mut io::stdout()),
+            };
+            match result {
+                Ok(()) => {
+                    if let Some(path) = positional.first() {
+                        println!("Tasks written to {}, import it into a Notion database", path);
+                    }
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "export" => {
+            let (true, Some(path)) = (flags.contains(&"--html"), positional.first()) else {
+                println!("Usage: export --html <file> [--open] | export --taskwarrior [file] | export --notion [file]");
+                println!("       export --linear [file] | export --asana [file] | export --markdown [file]");
+                println!("       export --all-users [file]");
+                return EXIT_FAILURE;
+            };
+
+            let html = app_data.export_html(&username, Utc::now().date_naive());
+            if let Err(e) = std::fs::write(path, html) {
+                println!("Error: {}", e);
+                return EXIT_FAILURE;
+            }
+            println!("Report written to {}", path);
+            if flags.contains(&"--open") {
+                if let Err(e) = open_in_browser(Path::new(path)) {
+                    println!("Could not open the report: {}", e);
+                }
+            }
+            EXIT_OK
+        }
+        "remove" | "clear-completed" => {
+            let plan = if args[0] == "remove" {
+                let ids: Result<Vec<u32>, AppError> =
+                    positional.iter().map(|input| app_data.resolve_task_id(&username, input)).collect();
+                ids.and_then(|ids| app_data.plan_remove(&username, &ids))
+            } else {
+                Ok(app_data.plan_clear_completed(&username))
+            };
+            let plan = match plan {
+                Ok(plan) => plan,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    return EXIT_FAILURE;
+                }
+            };
+
+            execute_plan(app_data, plan, flags.contains(&"--dry-run"))
+        }
+        "archive-stale" => {
+            let older_than_days = match positional.first().map(|days| days.parse::<u32>()) {
+                Some(Ok(days)) => days,
+                None if app_data.config.auto_archive_days > 0 => app_data.config.auto_archive_days,
+                _ => {
+                    println!("Usage: archive-stale <days>");
+                    return EXIT_FAILURE;
+                }
+            };
+            let now = Utc::now();
+            if app_data.archivable(&username, older_than_days, now).is_empty() {
+                println!("No tasks untouched for more than {} days.", older_than_days);
+                return EXIT_NOTHING_TO_DO;
+            }
+            match app_data.execute(&username, Op::ArchiveStale { older_than_days, now }) {
+                Ok(archived) => match save_app_data(app_data) {
+                    Ok(()) => {
+                        println!("Archived {} task(s) untouched for more than {} days.", archived, older_than_days);
+                        EXIT_OK
+                    }
+                    Err(e) => {
+                        println!("Error saving data: {}", e);
+                        EXIT_FAILURE
+                    }
+                },
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "status" => {
+            let op = match (positional.as_slice(), flags.contains(&"--next"), flags.contains(&"--prev")) {
+                ([input], true, false) | ([input], false, true) => {
+                    app_data.resolve_task_id(&username, input).map(|task_id| Op::StepStatus {
+                        task_id,
+                        forward: flags.contains(&"--next"),
+                    })
+                }
+                ([input, status], false, false) => app_data
+                    .resolve_task_id(&username, input)
+                    .map(|task_id| Op::SetStatus { task_id, status: status.to_string() }),
+                _ => {
+                    println!("Usage: status <task id> <status> | status <task id> --next | --prev");
+                    return EXIT_FAILURE;
+                }
+            };
+            match op.and_then(|op| app_data.execute(&username, op)) {
+                Ok(_) => match save_app_data(app_data) {
+                    Ok(()) => EXIT_OK,
+                    Err(e) => {
+                        println!("Error saving data: {}", e);
+                        EXIT_FAILURE
+                    }
+                },
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "find-replace" => {
+            let [find, replace] = positional[..] else {
+                println!("Usage: find-replace <find> <replace>");
+                return EXIT_FAILURE;
+            };
+            let op = Op::FindReplace { find: find.to_string(), replace: replace.to_string() };
+            report_locked_skipped(app_data, &username, &op);
+            match app_data.execute(&username, op) {
+                Ok(0) => {
+                    println!("No task descriptions contain '{}'.", find);
+                    EXIT_NOTHING_TO_DO
+                }
+                Ok(changed) => match save_app_data(app_data) {
+                    Ok(()) => {
+                        println!("{} task(s) updated.", changed);
+                        EXIT_OK
+                    }
+                    Err(e) => {
+                        println!("Error saving data: {}", e);
+                        EXIT_FAILURE
+                    }
+                },
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "rebalance-priorities" => {
+            report_locked_skipped(app_data, &username, &Op::RebalancePriorities);
+            let plan = app_data.plan_rebalance(&username);
+            if plan.is_empty() {
+                println!("Priorities are already balanced.");
+                return EXIT_NOTHING_TO_DO;
+            }
+            print_rebalance_plan(&plan);
+            if !flags.contains(&"--confirm") {
+                println!("Dry run; pass --confirm to apply.");
+                return EXIT_OK;
+            }
+            match app_data.execute(&username, Op::RebalancePriorities) {
+                Ok(changed) => match save_app_data(app_data) {
+                    Ok(()) => {
+                        println!("{} task(s) reprioritised.", changed);
+                        EXIT_OK
+                    }
+                    Err(e) => {
+                        println!("Error saving data: {}", e);
+                        EXIT_FAILURE
+                    }
+                },
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "complete-overdue" => {
+            let today = Utc::now().date_naive();
+            for task in app_data.blocked_overdue(&username, today) {
+                let reason = if task.locked { "locked" } else { "waiting on a dependency" };
+                println!("Skipping #{} {} ({})", task.id, task.description, reason);
+            }
+            match app_data.execute(&username, Op::CompleteOverdue { today }) {
+                Ok(0) => {
+                    println!("No overdue tasks to complete.");
+                    EXIT_NOTHING_TO_DO
+                }
+                Ok(completed) => match save_app_data(app_data) {
+                    Ok(()) => {
+                        println!("{} overdue task(s) completed.", completed);
+                        EXIT_OK
+                    }
+                    Err(e) => {
+                        println!("Error saving data: {}", e);
+                        EXIT_FAILURE
+                    }
+                },
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "merge-users" => {
+            let (Some(keep), Some(merge_from)) = (positional.first(), positional.get(1)) else {
+                println!("Usage: merge-users <keep> <merge from>");
+                return EXIT_FAILURE;
+            };
+
+            match app_data
+                .merge_users(&username, keep, merge_from)
+                .and_then(|report| save_app_data(app_data).map(|()| report))
+            {
+                Ok(report) => {
+                    println!(
+                        "Merged '{}' into '{}': {} task(s) and {} Pomodoro session(s) moved.",
+                        merge_from, keep, report.tasks_moved, report.sessions_merged
+                    );
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        #[cfg(feature = "caldav")]
+        "sync" if positional.first() == Some(&"caldav") => {
+            match app_data.sync_caldav(&username).and_then(|report| save_app_data(app_data).map(|()| report)) {
+                Ok(report) => {
+                    println!("{}", report);
+                    for conflict in &report.conflicts {
+                        println!("  {}", conflict);
+                    }
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "import-dir" => {
+            let Some(dir) = positional.first() else {
+                println!("Usage: import-dir <directory of task JSON files>");
+                return EXIT_FAILURE;
+            };
+
+            match app_data
+                .batch_import_from_dir(Path::new(dir), &username)
+                .and_then(|count| save_app_data(app_data).map(|()| count))
+            {
+                Ok(count) => {
+                    println!("Imported {} task(s).", count);
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "import-markdown" => {
+            let Some(path) = positional.first() else {
+                println!("Usage: import-markdown <file>");
+                return EXIT_FAILURE;
+            };
+
+            match app_data
+                .import_markdown(&username, Path::new(path))
+                .and_then(|count| save_app_data(app_data).map(|()| count))
+            {
+                Ok(count) => {
+                    println!("Imported {} task(s).", count);
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "import-data" => {
+            let Some(dir) = positional.first() else {
+                println!("Usage: import-data <data directory>");
+                return EXIT_FAILURE;
+            };
+
+            match import_data_dir(app_data, Path::new(dir)).and_then(|count| save_app_data(app_data).map(|()| count)) {
+                Ok(count) => {
+                    println!("Imported {} user(s).", count);
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "tasks" if positional.first() == Some(&"settings") => {
+            let (key, value) = match positional[1..] {
+                [] => {
+                    for (key, value, source) in app_data.settings_overview(&username) {
+                        println!("{} = {} ({})", key, value, source);
+                    }
+                    return EXIT_OK;
+                }
+                ["set", key, value] => (key, Some(value.to_string())),
+                ["unset", key] => (key, None),
+                _ => {
+                    println!("Usage: tasks settings [set <key> <value> | unset <key>]");
+                    return EXIT_FAILURE;
+                }
+            };
+            if let Err(e) = app_data.execute(&username, Op::SetUserSetting { key: key.to_string(), value }) {
+                println!("Error: {}", e);
+                return EXIT_FAILURE;
+            }
+            match save_app_data(app_data) {
+                Ok(()) => EXIT_OK,
+                Err(e) => {
+                    println!("Error saving data: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        #[cfg(feature = "import")]
+        "import-github" => {
+            let (Some(owner), Some(repo)) = (positional.first(), positional.get(1)) else {
+                println!("Usage: import-github <owner> <repo> (token read from GITHUB_TOKEN or the github-token secret)");
+                return EXIT_FAILURE;
+            };
+            let token = match env::var("GITHUB_TOKEN") {
+                Ok(token) => token,
+                Err(_) => match secrets::get(&app_data.config.data_dir, secrets::GITHUB_TOKEN) {
+                    Ok(Some(token)) => token,
+                    Ok(None) => {
+                        println!("Set GITHUB_TOKEN or run 'secret set github-token' to provide a GitHub access token.");
+                        return EXIT_FAILURE;
+                    }
+                    Err(e) => {
+                        println!("Error: {}", e);
+                        return EXIT_FAILURE;
+                    }
+                },
+            };
+
+            match app_data
+                .import_github_issues(&username, owner, repo, &token)
+                .and_then(|count| save_app_data(app_data).map(|()| count))
+            {
+                Ok(count) => {
+                    println!("Imported {} issue(s).", count);
+                    EXIT_OK
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        other => {
+            println!("Unknown command '{}'", other);
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// Saves, asking what to do if a data file was changed by another program
+/// since it was loaded.
+fn save_app_data(app_data: &mut AppData) -> Result<(), AppError> {
+    loop {
+        match app_data.save_with_retry(3) {
+            Err(AppError::ModifiedExternally(path)) => {
+                println!("{} was changed by another program since it was loaded.", path.display());
+                println!("(m) Reload it and merge your changes, (o) overwrite it, (a) save tasks to another file, (c) cancel");
+                match read_line().to_lowercase().as_str() {
+                    "m" => {
+                        let conflicts = app_data.reload_and_merge()?;
+                        if conflicts.is_empty() {
+                            println!("Merged without conflicts.");
+                        } else {
+                            println!("Merged with {} conflict(s):", conflicts.len());
+                            for conflict in &conflicts {
+                                println!("  {}", conflict);
+                            }
+                        }
+                    }
+                    "o" => return app_data.save_overwriting(),
+                    "a" => {
+                        println!("Enter the file to save tasks to:");
+                        return app_data.save_as(Path::new(&read_line()));
+                    }
+                    _ => return Err(AppError::ModifiedExternally(path)),
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+enum WatchSignal {
+    Changed,
+    Stop,
+}
+
+/// Prints the user's tasks, then every change made to tasks.json on disk
+/// until Ctrl-C. Bursts of events are debounced, and a file that is briefly
+/// missing or half-written (e.g. during an atomic rename) is simply re-read
+/// on the next event.
+fn watch_tasks(app_data: &AppData, username: &str) -> Result<(), AppError> {
+    let path = app_data.tasks_path();
+    app_data.display_tasks(username);
+
+    let (sender, receiver) = mpsc::channel();
+    let stop = sender.clone();
+    ctrlc::set_handler(move || {
+        let _ = stop.send(WatchSignal::Stop);
+    })
+    .map_err(io::Error::other)?;
+
+    let file_name = path.file_name().map(|name| name.to_os_string());
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        if event.paths.iter().any(|changed| changed.file_name().map(|name| name.to_os_string()) == file_name) {
+            let _ = sender.send(WatchSignal::Changed);
+        }
+    })?;
+    // Watch the directory rather than the file so replacing it by rename is seen.
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    println!("Watching {} for changes, press Ctrl-C to stop.", path.display());
+
+    let mut snapshot = AppData::from_tasks_file(&path).unwrap_or_else(|_| AppData::new());
+    while let Ok(WatchSignal::Changed) = receiver.recv() {
+        loop {
+            match receiver.recv_timeout(WATCH_DEBOUNCE) {
+                Ok(WatchSignal::Changed) => continue,
+                Ok(WatchSignal::Stop) | Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    println!("Stopped watching.");
+                    return Ok(());
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+            }
+        }
+
+        let Ok(current) = AppData::from_tasks_file(&path) else {
+            continue;
+        };
+        for change in AppData::diff(&snapshot, &current).iter().filter(|change| change.username() == username) {
+            println!("{}", change);
+        }
+        snapshot = current;
+    }
+    println!("Stopped watching.");
+    Ok(())
+}
+
+/// Drops all but the first entry for each username in a users file, with a
+/// warning, so a hand-edited or badly merged file doesn't silently lose the
+/// original account to a later one.
+fn dedup_users(users: Vec<User>, path: &Path) -> Vec<User> {
+    let mut seen = HashSet::new();
+    users
+        .into_iter()
+        .filter(|user| {
+            let first = seen.insert(user.username.clone());
+            if !first {
+                println!(
+                    "Warning: {} lists user '{}' more than once; keeping the first entry.",
+                    path.display(),
+                    user.username
+                );
+            }
+            first
+        })
+        .collect()
+}
+
+/// Imports the users and task lists saved in another data directory. For
+/// every username that already exists the user picks skip, overwrite or
+/// merge. Returns the number of users imported.
+fn import_data_dir(app_data: &mut AppData, dir: &Path) -> Result<usize, AppError> {
+    let users_path = dir.join("users.json");
+    let users = parse_json_file(&users_path, &read_data_file(&users_path, true)?)?;
+    let users = dedup_users(users, &users_path);
+    let task_path = dir.join("tasks.json");
+    let mut task_lists = if task_path.exists() {
+        TasksFile::parse(&task_path, &read_data_file(&task_path, true)?)?.task_lists.into_owned()
+    } else {
+        Vec::new()
+    };
+
+    let mut imported = 0;
+    for user in users {
+        let decision = if app_data.users.contains_key(&user.username) {
+            println!("User '{}' already exists. (s) Skip, (o) overwrite or (m) merge? [s]", user.username);
+            ImportDecision::from_answer(&read_line())
+        } else {
+            ImportDecision::Overwrite
+        };
+        if decision == ImportDecision::Skip {
+            println!("Skipped '{}'.", user.username);
+            continue;
+        }
+
+        let (lists, rest) = task_lists.into_iter().partition(|list| list.username == user.username);
+        task_lists = rest;
+        let username = user.username.clone();
+        app_data.execute(&username, Op::ImportUser { user, task_lists: lists, decision })?;
+        imported += 1;
+    }
+    Ok(imported)
+}
+
+/// Prints a change plan and, unless this is a dry run, applies and saves it.
+fn execute_plan(app_data: &mut AppData, plan: ChangePlan, dry_run: bool) -> i32 {
+    if plan.is_empty() {
+        println!("Nothing to change.");
+        return EXIT_NOTHING_TO_DO;
+    }
+
+    if dry_run {
+        println!("Dry run: {}", plan.describe("would delete"));
+        return EXIT_OK;
+    }
+    if app_data.read_only {
+        println!("Not available in read-only mode.");
+        return EXIT_FAILURE;
+    }
+
+    println!("{}", plan.describe("Deleted"));
+    let username = plan.username.clone();
+    if let Err(e) = app_data.execute(&username, Op::RemoveTasks { task_ids: plan.remove }) {
+        println!("Error: {}", e);
+        return EXIT_FAILURE;
+    }
+    match save_app_data(app_data) {
+        Ok(()) => EXIT_OK,
+        Err(e) => {
+            println!("Error saving data: {}", e);
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// Loads the app data. A failed integrity check is never skipped silently:
+/// the user chooses between continuing, restoring the backup, or quitting.
+fn load_app_data(profile: Option<&str>) -> AppData {
+    let result = match AppData::load(profile) {
+        Err(AppError::ChecksumMismatch(path)) => {
+            println!("!!! WARNING: {} does not match its checksum. !!!", path.display());
+            println!("!!! The file may be corrupted or was modified outside this app. !!!");
+            println!("Enter 'c' to continue anyway, 'r' to restore the last backup, or anything else to quit:");
+            match read_line().as_str() {
+                "c" => AppData::load_unverified(profile),
+                "r" => restore_backup(&path).and_then(|()| AppData::load(profile)),
+                _ => process::exit(1),
+            }
+        }
+        result => result,
+    };
+
+    result.unwrap_or_else(|e| {
+        println!("Error loading data: {}", e);
+        // Starting empty would save over the default profile's files.
+        if profile.is_some() {
+            process::exit(1);
+        }
+        AppData::new()
+    })
+}
+
+/// Main menu entries that single-key mode runs on a key press.
+const MENU_KEYS: &[(char, &str)] =
+    &[('v', "1"), ('a', "2"), ('r', "3"), ('e', "4"), ('c', "5"), ('s', "6"), ('q', MENU_SAVE_AND_EXIT)];
+const MENU_SAVE_AND_EXIT: &str = "48";
+
+/// What a key press means at the main menu in single-key mode.
+#[derive(Debug, PartialEq)]
+enum MenuKey {
+    Choice(&'static str),
+    /// The start of a menu number, finished with line input.
+    Digit(char),
+    Ignored,
+}
+
+fn menu_key(key: &KeyEvent) -> MenuKey {
+    match key.code {
+        // Raw mode swallows SIGINT, so Ctrl-C arrives as a key press.
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => MenuKey::Choice(MENU_SAVE_AND_EXIT),
+        KeyCode::Char(c) if c.is_ascii_digit() => MenuKey::Digit(c),
+        KeyCode::Char(c) => MENU_KEYS
+            .iter()
+            .find(|(key, _)| *key == c.to_ascii_lowercase())
+            .map_or(MenuKey::Ignored, |(_, choice)| MenuKey::Choice(choice)),
+        _ => MenuKey::Ignored,
+    }
+}
+
+/// `label` with its menu key letter in brackets, e.g. "[V]iew tasks", or
+/// the key appended when the label doesn't contain it.
+fn highlight_key(label: &str, choice: &str) -> String {
+    let Some((key, _)) = MENU_KEYS.iter().find(|(_, key_choice)| *key_choice == choice) else {
+        return label.to_string();
+    };
+    match label.char_indices().find(|(_, c)| c.to_ascii_lowercase() == *key) {
+        Some((index, c)) => format!("{}[{}]{}", &label[..index], c, &label[index + c.len_utf8()..]),
+        None => format!("{} [{}]", label, key),
+    }
+}
+
+/// Keeps the terminal in raw mode while alive.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Whether single-key input can work here: stdin must be a terminal that
+/// accepts raw mode, which rules out pipes and CI.
+fn single_key_available() -> bool {
+    io::stdin().is_terminal() && RawMode::enable().is_ok()
+}
+
+/// Reads a main menu choice, from a single key press when `single_key` is
+/// set and otherwise (or if reading keys fails) from a line.
+fn read_menu_choice(single_key: bool) -> String {
+    if single_key {
+        if let Ok(raw_mode) = RawMode::enable() {
+            while let Ok(event) = event::read() {
+                let Event::Key(key) = event else {
+                    continue;
+                };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match menu_key(&key) {
+                    MenuKey::Choice(choice) => {
+                        drop(raw_mode);
+                        println!();
+                        return choice.to_string();
+                    }
+                    MenuKey::Digit(digit) => {
+                        // Prompts after this need whole lines, so finish in line mode.
+                        drop(raw_mode);
+                        print!("{}", digit);
+                        io::stdout().flush().unwrap();
+                        return format!("{}{}", digit, read_line());
+                    }
+                    MenuKey::Ignored => {}
+                }
+            }
+        }
+    }
+    read_line()
+}
+
+/// The answer to the preview shown before saving.
+enum SaveDecision {
+    Save,
+    KeepWorking,
+    Discard,
+}
+
+/// Lists what this session changed and asks whether to save it. No answer
+/// (including EOF) means save, as before the preview existed; anything
+/// unrecognised keeps the session going rather than guess.
+fn preview_save(app_data: &AppData) -> SaveDecision {
+    let changes = app_data.session_changes();
+    if changes.is_empty() {
+        println!("No task or user changes; other changes such as Pomodoro sessions are unsaved.");
+    } else {
+        println!("Changes this session:");
+        for change in &changes {
+            if app_data.config.accessible {
+                println!("{}", change.sentence());
+            } else {
+                println!("  {}", change);
+            }
+        }
+    }
+    println!("(s) Save and exit, (k) keep working or (d) discard the changes and exit? [s]");
+    match read_line().to_lowercase().as_str() {
+        "" | "s" => SaveDecision::Save,
+        "d" => SaveDecision::Discard,
+        _ => SaveDecision::KeepWorking,
+    }
+}
+
+/// Ends an idle session, saving unsaved changes first.
+fn save_after_timeout(app_data: &mut AppData) {
+    println!("Session timed out after inactivity. Exiting...");
+    if !app_data.dirty {
+        return;
+    }
+    // Nobody is around to resolve a conflict, so keep both versions of the
+    // tasks.
+    let result = match app_data.save_with_retry(3) {
+        Err(AppError::ModifiedExternally(changed)) => {
+            let path = app_data.config.data_dir.join("tasks.conflict.json");
+            println!("{} was changed elsewhere; saving tasks to {}.", changed.display(), path.display());
+            app_data.save_as(&path)
+        }
+        result => result,
+    };
+    if let Err(e) = result {
+        println!("Error saving data: {}", e);
+    }
+}
+
+/// Runs the `> ` command prompt until `quit` or a session timeout. Returns
+/// true when the user asked for the numbered menu instead.
+fn run_repl(app_data: &mut AppData, username: &str) -> bool {
+    println!("Type 'help' for the list of commands.");
+    let session_timeout = app_data.config.session_timeout_minutes * 60;
+    let mut last_activity = Instant::now();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line).unwrap() == 0 {
+            // End of input: leave like 'quit' rather than spin on an empty prompt.
+            println!();
+            line = "quit".to_string();
+        }
+
+        if last_activity.elapsed().as_secs() > session_timeout {
+            save_after_timeout(app_data);
+            return false;
+        }
+        last_activity = Instant::now();
+        app_data.collect_autosave();
+
+        let command = match parse_repl_line(&line, Utc::now().date_naive()) {
+            Ok(command) => command,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+        if command.is_mutating() && app_data.read_only {
+            println!("Not available in read-only mode.");
+            continue;
+        }
+
+        match command {
+            ReplCommand::Empty => {}
+            ReplCommand::Help => {
+                for (usage, help) in REPL_COMMANDS {
+                    println!("  {:<42} {}", usage, help);
+                }
+            }
+            ReplCommand::Menu => return true,
+            ReplCommand::Quit if !app_data.dirty => return false,
+            ReplCommand::Quit => match preview_save(app_data) {
+                SaveDecision::KeepWorking => {}
+                SaveDecision::Discard => match app_data.discard_changes() {
+                    Ok(()) => {
+                        println!("Changes discarded.");
+                        return false;
+                    }
+                    Err(e) => println!("Error: {}", e),
+                },
+                SaveDecision::Save => match save_app_data(app_data) {
+                    Ok(()) => {
+                        println!("Data saved.");
+                        return false;
+                    }
+                    Err(e) => println!("Error saving data: {}", e),
+                },
+            },
+            ReplCommand::List { status } => app_data.display_tasks_with_status(username, status),
+            ReplCommand::Undo => {
+                if run_op(app_data, username, Op::Undo).is_some() {
+                    println!("Last change undone.");
+                }
+            }
+            ReplCommand::Add { description, due_date, link } => {
+                if !confirm_add_task(app_data, username, &description) {
+                    continue;
+                }
+                if run_op(app_data, username, Op::AddTask { description }).is_none() {
+                    continue;
+                }
+                let Some(task_id) = app_data.tasks(username).last().map(|task| task.id) else {
+                    continue;
+                };
+                if due_date.is_some() {
+                    run_op(app_data, username, Op::SetDueDate { task_id, due_date });
+                }
+                if link.is_some() {
+                    run_op(app_data, username, Op::SetLink { task_id, link });
+                }
+                println!("Added task #{}.", task_id);
+            }
+            ReplCommand::Done { task_ids } | ReplCommand::Remove { task_ids } if task_ids.is_empty() => {
+                println!("Give at least one task ID.");
+            }
+            ReplCommand::Done { task_ids } => {
+                for input in task_ids {
+                    match app_data.resolve_task_id(username, &input) {
+                        Ok(task_id) => {
+                            run_op(app_data, username, Op::MarkCompleted { task_id });
+                        }
+                        Err(e) => println!("Error: {}", e),
+                    }
+                }
+            }
+            ReplCommand::Remove { task_ids } => {
+                let ids: Result<Vec<u32>, AppError> =
+                    task_ids.iter().map(|input| app_data.resolve_task_id(username, input)).collect();
+                match ids {
+                    Ok(task_ids) => {
+                        run_op(app_data, username, Op::RemoveTasks { task_ids });
+                    }
+                    Err(e) => println!("Error: {}", e),
+                }
+            }
+        }
+    }
+}
+
+// Menu entries that modify data; refused in read-only mode.
+const MUTATING_CHOICES: &[&str] = &[
+    "2", "3", "4", "5", "7", "8", "9", "11", "14", "15", "17", "18", "20", "22", "23", "25", "29", "31", "32", "36",
+    "38", "40", "43", "44", "46", "47",
+];
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let profile = match args.iter().position(|arg| arg == "--profile") {
+        Some(position) if position + 1 < args.len() => {
+            args.remove(position);
+            Some(args.remove(position))
+        }
+        Some(_) => {
+            println!("Usage: --profile <name>");
+            process::exit(EXIT_FAILURE);
+        }
+        None => None,
+    };
+    let mut app_data = load_app_data(profile.as_deref());
+
+    if let Some(position) = args.iter().position(|arg| arg == "--no-open") {
+        args.remove(position);
+        app_data.file_config.open_links = false;
+        app_data.config.open_links = false;
+    }
+    if let Some(position) = args.iter().position(|arg| arg == "--accessible") {
+        args.remove(position);
+        app_data.file_config.accessible = true;
+        app_data.config.accessible = true;
+    }
+    if let Some(position) = args.iter().position(|arg| arg == "--read-only") {
+        args.remove(position);
+        app_data.read_only = true;
+    } else if !is_dir_writable(&app_data.config.data_dir) {
+        println!(
+            "Warning: {} is not writable, starting in read-only mode. Changes will not be saved.",
+            app_data.config.data_dir.display()
+        );
+        app_data.read_only = true;
+    }
+
+    if !args.is_empty() {
+        let code = run_command(&mut app_data, &args);
+        // process::exit skips destructors; let queued webhooks go out first.
+        drop(app_data);
+        process::exit(code);
+    }
+
+    println!("Enter 1 to register a new user or anything else to log in: ");
+    let mut choice = String::new();
+    io::stdin().read_line(&mut choice).unwrap();
+    let choice = choice.trim();
+
+    if choice == "1" && app_data.read_only {
+        println!("Cannot register users in read-only mode.");
+    } else if choice == "1" {
+        println!("Enter username for new user: ");
+        let mut new_user_username = String::new();
+        io::stdin().read_line(&mut new_user_username).unwrap();
+        let new_user_username = new_user_username.trim();
+
+        println!("Enter password: ");
+        let mut new_user_password = String::new();
+        io::stdin().read_line(&mut new_user_password).unwrap();
+        let new_user_password = new_user_password.trim();
+
+        if let Err(e) = app_data.register_user(new_user_username.to_string(), new_user_password.to_string()) {
+            println!("Error: {}", e);
+        } else {
+            println!("User successfully registered!");
+        }
+    }
+
+    if let Some(username) = log_in(&app_data) {
+        let username = username.as_str();
+        println!("Authentication successful!");
+        app_data.use_settings_of(username);
+        app_data.start_autosave();
+        let older_than_days = app_data.config.auto_archive_days;
+        let now = Utc::now();
+        if older_than_days > 0
+            && !app_data.read_only
+            && !app_data.archivable(username, older_than_days, now).is_empty()
+        {
+            if let Some(archived) = run_op(&mut app_data, username, Op::ArchiveStale { older_than_days, now }) {
+                println!("Archived {} task(s) untouched for more than {} days.", archived, older_than_days);
+            }
+        }
+
+        if app_data.config.persist_overdue_escalation
+            && !app_data.read_only
+            && !app_data.escalatable(username, now).is_empty()
+        {
+            if let Some(escalated) = run_op(&mut app_data, username, Op::EscalateOverdue { now }) {
+                println!("Raised the priority of {} overdue task(s).", escalated);
+            }
+        }
+
+        if app_data.config.repl && !run_repl(&mut app_data, username) {
+            return;
+        }
+
+        let single_key = app_data.config.single_key_menu && single_key_available();
+        let label = |label: &str, choice: &str| {
+            if single_key {
+                highlight_key(label, choice)
+            } else {
+                label.to_string()
+            }
+        };
+        let session_timeout = app_data.config.session_timeout_minutes * 60;
+        let mut last_activity = Instant::now();
+        loop {
+            let today = app_data.config.display_timezone().date(Utc::now());
+            println!("\n{}", app_data.task_stats(username, today).summary_line(app_data.config.accessible));
+            println!("Menu:");
+            println!("1. {}", label("View tasks", "1"));
+            println!("2. {}", label("Add task", "2"));
+            println!("3. {}", label("Remove task", "3"));
+            println!("4. {}", label("Edit task", "4"));
+            println!("5. {}", label("Mark task as completed", "5"));
+            println!("6. {}", label("Search all lists", "6"));
+            println!("7. Mark task as in progress");
+            println!("8. Set due date");
+            println!("9. Add tag");
+            println!("10. Export to Org-mode");
+            println!("11. Set priority");
+            println!("12. Show pending tasks by priority");
+            println!("13. Show stale tasks");
+            println!("14. Bulk operations");
+            println!("15. Set link");
+            println!("16. Show recently added tasks");
+            println!("17. Clear completed tasks");
+            println!("18. Add or remove dependency");
+            println!("19. Show dependency matrix");
+            println!("20. Start Pomodoro");
+            println!("21. View Pomodoro history");
+            println!("22. Pick a task from the list");
+            println!("23. Change password");
+            println!("24. Generate standup");
+            println!("25. Set story points, sprint and milestone");
+            println!("26. View sprint capacity");
+            println!("27. Sync info");
+            println!("28. Calendar");
+            println!("29. Set color");
+            println!("30. Trash");
+            println!("31. Set progress");
+            println!("32. Reopen completed task");
+            println!("33. View task detail");
+            println!("34. Show blockers");
+            println!("35. Admin: User summary");
+            println!("36. Lock or unlock task");
+            println!("37. Tasks created by day of week");
+            println!("38. Undo last change");
+            println!("39. Eisenhower matrix");
+            println!("40. Set context");
+            println!("41. Filter by context");
+            println!("42. Admin: Search all users");
+            println!("43. Set your workflow");
+            println!("44. View and change workflow statuses");
+            println!("45. What should I do next?");
+            println!("46. Review tasks");
+            println!("47. Settings");
+            println!("48. {}", label("Save and exit", MENU_SAVE_AND_EXIT));
+            if app_data.config.accessible {
+                println!("Enter a number from 1 to {}:", MENU_SAVE_AND_EXIT);
+            }
+
+            let choice = read_menu_choice(single_key);
+            let choice = choice.as_str();
+
+            if last_activity.elapsed().as_secs() > session_timeout {
+                save_after_timeout(&mut app_data);
+                break;
+            }
+            last_activity = Instant::now();
+            app_data.collect_autosave();
+
+            if MUTATING_CHOICES.contains(&choice) && app_data.read_only {
+                println!("Not available in read-only mode.");
+                continue;
+            }
+
+            match choice {
+                "1" => {
+                    app_data.display_tasks(username);
+                }
+                "2" => {
+                    println!("Enter task description (type the start and press Tab, Enter for suggestions):");
+                    let mut description = String::new();
+                    io::stdin().read_line(&mut description).unwrap();
+                    let description = description.trim_end_matches(['\r', '\n']);
+                    let description = match description.strip_suffix('\t') {
+                        Some(prefix) => prompt_completion(&app_data, username, prefix.trim()),
+                        None => description.trim().to_string(),
+                    };
+                    if description.is_empty() {
+                        continue;
+                    }
+                    if confirm_add_task(&app_data, username, &description) {
+                        run_op(&mut app_data, username, Op::AddTask { description });
+                    }
+                }
+                "3" => {
+                    if let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to remove:") {
+                        run_op(&mut app_data, username, Op::RemoveTask { task_id });
+                    }
+                }
+                "4" => {
+                    let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to edit:") else {
+                        continue;
+                    };
+                    let expected_version = app_data.task(username, task_id).map(|task| task.version);
+
+                    println!("Enter new task description:");
+                    let mut new_description = String::new();
+                    io::stdin().read_line(&mut new_description).unwrap();
+                    let description = new_description.trim().to_string();
+                    run_op(&mut app_data, username, Op::EditTask { task_id, description, expected_version });
+                }
+                "5" => {
+                    if let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to mark as completed:") {
+                        run_op(&mut app_data, username, Op::MarkCompleted { task_id });
+                    }
+                }
+                "6" => {
+                    println!("Enter search text:");
+                    let query = read_line();
+                    let results = app_data.search_all(username, &query);
+                    app_data.display_search_results(&results);
+                    if results.is_empty() {
+                        continue;
+                    }
+
+                    println!("Enter a result number to view it, or press Enter to go back:");
+                    let selected = read_line().parse::<usize>().ok().and_then(|n| results.get(n.wrapping_sub(1)));
+                    if let Some((list_name, task)) = selected {
+                        println!("List '{}': {}", list_name, app_data.format_task(task));
+                    }
+                }
+                "7" => {
+                    if let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to mark as in progress:") {
+                        run_op(&mut app_data, username, Op::MarkInProgress { task_id });
+                    }
+                }
+                "8" => {
+                    let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to set the due date for:") else {
+                        continue;
+                    };
+
+                    println!("Enter due date (YYYY-MM-DD), or leave empty to clear:");
+                    let input = read_line();
+                    let due_date = match NaiveDate::parse_from_str(&input, "%Y-%m-%d") {
+                        Ok(due_date) => Some(due_date),
+                        Err(_) if input.is_empty() => None,
+                        Err(_) => {
+                            println!("Invalid date, expected YYYY-MM-DD.");
+                            continue;
+                        }
+                    };
+                    if run_op(&mut app_data, username, Op::SetDueDate { task_id, due_date }).is_none() {
+                        continue;
+                    }
+                    let capacity = app_data.config.daily_capacity;
+                    let overbooked =
+                        app_data.overbooked_days(username).into_iter().find(|(date, _)| Some(*date) == due_date);
+                    if let Some((date, points)) = overbooked {
+                        println!("{}", overbooked_warning(date, points, capacity));
+                        let today = Utc::now().date_naive();
+                        if let Some(free) = app_data.postpone_date(username, task_id, today) {
+                            println!("The next day with room for this task is {}.", free.format("%a %Y-%m-%d"));
+                        }
+                    }
+                }
+                "9" => {
+                    let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to tag:") else {
+                        continue;
+                    };
+
+                    println!("Enter tag:");
+                    run_op(&mut app_data, username, Op::AddTag { task_id, tag: read_line() });
+                }
+                "10" => {
+                    let path = app_data.config.data_dir.join(format!("{}.org", username));
+                    match std::fs::write(&path, app_data.export_orgmode(username)) {
+                        Ok(()) => println!("Tasks exported to {}", path.display()),
+                        Err(e) => println!("Error: {}", e),
+                    }
+                }
+                "11" => {
+                    let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to set the priority for:") else {
+                        continue;
+                    };
+
+                    println!("Enter priority (Critical, High, Medium, Low):");
+                    match Priority::parse(&read_line()) {
+                        Some(priority) => {
+                            run_op(&mut app_data, username, Op::SetPriority { task_id, priority });
+                        }
+                        None => println!("Invalid priority."),
+                    }
+                }
+                "12" => {
+                    let breakdown = app_data.priority_breakdown(username);
+                    println!("Pending tasks by priority:");
+                    for (priority, count) in breakdown.iter().rev() {
+                        println!("{}: {}", priority.label(), count);
+                    }
+                }
+                "13" => {
+                    let stale_after_days = app_data.config.stale_after_days;
+                    let stale = app_data.find_stale_tasks(username, stale_after_days);
+                    if stale.is_empty() {
+                        println!("No tasks untouched for more than {} days.", stale_after_days);
+                    } else {
+                        println!("Tasks untouched for more than {} days:", stale_after_days);
+                        for task in stale {
+                            println!("{}", app_data.format_task(task));
+                        }
+                    }
+                }
+                "14" => {
+                    println!("Enter 1 to add a tag, 2 to remove a tag, 3 to set the priority of matching tasks,");
+                    println!("4 to renumber all tasks by due date, 5 to assign matching tasks to a sprint,");
+                    println!("6 to spread tasks without a due date over the coming days,");
+                    println!("7 to complete all overdue tasks, 8 to rebalance priorities into equal tiers");
+                    println!("or 9 to find and replace text in task descriptions:");
+                    let operation = read_line();
+                    if operation == "9" {
+                        println!("Enter text to find:");
+                        let find = read_line();
+                        if find.is_empty() {
+                            continue;
+                        }
+                        println!("Enter replacement text:");
+                        let replace = read_line();
+                        if let Some(changed) = run_op(&mut app_data, username, Op::FindReplace { find, replace }) {
+                            println!("{} task(s) updated.", changed);
+                        }
+                        continue;
+                    }
+                    if operation == "8" {
+                        let plan = app_data.plan_rebalance(username);
+                        if plan.is_empty() {
+                            println!("Priorities are already balanced.");
+                            continue;
+                        }
+                        print_rebalance_plan(&plan);
+                        println!("Apply these changes? (y/n)");
+                        if read_line().eq_ignore_ascii_case("y") {
+                            if let Some(changed) = run_op(&mut app_data, username, Op::RebalancePriorities) {
+                                println!("{} task(s) reprioritised.", changed);
+                            }
+                        }
+                        continue;
+                    }
+                    if operation == "7" {
+                        let today = Utc::now().date_naive();
+                        for task in app_data.blocked_overdue(username, today) {
+                            let reason = if task.locked { "locked" } else { "waiting on a dependency" };
+                            println!("Skipping #{} {} ({})", task.id, task.description, reason);
+                        }
+                        if let Some(completed) = run_op(&mut app_data, username, Op::CompleteOverdue { today }) {
+                            println!("{} overdue task(s) completed.", completed);
+                        }
+                        continue;
+                    }
+                    if operation == "4" {
+                        if let Some(changed) = run_op(&mut app_data, username, Op::RenumberByDue) {
+                            println!("{} task(s) renumbered.", changed);
+                        }
+                        continue;
+                    }
+                    if operation == "3" {
+                        println!("Enter text to match in task descriptions:");
+                        let query = read_line();
+                        println!("Enter priority (Critical, High, Medium, Low):");
+                        let Some(priority) = Priority::parse(&read_line()) else {
+                            println!("Invalid priority.");
+                            continue;
+                        };
+                        if let Some(updated) = run_op(&mut app_data, username, Op::SetPriorityMatching { query, priority }) {
+                            println!("{} task(s) updated.", updated);
+                        }
+                        continue;
+                    }
+                    if operation == "6" {
+                        println!("How many tasks per day?");
+                        let Ok(tasks_per_day) = read_line().parse() else {
+                            println!("Invalid number.");
+                            continue;
+                        };
+                        println!("Start date (YYYY-MM-DD), or leave empty for tomorrow:");
+                        let input = read_line();
+                        let start_date = if input.is_empty() {
+                            Utc::now().date_naive() + Days::new(1)
+                        } else if let Ok(date) = NaiveDate::parse_from_str(&input, "%Y-%m-%d") {
+                            date
+                        } else {
+                            println!("Invalid date, expected YYYY-MM-DD.");
+                            continue;
+                        };
+                        println!("Skip weekends? (y/n)");
+                        let skip_weekends = read_line().eq_ignore_ascii_case("y");
+                        let op = Op::SmartSchedule { tasks_per_day, start_date, skip_weekends };
+                        if let Some(scheduled) = run_op(&mut app_data, username, op) {
+                            println!("{} task(s) scheduled.", scheduled);
+                        }
+                        continue;
+                    }
+                    if operation == "5" {
+                        let filter = prompt_filter();
+                        println!("Enter sprint, or leave empty to take the tasks out of their sprint:");
+                        let sprint = read_line();
+                        if let Some(updated) = run_op(&mut app_data, username, Op::BulkAssignSprint { filter, sprint }) {
+                            println!("{} task(s) updated.", updated);
+                        }
+                        continue;
+                    }
+                    if operation != "1" && operation != "2" {
+                        println!("Invalid choice.");
+                        continue;
+                    }
+
+                    let filter = prompt_filter();
+                    println!("Enter tag:");
+                    let tag = read_line();
+                    let op = if operation == "1" {
+                        Op::BulkSetTag { filter, tag }
+                    } else {
+                        Op::BulkRemoveTag { filter, tag }
+                    };
+                    if let Some(updated) = run_op(&mut app_data, username, op) {
+                        println!("{} task(s) updated.", updated);
+                    }
+                }
+                "15" => {
+                    let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to set the link for:") else {
+                        continue;
+                    };
+
+                    println!("Enter link (http:// or https://), or leave empty to clear:");
+                    let link = Some(read_line()).filter(|link| !link.is_empty());
+                    run_op(&mut app_data, username, Op::SetLink { task_id, link });
+                }
+                "16" => {
+                    println!("Show tasks added in the last how many days?");
+                    let Ok(days) = read_line().parse::<i64>() else {
+                        println!("Invalid number of days.");
+                        continue;
+                    };
+
+                    let since = Utc::now() - TimeDelta::days(days);
+                    let tasks = app_data.recently_added(username, since);
+                    if tasks.is_empty() {
+                        println!("No tasks added in the last {} days.", days);
+                    }
+                    for task in tasks {
+                        println!("{}", app_data.format_task(task));
+                    }
+                }
+                "17" => {
+                    let plan = app_data.plan_clear_completed(username);
+                    if plan.is_empty() {
+                        println!("No completed tasks to clear.");
+                    } else {
+                        println!("{}", plan.describe("Deleted"));
+                        run_op(&mut app_data, username, Op::RemoveTasks { task_ids: plan.remove });
+                    }
+                }
+                "18" => {
+                    let Some(task_id) = prompt_task_id(&app_data, username, "Enter the ID of the dependent task:") else {
+                        continue;
+                    };
+                    let Some(depends_on_id) =
+                        prompt_task_id(&app_data, username, "Enter the ID of the task it depends on:")
+                    else {
+                        continue;
+                    };
+
+                    println!("Enter 1 to add this dependency or 2 to remove it:");
+                    match read_line().as_str() {
+                        "1" => {
+                            run_op(&mut app_data, username, Op::AddDependency { task_id, depends_on: depends_on_id });
+                        }
+                        "2" => {
+                            run_op(&mut app_data, username, Op::RemoveDependency { task_id, depends_on: depends_on_id });
+                        }
+                        _ => println!("Invalid choice."),
+                    }
+                }
+                "19" => {
+                    app_data.print_dependency_matrix(username, &mut io::stdout()).unwrap();
+                }
+                "20" => {
+                    let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to focus on:") else {
+                        continue;
+                    };
+
+                    let result = app_data.start_pomodoro(username, task_id, |length| {
+                        let started = Instant::now();
+                        println!(
+                            "Focus for {} minutes. Press Enter when the interval is over, or type 'x' to abandon it.",
+                            length.as_secs() / 60
+                        );
+                        let abandoned = read_line() == "x";
+                        if !abandoned && started.elapsed() < length {
+                            println!("The interval ended early and won't be counted.");
+                            return false;
+                        }
+                        !abandoned
+                    });
+                    match result {
+                        Ok(true) => println!("Pomodoro recorded."),
+                        Ok(false) => println!("Pomodoro not recorded."),
+                        Err(e) => println!("Error: {}", e),
+                    }
+                }
+                "21" => {
+                    let sessions: Vec<&PomodoroSession> = app_data.pomodoro_sessions(username).collect();
+                    if sessions.is_empty() {
+                        println!("No Pomodoro sessions yet.");
+                        continue;
+                    }
+
+                    for session in &sessions {
+                        println!(
+                            "#{}: {} - {}",
+                            session.task_id,
+                            app_data.config.format_timestamp(session.started_at),
+                            app_data.config.format_timestamp(session.completed_at)
+                        );
+                    }
+                    let mut task_ids: Vec<u32> = sessions.iter().map(|session| session.task_id).collect();
+                    task_ids.sort_unstable();
+                    task_ids.dedup();
+                    for task_id in task_ids {
+                        println!("Task #{}: {} Pomodoro(s)", task_id, app_data.pomodoro_count_for_task(username, task_id));
+                    }
+                    println!("Total focus time: {} minutes", app_data.total_focus_minutes(username));
+                }
+                "22" => {
+                    let tasks = app_data.tasks(username);
+                    if tasks.is_empty() {
+                        println!("No tasks found for {}", username);
+                        continue;
+                    }
+                    for (index, task) in tasks.iter().enumerate() {
+                        println!("{}) {} [{}]", index + 1, task.description, task.status().label());
+                    }
+
+                    println!("Enter the number of the task:");
+                    let Some(task_id) = read_line().parse().ok().and_then(|pos| app_data.id_at_position(username, pos))
+                    else {
+                        println!("No task at that position.");
+                        continue;
+                    };
+
+                    println!("Enter 'e' to edit, 'r' to remove or 'c' to mark it as completed:");
+                    match read_line().as_str() {
+                        "e" => {
+                            let expected_version = app_data.task(username, task_id).map(|task| task.version);
+                            println!("Enter new task description:");
+                            let description = read_line();
+                            run_op(&mut app_data, username, Op::EditTask { task_id, description, expected_version });
+                        }
+                        "r" => {
+                            run_op(&mut app_data, username, Op::RemoveTask { task_id });
+                        }
+                        "c" => {
+                            run_op(&mut app_data, username, Op::MarkCompleted { task_id });
+                        }
+                        _ => println!("Invalid choice."),
+                    }
+                }
+                "23" => {
+                    println!("Enter current password:");
+                    let old_password = read_line();
+                    println!("Enter new password:");
+                    let new_password = read_line();
+                    match app_data.change_password(username, &old_password, &new_password) {
+                        Ok(()) => println!("Password changed."),
+                        Err(e) => println!("Error: {}", e),
+                    }
+                }
+                "24" => {
+                    let since = Utc::now() - TimeDelta::days(1);
+                    if let Err(e) = app_data.export_plaintext_standup(username, since, &mut io::stdout()) {
+                        println!("Error: {}", e);
+                    }
+                }
+                "25" => {
+                    let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to plan:") else {
+                        continue;
+                    };
+
+                    println!("Enter story points, or leave empty to clear:");
+                    let points = match read_line().as_str() {
+                        "" => None,
+                        input => match input.parse::<u32>() {
+                            Ok(points) if points > 0 => Some(points),
+                            _ => {
+                                println!("Story points must be a positive whole number.");
+                                continue;
+                            }
+                        },
+                    };
+                    println!("Enter sprint name, or leave empty to clear:");
+                    let sprint = Some(read_line()).filter(|sprint| !sprint.is_empty());
+                    println!("Enter milestone, or leave empty to clear:");
+                    let milestone = Some(read_line()).filter(|milestone| !milestone.is_empty());
+                    run_op(&mut app_data, username, Op::SetStoryPoints { task_id, points });
+                    run_op(&mut app_data, username, Op::SetSprint { task_id, sprint });
+                    run_op(&mut app_data, username, Op::SetMilestone { task_id, milestone });
+                }
+                "26" => {
+                    println!("Enter sprint name:");
+                    let sprint = read_line();
+                    let capacity = app_data.sprint_capacity(username, &sprint);
+                    println!("Sprint {}:", sprint);
+                    println!("Total points: {}", capacity.total_points);
+                    println!("Completed points: {}", capacity.completed_points);
+                    println!("Remaining points: {}", capacity.remaining_points);
+                    println!("Completion: {:.1}%", capacity.completion_pct);
+                }
+                "27" => println!("{}", app_data.sync_info()),
+                "28" => {
+                    let today = app_data.config.display_timezone().date(Utc::now());
+                    show_calendar(&app_data, username, today.year(), today.month());
+                }
+                "29" => {
+                    let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to color:") else {
+                        continue;
+                    };
+
+                    println!("Enter a color number (0-255) or name (black, red, green, yellow, blue, magenta, cyan,");
+                    println!("white), or leave empty to use the status color:");
+                    let input = read_line();
+                    let color_tag = if input.is_empty() {
+                        None
+                    } else if let Some(color) = TermColor::parse(&input) {
+                        // The first 8 palette entries are the basic colors.
+                        Some(color as u8)
+                    } else if let Ok(color) = input.parse::<u8>() {
+                        Some(color)
+                    } else {
+                        println!("Colors must be a number from 0 to 255 or a color name.");
+                        continue;
+                    };
+                    run_op(&mut app_data, username, Op::SetColorTag { task_id, color_tag });
+                }
+                "30" => {
+                    let trash = app_data.list_trash(username);
+                    if trash.is_empty() {
+                        println!("The trash is empty.");
+                        continue;
+                    }
+                    println!("Trash:");
+                    for task in trash {
+                        println!("{}", app_data.format_task(task));
+                    }
+
+                    println!("Enter a task ID to restore, 'empty' to empty the trash, or leave empty to go back:");
+                    match read_line().as_str() {
+                        "" => {}
+                        "empty" => {
+                            if let Some(deleted) = run_op(&mut app_data, username, Op::EmptyTrash) {
+                                println!("{} task(s) permanently deleted.", deleted);
+                            }
+                        }
+                        input => match input.parse() {
+                            Ok(task_id) => {
+                                if run_op(&mut app_data, username, Op::RestoreFromTrash { task_id }).is_some() {
+                                    println!("Task restored.");
+                                }
+                            }
+                            Err(_) => println!("Invalid task ID."),
+                        },
+                    }
+                }
+                "31" => {
+                    let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID:") else {
+                        continue;
+                    };
+
+                    println!("Enter percentage done (0-100):");
+                    match read_line().parse::<u8>() {
+                        Ok(progress) if progress <= 100 => {
+                            run_op(&mut app_data, username, Op::SetProgress { task_id, progress });
+                        }
+                        _ => println!("Progress must be a number from 0 to 100."),
+                    }
+                }
+                "32" => {
+                    if let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to reopen:") {
+                        run_op(&mut app_data, username, Op::Reopen { task_id });
+                    }
+                }
+                "33" => {
+                    let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to view:") else {
+                        continue;
+                    };
+                    if let Err(e) = app_data.get_task_detail(username, task_id, &mut io::stdout()) {
+                        println!("Error: {}", e);
+                        continue;
+                    }
+                    let Some(task) = app_data.task(username, task_id) else {
+                        continue;
+                    };
+                    let open = if task.link.is_some() { ", 'o' to open the link" } else { "" };
+                    println!("Enter 'c' to copy the description, 'f' to copy all details{}", open);
+                    println!("or press Enter to go back:");
+                    match read_line().to_lowercase().as_str() {
+                        "c" => copy_text(&task.description),
+                        "f" => copy_text(&format_task_markdown(task)),
+                        "o" => {
+                            if let Some(link) = &task.link {
+                                open_link(&app_data.config, link);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                "34" => {
+                    let blockers = app_data.blockers(username);
+                    if blockers.is_empty() {
+                        println!("No task is blocking another.");
+                    }
+                    for blocker in blockers {
+                        let blocked: Vec<String> = app_data
+                            .tasks(username)
+                            .iter()
+                            .filter(|task| !task.completed && task.depends_on.contains(&blocker.id))
+                            .map(|task| format!("#{}", task.id))
+                            .collect();
+                        println!("{} (blocks {})", app_data.format_task(blocker), blocked.join(", "));
+                    }
+                }
+                "35" => {
+                    if let Err(e) = app_data.print_summary_table(username, &mut io::stdout()) {
+                        println!("Error: {}", e);
+                    }
+                }
+                "36" => {
+                    let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to lock or unlock:") else {
+                        continue;
+                    };
+                    let locked = app_data.tasks(username).iter().any(|task| task.id == task_id && task.locked);
+                    let op = if locked { Op::UnlockTask { task_id } } else { Op::LockTask { task_id } };
+                    if run_op(&mut app_data, username, op).is_some() {
+                        println!("Task #{} {}.", task_id, if locked { "unlocked" } else { "locked" });
+                    }
+                }
+                "37" => {
+                    const BAR_WIDTH: usize = 40;
+                    let counts = app_data.created_by_weekday(username);
+                    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+                    let mut weekday = app_data.config.week_start;
+                    for _ in 0..7 {
+                        let count = counts[weekday.num_days_from_monday() as usize];
+                        if app_data.config.accessible {
+                            let noun = if count == 1 { "task" } else { "tasks" };
+                            println!("{}: {} {} created.", weekday_name(weekday), count, noun);
+                        } else {
+                            let line = format!("{} {:>4} {}", weekday, count, "#".repeat(count * BAR_WIDTH / max));
+                            println!("{}", line.trim_end());
+                        }
+                        weekday = weekday.succ();
+                    }
+                }
+                "38" => {
+                    if run_op(&mut app_data, username, Op::Undo).is_some() {
+                        println!("Last change undone.");
+                    }
+                }
+                "39" => print!("{}", app_data.format_eisenhower_matrix(username, Utc::now())),
+                "40" => {
+                    let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to set the context for:") else {
+                        continue;
+                    };
+
+                    println!("Enter context (e.g. @office), or leave empty to clear:");
+                    let context = Some(read_line()).filter(|context| !context.is_empty());
+                    run_op(&mut app_data, username, Op::SetContext { task_id, context });
+                }
+                "41" => {
+                    let contexts = app_data.all_contexts(username);
+                    if contexts.is_empty() {
+                        println!("No task has a context yet.");
+                        continue;
+                    }
+                    println!("Contexts: {}", contexts.join(", "));
+                    println!("Enter context:");
+                    let context = read_line();
+                    let tasks = app_data.tasks_by_context(username, &context);
+                    if tasks.is_empty() {
+                        println!("No tasks in {}.", context);
+                    }
+                    let today = Utc::now().date_naive();
+                    for task in tasks {
+                        if app_data.config.accessible {
+                            println!("{}", app_data.format_task_accessible(task, today));
+                        } else {
+                            println!("{}", app_data.format_task(task));
+                        }
+                    }
+                }
+                "42" => {
+                    println!("Enter search text:");
+                    match app_data.search_all_users(username, &read_line()) {
+                        Ok(results) if results.is_empty() => println!("No matching tasks."),
+                        Ok(results) => {
+                            for (owner, task) in results {
+                                println!("{}: #{} {}", owner, task.id, task.description);
+                            }
+                        }
+                        Err(e) => println!("Error: {}", e),
+                    }
+                }
+                "43" => {
+                    let names: Vec<String> = app_data
+                        .workflow(username)
+                        .into_iter()
+                        .map(|status| if status.terminal { format!("{}*", status.name) } else { status.name })
+                        .collect();
+                    println!("Current workflow: {}", names.join(", "));
+                    println!("Enter statuses in order, separated by commas, with * after the one that means done");
+                    println!("(e.g. Backlog, Ready, Doing, Review, Done*), 'default' for the built-in workflow,");
+                    println!("or press Enter to keep it:");
+                    let statuses = match read_line().as_str() {
+                        "" => continue,
+                        input if input.eq_ignore_ascii_case("default") => Vec::new(),
+                        input => match WorkflowStatus::parse_workflow(input) {
+                            Ok(statuses) => statuses,
+                            Err(e) => {
+                                println!("Error: {}", e);
+                                continue;
+                            }
+                        },
+                    };
+                    if run_op(&mut app_data, username, Op::SetWorkflow { statuses }).is_some() {
+                        println!("Workflow updated.");
+                    }
+                }
+                "44" => {
+                    for (status, tasks) in app_data.tasks_by_workflow(username) {
+                        println!("{} ({}):", status.name, tasks.len());
+                        for task in tasks {
+                            println!("  #{} {}", task.id, app_data.list_description(&task.description));
+                        }
+                    }
+                    let Some(task_id) = prompt_task_id(&app_data, username, "Enter task ID to move:") else {
+                        continue;
+                    };
+                    println!("Enter 'f' to move it forward, 'b' to move it back, or a status name:");
+                    let op = match read_line().as_str() {
+                        "" => continue,
+                        "f" | "F" => Op::StepStatus { task_id, forward: true },
+                        "b" | "B" => Op::StepStatus { task_id, forward: false },
+                        status => Op::SetStatus { task_id, status: status.to_string() },
+                    };
+                    run_op(&mut app_data, username, op);
+                }
+                "45" => match app_data.suggest_next_task(username) {
+                    Some(task) => println!("Next up: {}", app_data.format_task(task)),
+                    None => println!("Nothing to do right now."),
+                },
+                "46" => {
+                    println!("Review which tasks? Leave everything empty to review all open tasks.");
+                    let filter = prompt_filter();
+                    run_review(&mut app_data, username, &filter, &mut io::stdin().lock());
+                }
+                "47" => {
+                    for (key, value, source) in app_data.settings_overview(username) {
+                        println!("{} = {} ({})", key, value, source);
+                    }
+                    println!("Enter a setting to change, or leave empty to go back:");
+                    let key = read_line();
+                    if key.is_empty() {
+                        continue;
+                    }
+                    println!("Enter the new value, or leave empty to go back to the default:");
+                    let value = Some(read_line()).filter(|value| !value.is_empty());
+                    if run_op(&mut app_data, username, Op::SetUserSetting { key, value }).is_some() {
+                        println!("Setting saved.");
+                    }
+                }
+                MENU_SAVE_AND_EXIT if !app_data.dirty => {
+                    println!("No changes to save. Exiting...");
+                    break;
+                }
+                MENU_SAVE_AND_EXIT => match preview_save(&app_data) {
+                    SaveDecision::KeepWorking => {}
+                    SaveDecision::Discard => match app_data.discard_changes() {
+                        Ok(()) => {
+                            println!("Changes discarded. Exiting...");
+                            break;
+                        }
+                        Err(e) => println!("Error: {}", e),
+                    },
+                    SaveDecision::Save => match save_app_data(&mut app_data) {
+                        Ok(()) => {
+                            println!("Data saved. Exiting...");
+                            break;
+                        }
+                        Err(e) => println!("Error saving data: {}", e),
+                    },
+                },
+                _ if app_data.config.accessible => {
+                    println!("Invalid choice. Enter a number from 1 to {}.", MENU_SAVE_AND_EXIT)
+                }
+                _ => println!("Invalid choice, please try again."),
+            }
+        }
+    } else {
+        println!("Authentication failed.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigBuilder;
+    use bcrypt::DEFAULT_COST;
+
+    /// The message of the `InvalidConfig` error `builder` fails with.
+    fn invalid_config(builder: ConfigBuilder) -> String {
+        match builder.build() {
+            Err(AppError::InvalidConfig(message)) => message,
+            other => panic!("expected InvalidConfig, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn config_builder_defaults_are_valid() {
+        let config = Config::builder().build().unwrap();
+        assert_eq!(config.cost, DEFAULT_COST);
+        assert!(config.data_dir.is_absolute());
+        assert_eq!(config.profile, None);
+    }
+
+    #[test]
+    fn config_builder_rejects_cost_out_of_range() {
+        assert!(invalid_config(Config::builder().with_cost(3)).contains("cost"));
+        assert!(invalid_config(Config::builder().with_cost(32)).contains("cost"));
+        assert!(Config::builder().with_cost(4).build().is_ok());
+    }
+
+    #[test]
+    fn config_builder_rejects_relative_data_dir() {
+        let builder = Config::builder().with_data_dir(PathBuf::from("data"));
+        assert!(invalid_config(builder).contains("data_dir"));
+    }
+
+    #[test]
+    fn config_builder_rejects_zero_max_tasks() {
+        assert!(invalid_config(Config::builder().with_max_tasks(0)).contains("max_tasks"));
+    }
+
+    #[test]
+    fn config_builder_rejects_zero_session_timeout() {
+        let builder = Config::builder().with_session_timeout_minutes(0);
+        assert!(invalid_config(builder).contains("session_timeout_minutes"));
+    }
+
+    #[test]
+    fn config_builder_rejects_zero_pomodoro_minutes() {
+        let builder = Config::builder().with_pomodoro_minutes(0);
+        assert!(invalid_config(builder).contains("pomodoro_minutes"));
+    }
+
+    #[test]
+    fn config_builder_rejects_bad_profile_names() {
+        assert!(invalid_config(Config::builder().with_profile("../work".to_string())).contains("profile"));
+        assert!(invalid_config(Config::builder().with_profile(String::new())).contains("profile"));
+        let config = Config::builder().with_profile("default".to_string()).build().unwrap();
+        assert_eq!(config.profile, None);
+    }
+
+    #[test]
+    fn config_builder_rejects_invalid_date_format() {
+        let builder = Config::builder().with_date_format("%Y-%Q".to_string());
+        assert!(invalid_config(builder).contains("date_format"));
+    }
+
+    #[test]
+    fn config_builder_rejects_unsorted_age_markers() {
+        let builder = Config::builder().with_age_marker_days([30, 7, 90]);
+        assert!(invalid_config(builder).contains("age_marker_days"));
+    }
+
+    #[test]
+    fn journal_replays_changes_made_after_the_last_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+
+        let mut app_data = AppData::load_with_config(config.clone(), true).unwrap();
+        app_data.register_user("alice".to_string(), "secret".to_string()).unwrap();
+        app_data.execute("alice", Op::AddTask { description: "Saved".to_string() }).unwrap();
+        app_data.save().unwrap();
+        app_data.execute("alice", Op::AddTask { description: "Only journaled".to_string() }).unwrap();
+        app_data.execute("alice", Op::MarkCompleted { task_id: 1 }).unwrap();
+        // Crash: no save, the process just goes away.
+        drop(app_data);
+
+        let recovered = AppData::load_with_config(config, true).unwrap();
+        let tasks = recovered.tasks("alice");
+        let descriptions: Vec<&str> = tasks.iter().map(|task| task.description.as_str()).collect();
+        assert_eq!(descriptions, ["Saved", "Only journaled"]);
+        assert!(tasks[0].completed);
+        assert!(recovered.dirty);
+    }
+
+    #[test]
+    fn journal_is_emptied_by_save_and_skips_failed_ops() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+        let journal_path = config.data_file("journal", "jsonl");
+
+        let mut app_data = AppData::load_with_config(config.clone(), true).unwrap();
+        app_data.register_user("bob".to_string(), "secret".to_string()).unwrap();
+        let bad_link = Op::SetLink { task_id: 1, link: Some("not a link".to_string()) };
+        assert!(matches!(app_data.execute("bob", bad_link), Err(AppError::InvalidLink(_))));
+        let journal = fs::read_to_string(&journal_path).unwrap();
+        let ops: Vec<String> = journal
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["op"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(ops, ["add_user", "set_link", "aborted"]);
+        let replayed = Journal { path: journal_path.clone(), fsync: false }.entries_since(None).unwrap();
+        assert!(matches!(replayed[..], [JournalEntry { op: Op::AddUser { .. }, .. }]));
+
+        app_data.save().unwrap();
+        assert_eq!(fs::read_to_string(&journal_path).unwrap(), "");
+        drop(app_data);
+
+        let reloaded = AppData::load_with_config(config, true).unwrap();
+        assert!(reloaded.users.contains_key("bob"));
+        assert!(!reloaded.dirty);
+    }
+
+    #[test]
+    fn autosaver_writes_the_snapshot_it_was_sent() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.build().unwrap();
+        let mut app_data = AppData::new();
+        app_data.add_task_force("carol", "Autosaved task".to_string()).unwrap();
+
+        let autosaver = Autosaver::start(Duration::from_millis(10), config.clone());
+        autosaver.send(AutosaveSnapshot {
+            task_lists: app_data.task_lists.clone(),
+            users: HashMap::new(),
+            pomodoros: Vec::new(),
+            generation: 1,
+            taken_at: SystemTime::now(),
+        });
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while lock_disk(&autosaver.disk).autosaved.is_none() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        drop(autosaver);
+
+        let saved = AppData::from_tasks_file(&config.data_file("tasks", "json")).unwrap();
+        assert_eq!(saved.tasks("carol")[0].description, "Autosaved task");
+    }
+
+    /// A sample value for each of `UserSettings::KEYS` that no default or
+    /// config file in these tests uses. A new key fails here until it gets one.
+    fn sample_setting(key: &str) -> &'static str {
+        match key {
+            "default_list" => "errands",
+            "date_format" => "%d.%m.%Y",
+            "due_soon_days" => "10",
+            "color" => "off",
+            "page_size" => "25",
+            "timezone" => "Europe/Kyiv",
+            _ => panic!("no sample value for setting '{}'", key),
+        }
+    }
+
+    #[test]
+    fn every_user_setting_overrides_the_config() {
+        for key in UserSettings::KEYS {
+            let mut settings = UserSettings::default();
+            settings.set(key, Some(sample_setting(key))).unwrap();
+            assert_eq!(settings.get(key).as_deref(), Some(sample_setting(key)), "{}", key);
+            let config = Config::default().with_user_settings(&settings);
+            assert_eq!(config.setting_value(key), sample_setting(key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn settings_resolve_user_then_config_file_then_default() {
+        let mut app_data = AppData::new();
+        app_data.file_config = Config::builder()
+            .with_default_list("inbox".to_string())
+            .with_due_soon_days(7)
+            .with_theme(Theme::Mono)
+            .build()
+            .unwrap();
+        app_data.users.insert(
+            "dana".to_string(),
+            User {
+                username: "dana".to_string(),
+                password: String::new(),
+                role: Role::default(),
+                email: None,
+                display_name: None,
+                settings: UserSettings::default(),
+                workflow: Vec::new(),
+            },
+        );
+        let source_of = |app_data: &AppData, key: &str| {
+            let overview = app_data.settings_overview("dana");
+            let (_, value, source) = overview.into_iter().find(|(other, _, _)| *other == key).unwrap();
+            (value, source)
+        };
+
+        assert_eq!(source_of(&app_data, "default_list"), ("inbox".to_string(), "config.json"));
+        assert_eq!(source_of(&app_data, "due_soon_days"), ("7".to_string(), "config.json"));
+        assert_eq!(source_of(&app_data, "color"), ("off".to_string(), "config.json"));
+        assert_eq!(source_of(&app_data, "page_size"), ("0".to_string(), "default"));
+        assert_eq!(source_of(&app_data, "timezone"), ("UTC".to_string(), "default"));
+
+        for key in UserSettings::KEYS {
+            app_data.set_user_setting("dana", key, Some(sample_setting(key))).unwrap();
+            assert_eq!(source_of(&app_data, key), (sample_setting(key).to_string(), "yours"), "{}", key);
+            assert_eq!(app_data.config.setting_value(key), sample_setting(key), "{}", key);
+        }
+
+        app_data.set_user_setting("dana", "due_soon_days", None).unwrap();
+        assert_eq!(source_of(&app_data, "due_soon_days"), ("7".to_string(), "config.json"));
+        assert_eq!(app_data.config.due_soon_days, 7);
+        app_data.set_user_setting("dana", "page_size", None).unwrap();
+        assert_eq!(source_of(&app_data, "page_size"), ("0".to_string(), "default"));
+    }
+
+    #[test]
+    fn color_setting_turns_the_configured_theme_on_and_off() {
+        let mut settings = UserSettings::default();
+        let high_contrast = Config::builder().with_theme(Theme::HighContrast).build().unwrap();
+        settings.set("color", Some("off")).unwrap();
+        assert_eq!(high_contrast.with_user_settings(&settings).theme, Theme::Mono);
+        settings.set("color", Some("ON")).unwrap();
+        assert_eq!(high_contrast.with_user_settings(&settings).theme, Theme::HighContrast);
+        let mono = Config::builder().with_theme(Theme::Mono).build().unwrap();
+        assert_eq!(mono.with_user_settings(&settings).theme, Theme::default());
+        assert!(matches!(settings.set("color", Some("maybe")), Err(AppError::InvalidSetting(_))));
+    }
+
+    #[test]
+    fn default_list_setting_names_the_first_list() {
+        let mut app_data = AppData::new();
+        app_data.file_config = Config::builder().with_default_list("inbox".to_string()).build().unwrap();
+        app_data.add_task_force("erin", "From config".to_string()).unwrap();
+        assert_eq!(app_data.task_lists[0].name, "inbox");
+
+        let mut settings = UserSettings::default();
+        settings.set("default_list", Some("  errands ")).unwrap();
+        app_data.users.insert(
+            "frank".to_string(),
+            User {
+                username: "frank".to_string(),
+                password: String::new(),
+                role: Role::default(),
+                email: None,
+                display_name: None,
+                settings,
+                workflow: Vec::new(),
+            },
+        );
+        app_data.add_task_force("frank", "From settings".to_string()).unwrap();
+        assert_eq!(app_data.task_lists[1].name, "errands");
+        assert!(UserSettings::default().set("default_list", Some(" ")).is_err());
+    }
+
+    #[test]
+    fn stats_json_counts_overdue_by_the_display_timezone() {
+        let mut app_data = AppData::new();
+        app_data.config = Config::builder().with_timezone(Some("+14:00".to_string())).build().unwrap();
+        let today = app_data.config.display_timezone().date(Utc::now());
+        for (description, tags) in [("Yesterday", vec!["home"]), ("Today", vec!["home", "work"]), ("Done", vec![])] {
+            app_data.add_task_force("gina", description.to_string()).unwrap();
+            let task = app_data.tasks_mut("gina").last_mut().unwrap();
+            task.tags = tags.into_iter().map(str::to_string).collect();
+        }
+        let tasks = app_data.tasks_mut("gina");
+        tasks[0].due_date = today.pred_opt();
+        tasks[0].priority = Priority::High;
+        tasks[1].due_date = Some(today);
+        tasks[2].completed = true;
+
+        let json: serde_json::Value = serde_json::from_str(&app_data.stats_json("gina").unwrap()).unwrap();
+        assert_eq!(json["total"], 3);
+        assert_eq!(json["pending"], 2);
+        assert_eq!(json["completed"], 1);
+        assert_eq!(json["overdue"], 1);
+        assert_eq!(json["per_priority"]["High"], 1);
+        assert_eq!(json["per_priority"]["Medium"], 1);
+        assert_eq!(json["per_tag"]["home"], 2);
+        assert_eq!(json["per_tag"]["work"], 1);
+    }
+
+    #[test]
+    fn reindex_continues_ids_after_the_highest_one() {
+        let mut app_data = AppData::new();
+        app_data.task_lists.push(TaskList {
+            username: "hana".to_string(),
+            name: default_list_name(),
+            shared_with: Vec::new(),
+            tasks: vec![Task::new(3, "Three".to_string()), Task::new(9, "Nine".to_string())],
+            deleted: Vec::new(),
+            trash: Vec::new(),
+            archived: Vec::new(),
+            caldav_synced_at: None,
+        });
+        let problems = app_data.reindex();
+        assert_eq!(problems, ["list 'default' belongs to unknown user 'hana'"]);
+        assert_eq!(app_data.id_generator.next_id("hana", 0), 10);
+        assert_eq!(app_data.id_generator.next_id("someone else", 0), 1);
+    }
+
+    #[test]
+    fn imported_tasks_never_reuse_a_deleted_id() {
+        let mut app_data = AppData::new();
+        for description in ["One", "Two", "Three"] {
+            app_data.add_task_force("ivan", description.to_string()).unwrap();
+        }
+        app_data.remove_task("ivan", 3).unwrap();
+
+        let imported = vec![Task::new(0, "Imported".to_string()), Task::new(0, "Also imported".to_string())];
+        assert_eq!(app_data.add_imported_tasks("ivan", imported).unwrap(), 2);
+        let ids: Vec<u32> = app_data.tasks("ivan").iter().map(|task| task.id).collect();
+        assert_eq!(ids, [1, 2, 4, 5]);
+        app_data.add_task_force("ivan", "Typed in".to_string()).unwrap();
+        assert_eq!(app_data.tasks("ivan").last().unwrap().id, 6);
+    }
+
+    #[test]
+    fn merged_users_tasks_get_ids_from_the_generator() {
+        let mut app_data = AppData::new();
+        for (username, descriptions) in [("jo", ["Jo 1", "Jo 2"]), ("joe", ["Joe 1", "Joe 2"])] {
+            app_data.users.insert(
+                username.to_string(),
+                User {
+                    username: username.to_string(),
+                    password: String::new(),
+                    role: Role::default(),
+                    email: None,
+                    display_name: None,
+                    settings: UserSettings::default(),
+                    workflow: Vec::new(),
+                },
+            );
+            for description in descriptions {
+                app_data.add_task_force(username, description.to_string()).unwrap();
+            }
+        }
+        app_data.add_dependency("joe", 2, 1).unwrap();
+        app_data.remove_task("jo", 2).unwrap();
+
+        assert_eq!(app_data.move_user_data("jo", "joe"), 2);
+        let tasks = app_data.tasks("jo");
+        let ids: Vec<u32> = tasks.iter().map(|task| task.id).collect();
+        assert_eq!(ids, [1, 3, 4]);
+        assert_eq!(tasks[2].depends_on, [3]);
+    }
+
+    #[test]
+    fn sync_resolve_renumbers_clashing_ids_with_the_generator() {
+        let mut app_data = AppData::new();
+        app_data.add_task_force("kim", "Mine".to_string()).unwrap();
+        app_data.add_task_force("kim", "Deleted".to_string()).unwrap();
+        app_data.remove_task("kim", 2).unwrap();
+
+        let mut theirs = app_data.task_lists.clone();
+        theirs[0].deleted.clear();
+        theirs[0].tasks = vec![Task::new(1, "Theirs".to_string())];
+        app_data.sync_resolve(theirs);
+        let tasks = app_data.tasks("kim");
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[1].description, "Theirs");
+        assert_eq!(tasks[1].id, 3);
+    }
+
+    #[test]
+    fn repl_parses_add_with_quotes_due_date_and_link() {
+        // 2024-03-06 is a Wednesday.
+        let today = NaiveDate::from_ymd_opt(2024, 3, 6).unwrap();
+        assert_eq!(
+            parse_repl_line("add buy milk due:fri", today),
+            Ok(ReplCommand::Add {
+                description: "buy milk".to_string(),
+                due_date: NaiveDate::from_ymd_opt(2024, 3, 8),
+                link: None,
+            })
+        );
+        assert_eq!(
+            parse_repl_line(r#"add "read 'due:later' notes" https://example.com/doc due:tomorrow"#, today),
+            Ok(ReplCommand::Add {
+                description: "read 'due:later' notes".to_string(),
+                due_date: NaiveDate::from_ymd_opt(2024, 3, 7),
+                link: Some("https://example.com/doc".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_repl_line(r"add it\'s   spaced due:2024-12-31", today),
+            Ok(ReplCommand::Add {
+                description: "it's spaced".to_string(),
+                due_date: NaiveDate::from_ymd_opt(2024, 12, 31),
+                link: None,
+            })
+        );
+        assert!(parse_repl_line("add due:someday", today).unwrap_err().contains("Invalid due date"));
+        assert!(parse_repl_line("add", today).unwrap_err().starts_with("Usage"));
+    }
+
+    #[test]
+    fn repl_parses_the_other_commands() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 6).unwrap();
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        assert_eq!(parse_repl_line("done 3 5", today), Ok(ReplCommand::Done { task_ids: ids(&["3", "5"]) }));
+        assert_eq!(parse_repl_line("  rm 7a3f  ", today), Ok(ReplCommand::Remove { task_ids: ids(&["7a3f"]) }));
+        assert_eq!(parse_repl_line("ls", today), Ok(ReplCommand::List { status: None }));
+        assert_eq!(
+            parse_repl_line("ls --in-progress", today),
+            Ok(ReplCommand::List { status: Some(Status::InProgress) })
+        );
+        assert!(parse_repl_line("ls --later", today).is_err());
+        assert_eq!(parse_repl_line("undo", today), Ok(ReplCommand::Undo));
+        assert_eq!(parse_repl_line("help", today), Ok(ReplCommand::Help));
+        assert_eq!(parse_repl_line("menu", today), Ok(ReplCommand::Menu));
+        assert_eq!(parse_repl_line("exit", today), Ok(ReplCommand::Quit));
+        assert_eq!(parse_repl_line("   ", today), Ok(ReplCommand::Empty));
+    }
+
+    #[test]
+    fn repl_suggests_the_closest_command() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 6).unwrap();
+        assert_eq!(parse_repl_line("dne 3", today), Err("Unknown command 'dne'. Did you mean 'done'?".to_string()));
+        assert_eq!(parse_repl_line("hepl", today), Err("Unknown command 'hepl'. Did you mean 'help'?".to_string()));
+        assert!(parse_repl_line("frobnicate", today).unwrap_err().contains("Type 'help'"));
+    }
+
+    #[test]
+    fn tokenizer_reports_unbalanced_quotes_and_trailing_escapes() {
+        assert_eq!(tokenize("add \"milk"), Err("Missing closing \".".to_string()));
+        assert!(tokenize("add milk\\").is_err());
+        let tokens = tokenize("a '' \"b c\"").unwrap();
+        assert_eq!(tokens[1], Token { text: String::new(), quoted: true });
+        assert_eq!(tokens[2], Token { text: "b c".to_string(), quoted: true });
+    }
+
+    #[test]
+    fn locked_tasks_resist_edits_until_unlocked() {
+        let mut app_data = AppData::new();
+        app_data.add_task_force("lena", "Keep me".to_string()).unwrap();
+        app_data.lock_task("lena", 1);
+
+        let refused = |result: Result<(), AppError>| matches!(result, Err(AppError::TaskLocked(1)));
+        assert!(refused(app_data.edit_task("lena", 1, "Changed".to_string(), None)));
+        assert!(refused(app_data.mark_completed("lena", 1)));
+        assert!(refused(app_data.remove_task("lena", 1)));
+        let task = app_data.task("lena", 1).unwrap();
+        assert_eq!(task.description, "Keep me");
+        assert!(!task.completed);
+
+        app_data.unlock_task("lena", 1);
+        app_data.edit_task("lena", 1, "Changed".to_string(), None).unwrap();
+        app_data.mark_completed("lena", 1).unwrap();
+        app_data.remove_task("lena", 1).unwrap();
+        assert!(app_data.tasks("lena").is_empty());
+    }
+
+    #[test]
+    fn bulk_operations_skip_and_report_locked_tasks() {
+        let mut app_data = AppData::new();
+        for description in ["Write report", "Write tests", "Read mail"] {
+            app_data.add_task_force("max", description.to_string()).unwrap();
+        }
+        app_data.lock_task("max", 2);
+        let writes = Filter { text: Some("write".to_string()), ..Filter::default() };
+        let skipped_ids = |app_data: &AppData, op: &Op| -> Vec<u32> {
+            app_data.locked_skipped_by("max", op).iter().map(|task| task.id).collect()
+        };
+
+        let op = Op::BulkSetTag { filter: writes.clone(), tag: "Desk".to_string() };
+        assert_eq!(skipped_ids(&app_data, &op), [2]);
+        assert_eq!(app_data.execute("max", op).unwrap(), 1);
+        assert_eq!(app_data.task("max", 1).unwrap().tags, ["desk"]);
+        assert!(app_data.task("max", 2).unwrap().tags.is_empty());
+
+        app_data.task_mut("max", 2).unwrap().tags.push("desk".to_string());
+        let op = Op::BulkRemoveTag { filter: writes.clone(), tag: "desk".to_string() };
+        assert_eq!(skipped_ids(&app_data, &op), [2]);
+        assert_eq!(app_data.execute("max", op).unwrap(), 1);
+        assert_eq!(app_data.task("max", 2).unwrap().tags, ["desk"]);
+
+        let op = Op::BulkAssignSprint { filter: writes, sprint: "S1".to_string() };
+        assert_eq!(app_data.execute("max", op).unwrap(), 1);
+        assert_eq!(app_data.task("max", 2).unwrap().sprint, None);
+
+        let op = Op::SetPriorityMatching { query: "WRITE".to_string(), priority: Priority::High };
+        assert_eq!(skipped_ids(&app_data, &op), [2]);
+        assert_eq!(app_data.execute("max", op).unwrap(), 1);
+        assert_eq!(app_data.task("max", 2).unwrap().priority, Priority::Medium);
+
+        let op = Op::FindReplace { find: "Write".to_string(), replace: "Draft".to_string() };
+        assert_eq!(skipped_ids(&app_data, &op), [2]);
+        assert_eq!(app_data.execute("max", op).unwrap(), 1);
+        assert_eq!(app_data.task("max", 2).unwrap().description, "Write tests");
+
+        assert_eq!(skipped_ids(&app_data, &Op::RebalancePriorities), [2]);
+        assert!(!app_data.plan_rebalance("max").contains_key(&2));
+        app_data.execute("max", Op::RebalancePriorities).unwrap();
+        assert_eq!(app_data.task("max", 2).unwrap().priority, Priority::Medium);
+        assert!(skipped_ids(&app_data, &Op::RenumberByDue).is_empty());
+    }
+
+    #[test]
+    fn autosave_writes_a_mutation_without_saving() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+        let mut app_data = AppData::load_with_config(config.clone(), true).unwrap();
+        app_data.autosaver = Some(Autosaver::start(Duration::from_millis(10), config.clone()));
+
+        app_data.execute("nina", Op::AddTask { description: "Autosaved".to_string() }).unwrap();
+        assert!(app_data.dirty);
+        let disk = Arc::clone(&app_data.autosaver.as_ref().unwrap().disk);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while lock_disk(&disk).generation < app_data.generation && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        app_data.collect_autosave();
+        assert!(!app_data.dirty);
+        assert!(app_data.session_changes().is_empty());
+
+        let on_disk = AppData::from_tasks_file(&config.data_file("tasks", "json")).unwrap();
+        assert_eq!(on_disk.tasks("nina")[0].description, "Autosaved");
+        // Dropping stops the thread; an explicit save afterwards still works.
+        app_data.autosaver = None;
+        app_data.save().unwrap();
+    }
+
+    #[test]
+    fn notion_csv_maps_statuses_and_quotes_tags() {
+        let mut app_data = AppData::new();
+        for description in ["Plan \"Q3\"", "Build", "Ship"] {
+            app_data.add_task_force("olga", description.to_string()).unwrap();
+        }
+        let tasks = app_data.tasks_mut("olga");
+        tasks[0].tags = vec!["work".to_string(), "planning".to_string()];
+        tasks[0].due_date = NaiveDate::from_ymd_opt(2024, 7, 1);
+        tasks[0].link = Some("https://example.com/q3".to_string());
+        tasks[1].started_at = Some(Utc::now());
+        tasks[2].completed = true;
+        tasks[2].priority = Priority::High;
+
+        let mut out = Vec::new();
+        app_data.export_notion_csv("olga", &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Name,Status,Priority,Tags,Due date,Notes\n\
+             \"Plan \"\"Q3\"\"\",Not started,Medium,\"work,planning\",2024-07-01,https://example.com/q3\n\
+             Build,In progress,Medium,,,\n\
+             Ship,Done,High,,,\n"
+        );
+    }
+
+    #[test]
+    fn save_then_load_round_trips_the_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+
+        let mut saved = AppData::load_with_config(config.clone(), true).unwrap();
+        saved.register_user("pat".to_string(), "one".to_string()).unwrap();
+        saved.register_user("quinn".to_string(), "two".to_string()).unwrap();
+        for description in ["Buy milk", "Call \"mum\"", "Ünïcödé ✓"] {
+            saved.execute("pat", Op::AddTask { description: description.to_string() }).unwrap();
+        }
+        saved.execute("pat", Op::AddTag { task_id: 2, tag: "family".to_string() }).unwrap();
+        saved.execute("quinn", Op::AddTask { description: "Quinn's".to_string() }).unwrap();
+        saved.execute("quinn", Op::MarkCompleted { task_id: 1 }).unwrap();
+        saved.save().unwrap();
+
+        let loaded = AppData::load_with_config(config, true).unwrap();
+        for username in ["pat", "quinn"] {
+            assert_eq!(loaded.tasks(username), saved.tasks(username));
+            assert_eq!(loaded.users[username].password, saved.users[username].password);
+        }
+        assert_eq!(loaded.pomodoros, saved.pomodoros);
+        assert_eq!(loaded.last_synced_at, saved.last_synced_at);
+        assert!(loaded.authenticate("quinn", "two"));
+        assert!(!loaded.dirty);
+    }
+
+    #[test]
+    fn load_reports_where_a_corrupted_file_breaks() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+        let mut app_data = AppData::load_with_config(config.clone(), true).unwrap();
+        app_data.add_task_force("rosa", "Soon corrupted".to_string()).unwrap();
+        app_data.save().unwrap();
+
+        // Cut the file short and give it a matching checksum, so only parsing fails.
+        let path = config.data_file("tasks", "json");
+        let bytes = fs::read(&path).unwrap();
+        write_data_file(&path, &bytes[..bytes.len() / 2]).unwrap();
+        match AppData::load_with_config(config.clone(), true) {
+            Err(AppError::Parse { path: error_path, pointer, .. }) => {
+                assert_eq!(error_path, path);
+                assert!(pointer.ends_with('^'));
+            }
+            other => panic!("expected a parse error, got {:?}", other.map(|_| ())),
+        }
+
+        restore_backup(&path).unwrap();
+        let restored = AppData::load_with_config(config, true).unwrap();
+        assert_eq!(restored.tasks("rosa")[0].description, "Soon corrupted");
+    }
+
+    #[test]
+    fn load_refuses_a_file_that_fails_its_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+        let mut app_data = AppData::load_with_config(config.clone(), true).unwrap();
+        app_data.add_task_force("sam", "Original".to_string()).unwrap();
+        app_data.save().unwrap();
+
+        // Still valid JSON, but not what the sidecar checksum describes.
+        let path = config.data_file("tasks", "json");
+        let edited = fs::read_to_string(&path).unwrap().replace("Original", "Tampered");
+        fs::write(&path, edited).unwrap();
+        match AppData::load_with_config(config.clone(), true) {
+            Err(AppError::ChecksumMismatch(mismatched)) => assert_eq!(mismatched, path),
+            other => panic!("expected a checksum mismatch, got {:?}", other.map(|_| ())),
+        }
+
+        let unverified = AppData::load_with_config(config, false).unwrap();
+        assert_eq!(unverified.tasks("sam")[0].description, "Tampered");
+    }
+
+    #[test]
+    fn edit_task_refuses_a_stale_version_without_touching_the_task() {
+        let mut app_data = AppData::new();
+        app_data.add_task_force("tara", "First".to_string()).unwrap();
+        let modified_at = Utc::now() - TimeDelta::days(1);
+        app_data.tasks_mut("tara")[0].last_modified_at = modified_at;
+
+        app_data.edit_task("tara", 1, "Second".to_string(), Some(0)).unwrap();
+        assert_eq!(app_data.task("tara", 1).unwrap().version, 1);
+
+        app_data.tasks_mut("tara")[0].last_modified_at = modified_at;
+        match app_data.edit_task("tara", 1, "Stale".to_string(), Some(0)) {
+            Err(AppError::VersionConflict { expected: 0, actual: 1 }) => {}
+            other => panic!("expected a version conflict, got {:?}", other),
+        }
+        let task = app_data.task("tara", 1).unwrap();
+        assert_eq!(task.description, "Second");
+        assert_eq!(task.last_modified_at, modified_at);
+
+        app_data.edit_task("tara", 1, "Third".to_string(), Some(1)).unwrap();
+        app_data.edit_task("tara", 1, "Fourth".to_string(), None).unwrap();
+        let task = app_data.task("tara", 1).unwrap();
+        assert_eq!((task.description.as_str(), task.version), ("Fourth", 3));
+    }
+
+    #[test]
+    fn linear_csv_maps_priorities_and_statuses() {
+        let mut app_data = AppData::new();
+        for description in ["Fix login, again", "Review", "Release", "Outage"] {
+            app_data.add_task_force("lin", description.to_string()).unwrap();
+        }
+        let tasks = app_data.tasks_mut("lin");
+        tasks[0].priority = Priority::High;
+        tasks[0].tags = vec!["bug".to_string(), "auth".to_string()];
+        tasks[0].estimated_story_points = Some(3);
+        tasks[0].due_date = NaiveDate::from_ymd_opt(2024, 5, 2);
+        tasks[0].link = Some("https://example.com/issue".to_string());
+        tasks[1].started_at = Some(Utc::now());
+        tasks[2].priority = Priority::Low;
+        tasks[2].completed = true;
+        tasks[3].priority = Priority::Critical;
+
+        let mut out = Vec::new();
+        app_data.export_linear_csv("lin", &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Title,Description,Status,Priority,Labels,Estimate,Due Date\n\
+             \"Fix login, again\",https://example.com/issue,Todo,1,bug;auth,3,2024-05-02\n\
+             Review,,In Progress,2,,,\n\
+             Release,,Done,3,,,\n\
+             Outage,,Todo,0,,,\n"
+        );
+    }
+
+    #[test]
+    fn asana_csv_formats_due_dates_and_completion() {
+        let mut app_data = AppData::new();
+        for description in ["Draft brief", "Send brief"] {
+            app_data.add_task_force("ana", description.to_string()).unwrap();
+        }
+        let tasks = app_data.tasks_mut("ana");
+        tasks[0].due_date = NaiveDate::from_ymd_opt(2024, 3, 9);
+        tasks[0].tags = vec!["docs".to_string(), "q1".to_string()];
+        // Asana stops at High.
+        tasks[0].priority = Priority::Critical;
+        tasks[1].completed = true;
+        tasks[1].link = Some("https://example.com/brief".to_string());
+
+        let mut out = Vec::new();
+        app_data.export_asana_csv("ana", &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Name,Description,Due Date,Priority,Tags,Assignee,Completed\n\
+             Draft brief,,03/09/2024,High,\"docs,q1\",,false\n\
+             Send brief,https://example.com/brief,,Medium,,,true\n"
+        );
+    }
+
+    #[test]
+    fn resolve_task_id_accepts_numbers_and_uuid_prefixes() {
+        let mut app_data = AppData::new();
+        for description in ["One", "Two", "Three"] {
+            app_data.add_task_force("uma", description.to_string()).unwrap();
+        }
+        let tasks = app_data.tasks_mut("uma");
+        tasks[0].uuid = Uuid::parse_str("abcd1234-0000-4000-8000-000000000001").unwrap();
+        tasks[1].uuid = Uuid::parse_str("abcd5678-0000-4000-8000-000000000002").unwrap();
+        tasks[2].uuid = Uuid::parse_str("12ef0000-0000-4000-8000-000000000003").unwrap();
+
+        assert_eq!(app_data.resolve_task_id("uma", "2").unwrap(), 2);
+        assert_eq!(app_data.resolve_task_id("uma", "ABCD5").unwrap(), 2);
+        assert_eq!(app_data.resolve_task_id("uma", "12ef-00").unwrap(), 3);
+        match app_data.resolve_task_id("uma", "abcd") {
+            Err(AppError::AmbiguousTaskId(input, ids)) => assert_eq!((input.as_str(), ids), ("abcd", vec![1, 2])),
+            other => panic!("expected an ambiguous ID, got {:?}", other),
+        }
+        assert!(matches!(app_data.resolve_task_id("uma", "abc"), Err(AppError::TaskNotFound(_))));
+        assert!(matches!(app_data.resolve_task_id("uma", "ffff"), Err(AppError::TaskNotFound(_))));
+        assert!(matches!(app_data.resolve_task_id("uma", "9"), Err(AppError::TaskNotFound(_))));
+    }
+
+    #[test]
+    fn legacy_tasks_get_a_uuid_that_survives_a_save() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("tasks.json"),
+            r#"[{"username": "uma", "tasks": [
+                {"id": 1, "description": "Old", "completed": false},
+                {"id": 2, "description": "Older", "completed": true}
+            ]}]"#,
+        )
+        .unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+
+        let mut app_data = AppData::load_with_config(config.clone(), true).unwrap();
+        let uuids: Vec<Uuid> = app_data.tasks("uma").iter().map(|task| task.uuid).collect();
+        assert!(uuids.iter().all(|uuid| !uuid.is_nil()));
+        assert_ne!(uuids[0], uuids[1]);
+
+        app_data.mark_dirty();
+        app_data.save().unwrap();
+        let reloaded = AppData::load_with_config(config, true).unwrap();
+        assert_eq!(reloaded.tasks("uma").iter().map(|task| task.uuid).collect::<Vec<_>>(), uuids);
+    }
+
+    #[test]
+    fn imported_tasks_are_deduplicated_by_uuid() {
+        let mut app_data = AppData::new();
+        app_data.add_task_force("uma", "Existing".to_string()).unwrap();
+        let mut duplicate = app_data.tasks("uma")[0].clone();
+        duplicate.description = "Existing, imported again".to_string();
+        let mut fresh = duplicate.clone();
+        fresh.uuid = Uuid::new_v4();
+        fresh.description = "New".to_string();
+
+        assert_eq!(app_data.add_imported_tasks("uma", vec![duplicate, fresh]).unwrap(), 1);
+        let descriptions: Vec<&str> = app_data.tasks("uma").iter().map(|task| task.description.as_str()).collect();
+        assert_eq!(descriptions, ["Existing", "New"]);
+    }
+
+    #[test]
+    fn timestamps_are_shown_in_the_configured_timezone() {
+        let timestamp: DateTime<Utc> = "2024-01-15T22:30:00Z".parse().unwrap();
+        let config = |timezone: &str| {
+            Config::builder()
+                .with_timezone(Some(timezone.to_string()))
+                .with_date_format("%Y-%m-%d %H:%M".to_string())
+                .build()
+                .unwrap()
+        };
+
+        assert_eq!(config("+05:30").format_timestamp(timestamp), "2024-01-16 04:00");
+        assert_eq!(config("America/New_York").format_timestamp(timestamp), "2024-01-15 17:30");
+        assert_eq!(config("Not/A_Zone").format_timestamp(timestamp), "2024-01-15 22:30");
+        assert_eq!(config("+05:30").display_timezone().date(timestamp), NaiveDate::from_ymd_opt(2024, 1, 16).unwrap());
+    }
+
+    #[test]
+    fn orgmode_export_marks_done_and_todo_headings() {
+        let mut app_data = AppData::new();
+        for description in ["Write report", "Review report", "Send report"] {
+            app_data.add_task_force("orla", description.to_string()).unwrap();
+        }
+        let tasks = app_data.tasks_mut("orla");
+        tasks[0].tags = vec!["work".to_string(), "q3".to_string()];
+        tasks[0].due_date = NaiveDate::from_ymd_opt(2024, 7, 5);
+        tasks[1].started_at = Some("2024-07-01T09:00:00Z".parse().unwrap());
+        tasks[2].completed = true;
+        tasks[2].completed_at = Some("2024-07-02T16:45:00Z".parse().unwrap());
+
+        assert_eq!(
+            app_data.export_orgmode("orla"),
+            "#+TITLE: Tasks for orla\n\n\
+             * TODO Write report :work:q3:\n  DEADLINE: <2024-07-05 Fri>\n\
+             * TODO Review report\n  SCHEDULED: <2024-07-01 Mon>\n\
+             * DONE Send report\n  CLOSED: [2024-07-02 Tue 16:45]\n"
+        );
+    }
+
+    #[test]
+    fn retry_with_backoff_retries_io_errors_until_one_succeeds() {
+        let mut calls = 0;
+        let result = retry_with_backoff(3, Duration::from_millis(1), || {
+            calls += 1;
+            if calls < 3 {
+                return Err(AppError::Io(io::Error::new(io::ErrorKind::Interrupted, "flaky share")));
+            }
+            Ok(calls)
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        let mut calls = 0;
+        let result: Result<(), AppError> = retry_with_backoff(2, Duration::from_millis(1), || {
+            calls += 1;
+            Err(AppError::Io(io::Error::new(io::ErrorKind::Interrupted, "still down")))
+        });
+        assert!(matches!(result, Err(AppError::Io(_))));
+        assert_eq!(calls, 2);
+
+        let mut calls = 0;
+        let result: Result<(), AppError> = retry_with_backoff(3, Duration::from_millis(1), || {
+            calls += 1;
+            Err(AppError::UserExists)
+        });
+        assert!(matches!(result, Err(AppError::UserExists)));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn html_report_escapes_descriptions_and_shows_only_filled_sections() {
+        let today = NaiveDate::from_ymd_opt(2024, 6, 12).unwrap();
+        let mut app_data = AppData::new();
+        assert!(!app_data.export_html("hal", today).contains("<h2>"));
+
+        for description in ["Fix <script>alert('x')</script> & more", "Plan \"offsite\"", "Done this week"] {
+            app_data.add_task_force("hal", description.to_string()).unwrap();
+        }
+        let tasks = app_data.tasks_mut("hal");
+        tasks[0].due_date = NaiveDate::from_ymd_opt(2024, 6, 10);
+        tasks[1].due_date = NaiveDate::from_ymd_opt(2024, 6, 14);
+        tasks[2].completed = true;
+        tasks[2].completed_at = Some("2024-06-11T08:00:00Z".parse().unwrap());
+
+        let html = app_data.export_html("hal", today);
+        assert!(html.contains("Fix &lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt; &amp; more"));
+        assert!(html.contains("Plan &quot;offsite&quot;"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("<h2>Overdue</h2>"));
+        assert!(html.contains("<h2>Due this week</h2>"));
+        assert!(html.contains("<h2>Completed this week</h2>"));
+        assert!(html.contains("Week of 2024-06-10 to 2024-06-16"));
+
+        app_data.tasks_mut("hal")[0].completed = true;
+        let html = app_data.export_html("hal", today);
+        assert!(!html.contains("<h2>Overdue</h2>"));
+        assert!(html.contains("Overdue: <b>0</b>"));
+    }
+
+    #[test]
+    fn priority_breakdown_counts_pending_tasks_per_level() {
+        let mut app_data = AppData::new();
+        let tasks = [
+            (Priority::High, false),
+            (Priority::High, false),
+            (Priority::High, true),
+            (Priority::Low, false),
+            (Priority::Critical, false),
+        ];
+        for (i, (priority, completed)) in tasks.into_iter().enumerate() {
+            app_data.add_task_force("pia", format!("Task {}", i)).unwrap();
+            let task = app_data.tasks_mut("pia").last_mut().unwrap();
+            task.priority = priority;
+            task.completed = completed;
+        }
+
+        let breakdown = app_data.priority_breakdown("pia");
+        assert_eq!(
+            breakdown.into_iter().collect::<Vec<_>>(),
+            [(Priority::Low, 1), (Priority::Medium, 0), (Priority::High, 2), (Priority::Critical, 1)]
+        );
+        assert!(app_data.priority_breakdown("nobody").values().all(|&count| count == 0));
+    }
+
+    #[test]
+    fn flipped_byte_fails_the_checksum_and_the_backup_restores() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.json");
+        write_data_file(&path, b"[\"first\"]").unwrap();
+        write_data_file(&path, b"[\"second\"]").unwrap();
+
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[3] ^= 0x01;
+        fs::write(&path, &bytes).unwrap();
+        match read_data_file(&path, true) {
+            Err(AppError::ChecksumMismatch(mismatched)) => assert_eq!(mismatched, path),
+            other => panic!("expected a checksum mismatch, got {:?}", other),
+        }
+        assert_eq!(read_data_file(&path, false).unwrap(), bytes);
+
+        restore_backup(&path).unwrap();
+        assert_eq!(read_data_file(&path, true).unwrap(), b"[\"first\"]");
+    }
+
+    #[test]
+    fn stale_tasks_are_pending_ones_untouched_past_the_cutoff() {
+        let mut app_data = AppData::new();
+        for description in ["Ancient", "Old", "Fresh", "Old but done"] {
+            app_data.add_task_force("sid", description.to_string()).unwrap();
+        }
+        let now = Utc::now();
+        let tasks = app_data.tasks_mut("sid");
+        tasks[0].last_modified_at = now - TimeDelta::days(90);
+        tasks[1].last_modified_at = now - TimeDelta::days(31);
+        tasks[2].last_modified_at = now - TimeDelta::days(29);
+        tasks[3].last_modified_at = now - TimeDelta::days(60);
+        tasks[3].completed = true;
+
+        let stale: Vec<u32> = app_data.find_stale_tasks("sid", 30).iter().map(|task| task.id).collect();
+        assert_eq!(stale, [1, 2]);
+
+        app_data.set_task_priority("sid", 1, Priority::High);
+        let stale: Vec<u32> = app_data.find_stale_tasks("sid", 30).iter().map(|task| task.id).collect();
+        assert_eq!(stale, [2]);
+        assert!(app_data.find_stale_tasks("sid", 100).is_empty());
+    }
+
+    #[test]
+    fn bulk_set_tag_is_idempotent_and_bulk_remove_undoes_it() {
+        let mut app_data = AppData::new();
+        for description in ["Sprint planning", "Sprint review", "Groceries"] {
+            app_data.add_task_force("ben", description.to_string()).unwrap();
+        }
+        app_data.tasks_mut("ben")[1].tags = vec!["Sprint-12".to_string()];
+        let filter = Filter { text: Some("sprint".to_string()), ..Filter::default() };
+
+        assert_eq!(app_data.bulk_set_tag("ben", &filter, "sprint-12").unwrap(), 1);
+        assert_eq!(app_data.bulk_set_tag("ben", &filter, "SPRINT-12").unwrap(), 0);
+        let tags: Vec<&Vec<String>> = app_data.tasks("ben").iter().map(|task| &task.tags).collect();
+        assert_eq!(tags, [&vec!["sprint-12".to_string()], &vec!["Sprint-12".to_string()], &Vec::<String>::new()]);
+
+        assert_eq!(app_data.bulk_remove_tag("ben", &filter, "Sprint-12"), 2);
+        assert_eq!(app_data.bulk_remove_tag("ben", &filter, "sprint-12"), 0);
+        assert!(app_data.tasks("ben").iter().all(|task| task.tags.is_empty()));
+    }
+
+    #[test]
+    fn set_link_accepts_http_urls_and_clear_link_removes_them() {
+        let mut app_data = AppData::new();
+        app_data.add_task_force("lia", "Read the RFC".to_string()).unwrap();
+
+        app_data.set_link("lia", 1, " https://example.com/rfc ".to_string()).unwrap();
+        assert_eq!(app_data.task("lia", 1).unwrap().link.as_deref(), Some("https://example.com/rfc"));
+
+        match app_data.set_link("lia", 1, "ftp://example.com/rfc".to_string()) {
+            Err(AppError::InvalidLink(link)) => assert_eq!(link, "ftp://example.com/rfc"),
+            other => panic!("expected an invalid link, got {:?}", other),
+        }
+        assert_eq!(app_data.task("lia", 1).unwrap().link.as_deref(), Some("https://example.com/rfc"));
+
+        app_data.clear_link("lia", 1);
+        assert_eq!(app_data.task("lia", 1).unwrap().link, None);
+    }
+
+    #[test]
+    fn read_only_mode_refuses_changes_and_never_gets_dirty() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+        let mut app_data = AppData::load_with_config(config.clone(), true).unwrap();
+        app_data.read_only = true;
+
+        let op = Op::AddTask { description: "Sneaky".to_string() };
+        assert!(matches!(app_data.execute("rex", op), Err(AppError::ReadOnly)));
+        app_data.mark_dirty();
+        assert!(!app_data.dirty);
+        assert!(matches!(app_data.save(), Err(AppError::ReadOnly)));
+        assert!(!config.data_file("tasks", "json").exists());
+        assert_eq!(fs::read_to_string(config.data_file("journal", "jsonl")).unwrap_or_default(), "");
+    }
+
+    #[test]
+    fn is_dir_writable_detects_unusable_data_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(is_dir_writable(dir.path()));
+        assert!(!dir.path().join(".write_test").exists());
+
+        assert!(!is_dir_writable(&dir.path().join("missing")));
+        let file = dir.path().join("not-a-dir");
+        fs::write(&file, "").unwrap();
+        assert!(!is_dir_writable(&file));
+    }
+
+    #[test]
+    fn recently_added_keeps_tasks_inside_the_window_newest_first() {
+        let mut app_data = AppData::new();
+        for description in ["Last month", "Three days ago", "Yesterday"] {
+            app_data.add_task_force("ria", description.to_string()).unwrap();
+        }
+        let now = Utc::now();
+        let tasks = app_data.tasks_mut("ria");
+        tasks[0].created_at = now - TimeDelta::days(30);
+        tasks[1].created_at = now - TimeDelta::days(3);
+        tasks[2].created_at = now - TimeDelta::days(1);
+
+        let recent = app_data.recently_added("ria", now - TimeDelta::days(7));
+        let recent: Vec<u32> = recent.iter().map(|task| task.id).collect();
+        assert_eq!(recent, [3, 2]);
+        assert!(app_data.recently_added("ria", now).is_empty());
+    }
+
+    #[test]
+    fn dependency_matrix_shows_only_tasks_in_a_dependency() {
+        let mut app_data = AppData::new();
+        for description in ["Deploy", "Test", "Unrelated", "Build"] {
+            app_data.add_task_force("dan", description.to_string()).unwrap();
+        }
+        let tasks = app_data.tasks_mut("dan");
+        tasks[0].depends_on = vec![2, 4];
+        tasks[1].depends_on = vec![4];
+
+        let mut out = Vec::new();
+        app_data.print_dependency_matrix("dan", &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "  1 2 4\n1 . X X\n2 . . X\n4 . . .\n");
+
+        let mut out = Vec::new();
+        app_data.print_dependency_matrix("nobody", &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "No dependencies.\n");
+    }
+
+    #[test]
+    fn pomodoro_log_survives_a_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+        let mut app_data = AppData::load_with_config(config.clone(), true).unwrap();
+        app_data.add_task_force("polly", "Write".to_string()).unwrap();
+        app_data.add_task_force("polly", "Read".to_string()).unwrap();
+
+        assert!(app_data.start_pomodoro("polly", 1, |_| true).unwrap());
+        assert!(app_data.start_pomodoro("polly", 1, |_| true).unwrap());
+        assert!(!app_data.start_pomodoro("polly", 2, |_| false).unwrap());
+        assert!(matches!(app_data.start_pomodoro("polly", 9, |_| true), Err(AppError::TaskNotFound(_))));
+        let started_at: DateTime<Utc> = "2024-04-01T09:00:00Z".parse().unwrap();
+        app_data.pomodoros.push(PomodoroSession {
+            username: "polly".to_string(),
+            task_id: 2,
+            started_at,
+            completed_at: started_at + TimeDelta::minutes(25),
+        });
+        app_data.save().unwrap();
+
+        let loaded = AppData::load_with_config(config, true).unwrap();
+        assert_eq!(loaded.pomodoros, app_data.pomodoros);
+        assert_eq!(loaded.pomodoro_count_for_task("polly", 1), 2);
+        assert_eq!(loaded.pomodoro_count_for_task("polly", 2), 1);
+        assert_eq!(loaded.pomodoro_count_for_task("someone", 1), 0);
+        assert_eq!(loaded.total_focus_minutes("polly"), 25);
+    }
+
+    #[test]
+    fn id_at_position_follows_the_listing_after_deletions() {
+        let mut app_data = AppData::new();
+        for description in ["One", "Two", "Three", "Four"] {
+            app_data.add_task_force("ivy", description.to_string()).unwrap();
+        }
+        assert_eq!(app_data.id_at_position("ivy", 2), Some(2));
+
+        app_data.remove_task("ivy", 2).unwrap();
+        assert_eq!(app_data.id_at_position("ivy", 1), Some(1));
+        assert_eq!(app_data.id_at_position("ivy", 2), Some(3));
+        assert_eq!(app_data.id_at_position("ivy", 3), Some(4));
+        assert_eq!(app_data.id_at_position("ivy", 4), None);
+        assert_eq!(app_data.id_at_position("ivy", 0), None);
+    }
+
+    #[test]
+    fn peppered_passwords_only_verify_with_the_same_pepper() {
+        let peppered = |pepper: Option<&str>| {
+            let builder = Config::builder().with_cost(4);
+            builder.with_pepper(pepper.map(str::to_string)).build().unwrap()
+        };
+        let mut app_data = AppData::new();
+        app_data.config = peppered(Some("first pepper"));
+        app_data.register_user("pep".to_string(), "secret".to_string()).unwrap();
+        assert!(app_data.authenticate("pep", "secret"));
+        assert!(!verify("secret", &app_data.users["pep"].password).unwrap());
+
+        app_data.config = peppered(Some("other pepper"));
+        assert!(!app_data.authenticate("pep", "secret"));
+        app_data.config = peppered(None);
+        assert!(!app_data.authenticate("pep", "secret"));
+
+        app_data.config = peppered(Some("first pepper"));
+        app_data.change_password("pep", "secret", "newer").unwrap();
+        assert!(app_data.authenticate("pep", "newer"));
+        assert!(!app_data.authenticate("pep", "secret"));
+        app_data.config = peppered(Some("other pepper"));
+        assert!(!app_data.authenticate("pep", "newer"));
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_modified_tasks_and_users() {
+        let mut old = AppData::new();
+        for description in ["Keep", "Finish", "Drop"] {
+            old.add_task_force("val", description.to_string()).unwrap();
+        }
+        old.users.insert(
+            "val".to_string(),
+            User {
+                username: "val".to_string(),
+                password: String::new(),
+                role: Role::default(),
+                email: None,
+                display_name: None,
+                settings: UserSettings::default(),
+                workflow: Vec::new(),
+            },
+        );
+
+        let mut new = AppData::new();
+        new.task_lists = old.task_lists.clone();
+        new.users = old.users.clone();
+        new.users.insert("wes".to_string(), User { username: "wes".to_string(), ..new.users["val"].clone() });
+        new.tasks_mut("val")[1].completed = true;
+        new.add_task_force("val", "Start".to_string()).unwrap();
+        new.remove_task("val", 3).unwrap();
+
+        let sentences: Vec<String> = AppData::diff(&old, &new).iter().map(Change::sentence).collect();
+        assert_eq!(
+            sentences,
+            ["User wes added.", "Task 2, Finish, completed.", "Task 3, Drop, removed.", "Task 4, Start, added."]
+        );
+        assert!(AppData::diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn sprint_capacity_sums_points_and_counts_missing_estimates_as_zero() {
+        let mut app_data = AppData::new();
+        let tasks = [(Some(5), true), (Some(3), false), (None, true), (Some(8), false), (Some(13), true)];
+        for (i, (points, completed)) in tasks.into_iter().enumerate() {
+            app_data.add_task_force("sue", format!("Story {}", i)).unwrap();
+            let task = app_data.tasks_mut("sue").last_mut().unwrap();
+            task.estimated_story_points = points;
+            task.completed = completed;
+            task.sprint = Some(if i < 4 { "2024-W12" } else { "2024-W13" }.to_string());
+        }
+
+        let capacity = app_data.sprint_capacity("sue", "2024-W12");
+        assert_eq!((capacity.total_points, capacity.completed_points, capacity.remaining_points), (16, 5, 11));
+        assert_eq!(capacity.completion_pct, 31.25);
+
+        let empty = app_data.sprint_capacity("sue", "2024-W99");
+        assert_eq!((empty.total_points, empty.remaining_points, empty.completion_pct), (0, 0, 0.0));
+    }
+
+    #[test]
+    fn import_conflict_answers_default_to_skip() {
+        assert_eq!(ImportDecision::from_answer("o"), ImportDecision::Overwrite);
+        assert_eq!(ImportDecision::from_answer(" Overwrite "), ImportDecision::Overwrite);
+        assert_eq!(ImportDecision::from_answer("M"), ImportDecision::Merge);
+        assert_eq!(ImportDecision::from_answer("merge"), ImportDecision::Merge);
+        assert_eq!(ImportDecision::from_answer("s"), ImportDecision::Skip);
+        assert_eq!(ImportDecision::from_answer(""), ImportDecision::Skip);
+        assert_eq!(ImportDecision::from_answer("yes please"), ImportDecision::Skip);
+    }
+
+    #[test]
+    fn import_user_skips_merges_or_overwrites_an_existing_user() {
+        let user = |password: &str| User {
+            username: "ike".to_string(),
+            password: password.to_string(),
+            role: Role::default(),
+            email: None,
+            display_name: None,
+            settings: UserSettings::default(),
+            workflow: Vec::new(),
+        };
+        let mut app_data = AppData::new();
+        app_data.users.insert("ike".to_string(), user("local"));
+        app_data.add_task_force("ike", "Local".to_string()).unwrap();
+        let mut exported = AppData::new();
+        exported.add_task_force("ike", "Exported".to_string()).unwrap();
+        let lists = exported.task_lists.clone();
+
+        assert_eq!(app_data.import_user(user("other"), lists.clone(), ImportDecision::Skip).unwrap(), 0);
+        assert_eq!(app_data.tasks("ike").len(), 1);
+
+        assert_eq!(app_data.import_user(user("other"), lists.clone(), ImportDecision::Merge).unwrap(), 1);
+        assert_eq!(app_data.users["ike"].password, "local");
+        let descriptions: Vec<&str> = app_data.tasks("ike").iter().map(|task| task.description.as_str()).collect();
+        assert_eq!(descriptions, ["Local", "Exported"]);
+
+        assert_eq!(app_data.import_user(user("other"), lists, ImportDecision::Overwrite).unwrap(), 1);
+        assert_eq!(app_data.users["ike"].password, "other");
+        let descriptions: Vec<&str> = app_data.tasks("ike").iter().map(|task| task.description.as_str()).collect();
+        assert_eq!(descriptions, ["Exported"]);
+    }
+
+    #[test]
+    fn merge_users_moves_tasks_and_sessions_to_the_kept_account() {
+        let mut app_data = AppData::new();
+        let accounts = [("root", Role::Admin, None), ("max", Role::User, None), ("maxx", Role::User, Some("m@x.io"))];
+        for (username, role, email) in accounts {
+            app_data.users.insert(
+                username.to_string(),
+                User {
+                    username: username.to_string(),
+                    password: String::new(),
+                    role,
+                    email: email.map(str::to_string),
+                    display_name: None,
+                    settings: UserSettings::default(),
+                    workflow: Vec::new(),
+                },
+            );
+        }
+        app_data.add_task_force("max", "Kept".to_string()).unwrap();
+        app_data.add_task_force("maxx", "Typo account task".to_string()).unwrap();
+        let started_at = Utc::now() - TimeDelta::minutes(25);
+        app_data.pomodoros.push(PomodoroSession {
+            username: "maxx".to_string(),
+            task_id: 1,
+            started_at,
+            completed_at: Utc::now(),
+        });
+
+        assert!(matches!(app_data.merge_users("max", "max", "maxx"), Err(AppError::PermissionDenied)));
+        assert!(matches!(app_data.merge_users("root", "max", "nobody"), Err(AppError::UserNotFound(_))));
+
+        let report = app_data.merge_users("root", "max", "maxx").unwrap();
+        assert_eq!((report.tasks_moved, report.sessions_merged), (1, 1));
+        assert!(!app_data.users.contains_key("maxx"));
+        assert_eq!(app_data.users["max"].email.as_deref(), Some("m@x.io"));
+        assert!(app_data.tasks("maxx").is_empty());
+        let descriptions: Vec<&str> = app_data.tasks("max").iter().map(|task| task.description.as_str()).collect();
+        assert_eq!(descriptions, ["Kept", "Typo account task"]);
+        assert_eq!(app_data.pomodoro_count_for_task("max", 2), 1);
+    }
+
+    #[test]
+    fn sync_resolve_keeps_the_newer_edit_and_both_sides_additions() {
+        let mut app_data = AppData::new();
+        for description in ["A", "B", "C"] {
+            app_data.add_task_force("nia", description.to_string()).unwrap();
+        }
+        let base = Utc::now() - TimeDelta::days(10);
+        for task in app_data.tasks_mut("nia") {
+            task.last_modified_at = base;
+        }
+        let mut theirs = app_data.task_lists.clone();
+
+        let ours = [("A ours", 1), ("B ours", 3), ("C ours", 1)];
+        for (task, (description, days)) in app_data.tasks_mut("nia").iter_mut().zip(ours) {
+            task.description = description.to_string();
+            task.last_modified_at = base + TimeDelta::days(days);
+        }
+        let their_edits = [("A theirs", 2), ("B theirs", 1), ("C theirs", 1)];
+        for (task, (description, days)) in theirs[0].tasks.iter_mut().zip(their_edits) {
+            task.description = description.to_string();
+            task.last_modified_at = base + TimeDelta::days(days);
+        }
+        app_data.add_task_force("nia", "Ours new".to_string()).unwrap();
+        theirs[0].tasks.push(Task::new(4, "Theirs new".to_string()));
+
+        let conflicts = app_data.sync_resolve(theirs);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].to_string(), "nia: #3 C ours (changed on both sides at once, kept both)");
+        let tasks: Vec<(u32, &str)> =
+            app_data.tasks("nia").iter().map(|task| (task.id, task.description.as_str())).collect();
+        assert_eq!(
+            tasks,
+            [
+                (1, "A theirs"),
+                (2, "B ours"),
+                (3, "C ours"),
+                (4, "Ours new"),
+                (5, "C theirs (conflict)"),
+                (6, "Theirs new"),
+            ]
+        );
+        assert!(app_data.dirty);
+    }
+
+    #[test]
+    fn sync_resolve_applies_deletions_only_to_tasks_unchanged_since() {
+        let mut app_data = AppData::new();
+        for description in ["Deleted there", "Edited here", "Deleted here", "Edited there"] {
+            app_data.add_task_force("nia", description.to_string()).unwrap();
+        }
+        let base = Utc::now() - TimeDelta::days(10);
+        for task in app_data.tasks_mut("nia") {
+            task.last_modified_at = base;
+        }
+        let mut theirs = app_data.task_lists.clone();
+
+        // Their side deleted the first two tasks a day after the base copy.
+        let their_tasks = std::mem::take(&mut theirs[0].tasks);
+        for task in &their_tasks[..2] {
+            theirs[0].deleted.push(Tombstone { uuid: task.uuid, deleted_at: base + TimeDelta::days(1) });
+        }
+        theirs[0].tasks = their_tasks[2..].to_vec();
+        theirs[0].tasks[1].last_modified_at = base + TimeDelta::days(2);
+        app_data.tasks_mut("nia")[1].last_modified_at = base + TimeDelta::days(2);
+
+        // Ours deleted the last two, the fourth before their edit to it.
+        app_data.remove_task("nia", 3).unwrap();
+        app_data.remove_task("nia", 4).unwrap();
+        app_data.task_lists[0].deleted.last_mut().unwrap().deleted_at = base + TimeDelta::days(1);
+
+        let conflicts: Vec<String> = app_data.sync_resolve(theirs).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            conflicts,
+            [
+                "nia: #2 Edited here (modified after it was deleted elsewhere, kept)",
+                "nia: #4 Edited there (modified elsewhere after you deleted it, kept)",
+            ]
+        );
+        let descriptions: Vec<&str> = app_data.tasks("nia").iter().map(|task| task.description.as_str()).collect();
+        assert_eq!(descriptions, ["Edited here", "Edited there"]);
+        assert_eq!(app_data.task_lists[0].deleted.len(), 4);
+    }
+
+    #[test]
+    fn sync_info_shows_how_long_ago_the_data_was_saved() {
+        let mut app_data = AppData::new();
+        app_data.config = Config::builder().with_date_format("%Y-%m-%d %H:%M".to_string()).build().unwrap();
+        assert_eq!(app_data.sync_info(), "Not saved yet.");
+
+        let saved_at = Utc::now() - TimeDelta::seconds(3 * 60 + 10);
+        app_data.last_synced_at = Some(saved_at);
+        assert_eq!(app_data.sync_info(), format!("Last saved: 3 minutes ago ({})", saved_at.format("%Y-%m-%d %H:%M")));
+
+        assert_eq!(format_elapsed(TimeDelta::seconds(59)), "just now");
+        assert_eq!(format_elapsed(TimeDelta::minutes(1)), "1 minute ago");
+        assert_eq!(format_elapsed(TimeDelta::hours(5)), "5 hours ago");
+        assert_eq!(format_elapsed(TimeDelta::days(1)), "1 day ago");
+    }
+
+    #[test]
+    fn save_records_when_it_happened() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+        let mut app_data = AppData::load_with_config(config.clone(), true).unwrap();
+        assert_eq!(app_data.last_synced_at, None);
+
+        let before = Utc::now();
+        app_data.save().unwrap();
+        let saved_at = app_data.last_synced_at.unwrap();
+        assert!(saved_at >= before && saved_at <= Utc::now());
+        assert_eq!(AppData::load_with_config(config, true).unwrap().last_synced_at, Some(saved_at));
+    }
+
+    #[test]
+    fn set_priority_matching_only_touches_matching_tasks() {
+        let mut app_data = AppData::new();
+        for description in ["Fix login bug", "Fix signup BUG", "Write docs"] {
+            app_data.add_task_force("tia", description.to_string()).unwrap();
+        }
+        app_data.tasks_mut("tia")[1].priority = Priority::High;
+
+        assert_eq!(app_data.set_priority_matching("tia", "bug", Priority::High), 1);
+        let priorities: Vec<Priority> = app_data.tasks("tia").iter().map(|task| task.priority).collect();
+        assert_eq!(priorities, [Priority::High, Priority::High, Priority::Medium]);
+        assert_eq!(app_data.set_priority_matching("tia", "nothing like it", Priority::Low), 0);
+    }
+
+    #[test]
+    fn format_month_lays_out_weeks_from_the_configured_start() {
+        let date = |month, day| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+        let due_counts = HashMap::from([(date(2, 14), 2), (date(2, 15), 0)]);
+
+        let leap = format_month(2024, 2, &due_counts, date(2, 9), Weekday::Mon);
+        let lines: Vec<&str> = leap.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], format!("{}February 2024", " ".repeat(18)));
+        assert_eq!(lines[1], "    Mon    Tue    Wed    Thu    Fri    Sat    Sun");
+        assert_eq!(lines[2], format!("{}      1      2      3      4", " ".repeat(21)));
+        assert_eq!(lines[3], "      5      6      7      8    [9]     10     11");
+        assert_eq!(lines[4], "     12     13  14(2)     15     16     17     18");
+        assert_eq!(lines[6], "     26     27     28     29");
+
+        let sunday_first = format_month(2024, 2, &HashMap::new(), date(1, 1), Weekday::Sun);
+        let lines: Vec<&str> = sunday_first.lines().collect();
+        assert_eq!(lines[1], "    Sun    Mon    Tue    Wed    Thu    Fri    Sat");
+        assert_eq!(lines[2], format!("{}      1      2      3", " ".repeat(28)));
+        assert_eq!(*lines.last().unwrap(), "     25     26     27     28     29");
+
+        let common_year = format_month(2023, 2, &HashMap::new(), date(1, 1), Weekday::Mon);
+        assert!(common_year.ends_with("     27     28\n"));
+        let april = format_month(2024, 4, &HashMap::new(), date(1, 1), Weekday::Mon);
+        assert!(april.ends_with("     29     30\n"));
+        assert_eq!(format_month(2024, 13, &HashMap::new(), date(1, 1), Weekday::Mon), "");
+    }
+
+    #[test]
+    fn save_refuses_to_overwrite_a_file_changed_since_loading() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+        let mut first = AppData::load_with_config(config.clone(), true).unwrap();
+        first.add_task_force("ed", "Mine".to_string()).unwrap();
+        first.save().unwrap();
+
+        let mut second = AppData::load_with_config(config.clone(), true).unwrap();
+        second.add_task_force("ed", "Also mine".to_string()).unwrap();
+        let path = config.data_file("tasks", "json");
+        let later = SystemTime::now() + Duration::from_secs(60);
+
+        // Touched but unchanged: nothing would be lost.
+        File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        first.save().unwrap();
+
+        first.add_task_force("ed", "Theirs".to_string()).unwrap();
+        first.save().unwrap();
+        File::options().write(true).open(&path).unwrap().set_modified(later + Duration::from_secs(60)).unwrap();
+        match second.save() {
+            Err(AppError::ModifiedExternally(changed)) => assert_eq!(changed, path),
+            other => panic!("expected an external modification, got {:?}", other),
+        }
+        assert_eq!(AppData::load_with_config(config, true).unwrap().tasks("ed").len(), 2);
+    }
+
+    #[test]
+    fn color_tags_use_256_color_escapes_over_the_status_color() {
+        assert_eq!(ansi_256_color(Some(0)), "\x1b[38;5;0m");
+        assert_eq!(ansi_256_color(Some(208)), "\x1b[38;5;208m");
+        assert_eq!(ansi_256_color(Some(255)), "\x1b[38;5;255m");
+        assert_eq!(ansi_256_color(None), "");
+
+        let mut task = Task::new(1, "Paint".to_string());
+        assert_eq!(task.ansi_color(Theme::Default), Status::Pending.color().ansi_code());
+        task.color_tag = Some(208);
+        assert_eq!(task.ansi_color(Theme::Default), "\x1b[38;5;208m");
+        assert_eq!(task.ansi_color(Theme::HighContrast), "\x1b[38;5;208m");
+        assert_eq!(task.ansi_color(Theme::Mono), "");
+
+        let mut app_data = AppData::new();
+        app_data.add_task_force("cy", "Tagged".to_string()).unwrap();
+        app_data.set_color_tag("cy", 1, Some(TermColor::Cyan as u8));
+        assert_eq!(app_data.task("cy", 1).unwrap().color_tag, Some(6));
+        app_data.set_color_tag("cy", 1, None);
+        assert_eq!(app_data.task("cy", 1).unwrap().color_tag, None);
+    }
+
+    #[test]
+    fn removed_tasks_go_to_the_trash_and_can_be_restored() {
+        let mut app_data = AppData::new();
+        for description in ["Base", "Oops", "Gone"] {
+            app_data.add_task_force("tom", description.to_string()).unwrap();
+        }
+        app_data.add_dependency("tom", 2, 1).unwrap();
+        app_data.remove_task("tom", 2).unwrap();
+        app_data.remove_task("tom", 1).unwrap();
+        assert_eq!(app_data.tasks("tom").len(), 1);
+        let trashed: Vec<u32> = app_data.list_trash("tom").iter().map(|task| task.id).collect();
+        assert_eq!(trashed, [2, 1]);
+
+        app_data.restore_from_trash("tom", 2).unwrap();
+        let restored = app_data.task("tom", 2).unwrap();
+        assert_eq!(restored.description, "Oops");
+        assert!(restored.depends_on.is_empty());
+        assert!(app_data.task_lists[0].deleted.iter().all(|tombstone| tombstone.uuid != restored.uuid));
+        assert_eq!(app_data.list_trash("tom").len(), 1);
+        assert!(matches!(app_data.restore_from_trash("tom", 2), Err(AppError::TaskNotFound(_))));
+    }
+
+    #[test]
+    fn empty_trash_deletes_trashed_tasks_for_good() {
+        let mut app_data = AppData::new();
+        for description in ["One", "Two", "Three"] {
+            app_data.add_task_force("tom", description.to_string()).unwrap();
+        }
+        app_data.remove_task("tom", 1).unwrap();
+        app_data.remove_task("tom", 2).unwrap();
+
+        assert_eq!(app_data.empty_trash("tom"), 2);
+        assert!(app_data.list_trash("tom").is_empty());
+        assert_eq!(app_data.empty_trash("tom"), 0);
+        assert!(matches!(app_data.restore_from_trash("tom", 1), Err(AppError::TaskNotFound(_))));
+        assert_eq!(app_data.tasks("tom").len(), 1);
+    }
+
+    #[test]
+    fn most_neglected_is_the_oldest_open_task_of_any_user() {
+        let mut app_data = AppData::new();
+        let now = Utc::now();
+        for (username, description, days_old, completed) in [
+            ("amy", "Recent", 2, false),
+            ("bob", "Oldest but done", 90, true),
+            ("bob", "Oldest open", 40, false),
+            ("cat", "Middling", 20, false),
+        ] {
+            app_data.add_task_force(username, description.to_string()).unwrap();
+            let task = app_data.tasks_mut(username).last_mut().unwrap();
+            task.created_at = now - TimeDelta::days(days_old);
+            task.completed = completed;
+        }
+
+        let (username, task) = app_data.most_neglected(now).unwrap();
+        assert_eq!((username, task.description.as_str()), ("bob", "Oldest open"));
+        assert!(app_data.most_neglected(now - TimeDelta::days(100)).is_none());
+        assert!(AppData::new().most_neglected(now).is_none());
+    }
+
+    #[test]
+    fn find_similar_tasks_filters_by_threshold_best_first() {
+        let mut app_data = AppData::new();
+        app_data.config = Config::builder().with_locale("en_US.UTF-8".to_string()).build().unwrap();
+        for description in ["Buy milk today", "Call the dentist", "BUY MILK"] {
+            app_data.add_task_force("sam", description.to_string()).unwrap();
+        }
+
+        let ids = |threshold| -> Vec<u32> {
+            app_data.find_similar_tasks("sam", "buy milk", threshold).iter().map(|task| task.id).collect()
+        };
+        assert_eq!(ids(0.9), [3, 1]);
+        assert_eq!(ids(0.95), [3]);
+        assert_eq!(ids(1.0), [3]);
+        assert!(app_data.find_similar_tasks("sam", "Renew passport", 0.9).is_empty());
+    }
+
+    #[test]
+    fn progress_is_clamped_and_follows_completion() {
+        let mut app_data = AppData::new();
+        app_data.add_task_force("pam", "Halfway".to_string()).unwrap();
+
+        app_data.set_progress("pam", 1, 40);
+        assert_eq!(app_data.task("pam", 1).unwrap().progress, 40);
+        app_data.set_progress("pam", 1, 250);
+        assert_eq!(app_data.task("pam", 1).unwrap().progress, 100);
+        assert!(!app_data.task("pam", 1).unwrap().completed);
+
+        app_data.set_progress("pam", 1, 60);
+        app_data.mark_completed("pam", 1).unwrap();
+        assert_eq!(app_data.task("pam", 1).unwrap().progress, 100);
+        app_data.reopen("pam", 1);
+        let task = app_data.task("pam", 1).unwrap();
+        assert_eq!((task.completed, task.completed_at, task.progress), (false, None, 0));
+    }
+
+    #[test]
+    fn batch_import_reads_every_json_file_and_skips_broken_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = [
+            ("alpha.json", r#"[{"id": 1, "description": "Alpha 1", "completed": false},
+                              {"id": 2, "description": "Alpha 2", "completed": true}]"#),
+            ("beta.json", r#"[{"id": 1, "description": "Beta", "completed": false, "tags": ["beta"]}]"#),
+            ("gamma.json", r#"[]"#),
+            ("broken.json", r#"[{"id": 1, "description": "Half"#),
+            ("notes.txt", r#"[{"id": 1, "description": "Not JSON by name", "completed": false}]"#),
+        ];
+        for (name, contents) in files {
+            fs::write(dir.path().join(name), contents).unwrap();
+        }
+
+        let mut app_data = AppData::new();
+        app_data.add_task_force("bea", "Existing".to_string()).unwrap();
+        assert_eq!(app_data.batch_import_from_dir(dir.path(), "bea").unwrap(), 3);
+        let tasks: Vec<(u32, &str, bool)> = app_data
+            .tasks("bea")
+            .iter()
+            .map(|task| (task.id, task.description.as_str(), task.completed))
+            .collect();
+        assert_eq!(tasks, [(1, "Existing", false), (2, "Alpha 1", false), (3, "Alpha 2", true), (4, "Beta", false)]);
+        assert_eq!(app_data.task("bea", 4).unwrap().tags, ["beta"]);
+    }
+
+    #[test]
+    fn mono_theme_leaves_task_lines_uncolored() {
+        let mut app_data = AppData::new();
+        app_data.add_task_force("moe", "Urgent".to_string()).unwrap();
+        let task = &mut app_data.tasks_mut("moe")[0];
+        task.priority = Priority::High;
+        task.color_tag = Some(200);
+
+        app_data.config = Config::builder().with_theme(Theme::Mono).build().unwrap();
+        let task = app_data.task("moe", 1).unwrap();
+        let line = app_data.format_task_colored(task);
+        assert_eq!(line, app_data.format_task(task));
+        assert!(!line.contains('\x1b'));
+        for status in [Status::Pending, Status::InProgress, Status::Completed] {
+            assert_eq!(Theme::Mono.status_color(status), "");
+        }
+        assert_eq!(Theme::Mono.priority_color(Priority::High), "");
+
+        app_data.config = Config::builder().with_theme(Theme::Default).build().unwrap();
+        let line = app_data.format_task_colored(app_data.task("moe", 1).unwrap());
+        assert!(line.starts_with("\x1b[38;5;200m") && line.ends_with(ANSI_RESET));
+        assert!(line.contains("\x1b[31mPriority: High\x1b[0m\x1b[38;5;200m"));
+    }
+
+    #[test]
+    fn renumber_by_due_orders_ids_and_keeps_references() {
+        let mut app_data = AppData::new();
+        let date = |day| NaiveDate::from_ymd_opt(2024, 9, day);
+        let tasks = [("No date", None), ("Late", date(20)), ("Early", date(3)), ("Also late", date(20))];
+        for (description, due_date) in tasks {
+            app_data.add_task_force("rue", description.to_string()).unwrap();
+            app_data.tasks_mut("rue").last_mut().unwrap().due_date = due_date;
+        }
+        app_data.add_dependency("rue", 1, 3).unwrap();
+        app_data.add_dependency("rue", 2, 4).unwrap();
+        let started_at = Utc::now() - TimeDelta::minutes(25);
+        app_data.pomodoros.push(PomodoroSession {
+            username: "rue".to_string(),
+            task_id: 1,
+            started_at,
+            completed_at: Utc::now(),
+        });
+
+        // "Late" keeps ID 2.
+        assert_eq!(app_data.renumber_by_due("rue"), 3);
+        let tasks: Vec<(u32, &str)> =
+            app_data.tasks("rue").iter().map(|task| (task.id, task.description.as_str())).collect();
+        assert_eq!(tasks, [(1, "Early"), (2, "Late"), (3, "Also late"), (4, "No date")]);
+        assert_eq!(app_data.task("rue", 4).unwrap().depends_on, [1]);
+        assert_eq!(app_data.task("rue", 2).unwrap().depends_on, [3]);
+        assert_eq!(app_data.pomodoro_count_for_task("rue", 4), 1);
+        assert_eq!(app_data.renumber_by_due("rue"), 0);
+    }
+
+    #[test]
+    fn task_detail_shows_a_section_for_each_filled_field() {
+        let mut app_data = AppData::new();
+        app_data.add_task_force("dee", "Bare".to_string()).unwrap();
+        app_data.add_task_force("dee", "Full".to_string()).unwrap();
+        let task = &mut app_data.tasks_mut("dee")[1];
+        task.tags = vec!["home".to_string(), "diy".to_string()];
+        task.due_date = NaiveDate::from_ymd_opt(2099, 1, 31);
+        task.estimated_story_points = Some(5);
+        task.sprint = Some("2099-W05".to_string());
+        task.milestone = Some("v2".to_string());
+        task.context = Some("@garage".to_string());
+        task.link = Some("https://example.com/shelf".to_string());
+        task.depends_on = vec![1];
+        task.started_at = Some(Utc::now());
+        task.progress = 30;
+        task.locked = true;
+        app_data.start_pomodoro("dee", 2, |_| true).unwrap();
+
+        let detail = |task_id| {
+            let mut out = Vec::new();
+            app_data.get_task_detail("dee", task_id, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let full = detail(2);
+        for expected in [
+            "#2 Full\n",
+            "  Status:     In progress (30%)\n",
+            "  Locked:     yes\n",
+            "  Priority:   Medium\n",
+            "  Tags:       home, diy\n",
+            "  Due:        2099-01-31\n",
+            "  Estimate:   5 story point(s)\n",
+            "  Sprint:     2099-W05\n",
+            "  Milestone:  v2\n",
+            "  Context:    @garage\n",
+            "  Link:       https://example.com/shelf\n",
+            "  Depends on: #1\n",
+            "  Pomodoros:  1\n",
+            "  Started:    ",
+        ] {
+            assert!(full.contains(expected), "missing {:?} in\n{}", expected, full);
+        }
+
+        let bare = detail(1);
+        for absent in [
+            "Locked", "Tags", "Due", "Estimate", "Sprint", "Milestone", "Context", "Link", "Depends", "Started",
+        ] {
+            assert!(!bare.contains(absent), "unexpected {:?} in\n{}", absent, bare);
+        }
+        assert!(bare.contains("  Pomodoros:  0\n") && bare.contains("  Created:    "));
+        assert!(matches!(app_data.get_task_detail("dee", 9, &mut Vec::new()), Err(AppError::TaskNotFound(_))));
+    }
+
+    #[test]
+    fn collation_orders_german_swedish_and_turkish_words() {
+        let sorted = |locale: &str, words: &[&'static str]| -> Vec<&'static str> {
+            let collation = Collation::for_locale(locale);
+            let mut words = words.to_vec();
+            words.sort_by_cached_key(|word| collation.collate_key(word));
+            words
+        };
+
+        assert_eq!(
+            sorted("de_DE.UTF-8", &["Zebra", "Äpfel", "apfel", "Banane", "Apfel"]),
+            ["Apfel", "apfel", "Äpfel", "Banane", "Zebra"]
+        );
+        assert_eq!(sorted("sv_SE", &["Ört", "Zon", "Åsa", "Ägg", "Apa"]), ["Apa", "Zon", "Åsa", "Ägg", "Ört"]);
+        assert_eq!(sorted("de", &["Ört", "Zon", "Apa"]), ["Apa", "Ört", "Zon"]);
+        assert_eq!(
+            sorted("tr_TR", &["İzmir", "Istanbul", "hava", "ılık", "dal", "çay", "cam"]),
+            ["cam", "çay", "dal", "hava", "ılık", "Istanbul", "İzmir"]
+        );
+        assert_eq!(Collation::Turkish.fold("IŞIK"), "ışık");
+        assert_eq!(Collation::Root.fold("Äpfel"), "apfel");
+    }
+
+    #[test]
+    fn autocomplete_suggests_past_descriptions_newest_first() {
+        let mut app_data = AppData::new();
+        let now = Utc::now();
+        let tasks = [
+            ("Call mum", 9),
+            ("call the bank", 1),
+            ("Call mum", 2),
+            ("Clean the car", 0),
+            ("CALL plumber", 5),
+            ("Call dentist", 7),
+            ("Call school", 3),
+            ("Call insurer", 8),
+        ];
+        for (description, days_old) in tasks {
+            app_data.add_task_force("cal", description.to_string()).unwrap();
+            app_data.tasks_mut("cal").last_mut().unwrap().created_at = now - TimeDelta::days(days_old);
+        }
+        app_data.tasks_mut("cal")[4].completed = true;
+        app_data.remove_task("cal", 6).unwrap();
+        let archived = app_data.task_lists[0].tasks.remove(6);
+        app_data.task_lists[0].archived.push(archived);
+
+        assert_eq!(
+            app_data.autocomplete_description("cal", "cALL "),
+            ["call the bank", "Call mum", "Call school", "CALL plumber", "Call dentist"]
+        );
+        assert_eq!(app_data.autocomplete_description("cal", "call p"), ["CALL plumber"]);
+        assert_eq!(app_data.autocomplete_description("cal", "call i"), ["Call insurer"]);
+        assert_eq!(app_data.autocomplete_description("cal", "call d"), ["Call dentist"]);
+        assert!(app_data.autocomplete_description("cal", "write").is_empty());
+    }
+
+    #[test]
+    fn cloned_data_is_independent_and_detached() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+        let mut original = AppData::load_with_config(config.clone(), true).unwrap();
+        original.add_task_force("cleo", "Original".to_string()).unwrap();
+
+        let mut copy = original.clone();
+        assert!(copy.journal.is_none());
+        copy.execute("cleo", Op::EditTask { task_id: 1, description: "Copy".to_string(), expected_version: None })
+            .unwrap();
+        copy.execute("cleo", Op::AddTask { description: "Only in the copy".to_string() }).unwrap();
+        copy.tasks_mut("cleo")[0].tags.push("copied".to_string());
+
+        let task = original.task("cleo", 1).unwrap();
+        assert_eq!((task.description.as_str(), task.tags.len()), ("Original", 0));
+        assert_eq!(original.tasks("cleo").len(), 1);
+        assert!(!config.data_file("journal", "jsonl").exists());
+
+        original.add_task_force("cleo", "Only in the original".to_string()).unwrap();
+        assert_eq!(copy.tasks("cleo")[1].description, "Only in the copy");
+    }
+
+    #[test]
+    fn accessible_output_reads_as_plain_sentences() {
+        let mut app_data = AppData::new();
+        app_data.config = Config::builder().with_accessible(true).with_description_width(80).build().unwrap();
+        let today = Utc::now().date_naive();
+        app_data.add_task_force("ana", "Buy milk".to_string()).unwrap();
+        app_data.add_task_force("ana", "Call dentist".to_string()).unwrap();
+        app_data.add_task_force("ana", "File taxes".to_string()).unwrap();
+        {
+            let tasks = app_data.tasks_mut("ana");
+            tasks[0].due_date = Some(today + Days::new(1));
+            tasks[0].tags = vec!["errand".to_string(), "home".to_string()];
+            tasks[1].started_at = Some(Utc::now());
+            tasks[1].progress = 40;
+            tasks[1].priority = Priority::High;
+            tasks[1].depends_on = vec![1];
+            tasks[2].completed = true;
+            tasks[2].priority = Priority::Low;
+            tasks[2].depends_on = vec![1, 2];
+        }
+
+        let tasks = app_data.tasks("ana");
+        let sentences: Vec<String> = tasks.iter().map(|task| app_data.format_task_accessible(task, today)).collect();
+        assert_eq!(
+            sentences,
+            [
+                "Task 1, Buy milk, pending, due tomorrow, tagged errand and home.",
+                "Task 2, Call dentist, in progress, 40 percent done, high priority, depends on task 1.",
+                "Task 3, File taxes, completed, low priority, depends on tasks 1 and 2.",
+            ]
+        );
+
+        let mut out = Vec::new();
+        app_data.print_dependency_matrix("ana", &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Task 2 depends on task 1.\nTask 3 depends on tasks 1 and 2.\n"
+        );
+
+        let stats = TaskStats { pending: 1, in_progress: 1, completed: 1, ..TaskStats::default() };
+        assert_eq!(stats.summary_line(true), "1 pending, 1 in progress, 0 overdue, 1 done.");
+        assert_eq!(stats.summary_line(false), "[1 pending | 1 in progress | 0 overdue | 1 done]");
+
+        let old = tasks[1].clone();
+        let renamed = Task { description: "Call the dentist".to_string(), ..old.clone() };
+        let done = Task { completed: true, ..old.clone() };
+        let changes = [
+            Change::Added { username: "ana".to_string(), task: tasks[0].clone() },
+            Change::Removed { username: "ana".to_string(), task: tasks[2].clone() },
+            Change::Modified { username: "ana".to_string(), old: Box::new(old.clone()), new: Box::new(done) },
+            Change::Modified { username: "ana".to_string(), old: Box::new(old.clone()), new: Box::new(renamed) },
+            Change::Modified {
+                username: "ana".to_string(),
+                old: Box::new(old.clone()),
+                new: Box::new(Task { progress: 60, ..old }),
+            },
+            Change::UserAdded { username: "ben".to_string() },
+            Change::UserRemoved { username: "cleo".to_string() },
+        ];
+        let sentences: Vec<String> = changes.iter().map(Change::sentence).collect();
+        assert_eq!(
+            sentences,
+            [
+                "Task 1, Buy milk, added.",
+                "Task 3, File taxes, removed.",
+                "Task 2, Call dentist, completed.",
+                "Task 2 renamed from Call dentist to Call the dentist.",
+                "Task 2, Call dentist, updated.",
+                "User ben added.",
+                "User cleo removed.",
+            ]
+        );
+    }
+
+    #[test]
+    fn taskwarrior_export_uses_its_date_format_and_fields() {
+        let created = "2024-03-05T14:30:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(taskwarrior_date(created), "20240305T143000Z");
+
+        let mut app_data = AppData::new();
+        app_data.add_task_force("dana", "Water plants".to_string()).unwrap();
+        app_data.add_task_force("dana", "Pay rent".to_string()).unwrap();
+        {
+            let tasks = app_data.tasks_mut("dana");
+            tasks[0].uuid = Uuid::nil();
+            tasks[0].created_at = created;
+            tasks[0].due_date = NaiveDate::from_ymd_opt(2024, 3, 9);
+            tasks[0].tags = vec!["home".to_string()];
+            tasks[1].completed = true;
+            tasks[1].completed_at = Some("2024-03-06T08:05:09Z".parse().unwrap());
+            tasks[1].priority = Priority::Critical;
+        }
+
+        let json = app_data.export_taskwarrior_json("dana");
+        let exported = json.as_array().unwrap();
+        assert_eq!(exported.len(), 2);
+        let pending = &exported[0];
+        assert_eq!(pending["description"], "Water plants");
+        assert_eq!(pending["status"], "pending");
+        assert_eq!(pending["entry"], "20240305T143000Z");
+        assert_eq!(pending["due"], "20240309T000000Z");
+        assert_eq!(pending["tags"], serde_json::json!(["home"]));
+        assert_eq!(pending["priority"], "M");
+        assert_eq!(exported[1]["priority"], "H");
+        assert!(pending.get("end").is_none());
+        let uuid: Uuid = pending["uuid"].as_str().unwrap().parse().unwrap();
+        assert!(!uuid.is_nil());
+
+        let done = &exported[1];
+        assert_eq!(done["status"], "completed");
+        assert_eq!(done["end"], "20240306T080509Z");
+        assert_eq!(done["uuid"], app_data.tasks("dana")[1].uuid.to_string());
+        assert!(done.get("due").is_none());
+    }
+
+    #[test]
+    fn duplicate_usernames_in_users_file_keep_the_first_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+        let user = |username: &str, email: &str| User {
+            username: username.to_string(),
+            password: hash("secret", 4).unwrap(),
+            role: Role::default(),
+            email: Some(email.to_string()),
+            display_name: None,
+            settings: UserSettings::default(),
+            workflow: Vec::new(),
+        };
+        let users = [
+            user("eve", "first@example.com"),
+            user("finn", "finn@example.com"),
+            user("eve", "second@example.com"),
+        ];
+        fs::write(config.data_file("users", "json"), serde_json::to_vec(&users).unwrap()).unwrap();
+
+        let app_data = AppData::load_with_config(config, true).unwrap();
+        assert_eq!(app_data.users.len(), 2);
+        assert_eq!(app_data.users["eve"].email.as_deref(), Some("first@example.com"));
+        assert_eq!(app_data.users["finn"].email.as_deref(), Some("finn@example.com"));
+    }
+
+    #[test]
+    fn blockers_lists_open_dependencies_most_blocking_first() {
+        let mut app_data = AppData::new();
+        for description in ["Design", "Build", "Test", "Docs", "Release", "Old spike"] {
+            app_data.add_task_force("gus", description.to_string()).unwrap();
+        }
+        {
+            let tasks = app_data.tasks_mut("gus");
+            // Build and Docs wait on Design, Test on Build, Release on Build and Test.
+            tasks[1].depends_on = vec![1];
+            tasks[2].depends_on = vec![2];
+            tasks[3].depends_on = vec![1];
+            tasks[4].depends_on = vec![2, 3, 6];
+            // A finished blocker no longer blocks anything.
+            tasks[5].completed = true;
+        }
+
+        let ids: Vec<u32> = app_data.blockers("gus").iter().map(|task| task.id).collect();
+        assert_eq!(ids, [1, 2, 3]);
+
+        // Blocking only finished tasks doesn't count.
+        app_data.tasks_mut("gus")[3].completed = true;
+        app_data.tasks_mut("gus")[1].completed = true;
+        let ids: Vec<u32> = app_data.blockers("gus").iter().map(|task| task.id).collect();
+        assert_eq!(ids, [3]);
+        assert!(app_data.blockers("nobody").is_empty());
+    }
+
+    #[test]
+    fn bulk_assign_sprint_sets_and_clears_only_matching_tasks() {
+        let mut app_data = AppData::new();
+        for description in ["Fix login bug", "Fix export bug", "Write blog post"] {
+            app_data.add_task_force("hal", description.to_string()).unwrap();
+        }
+        app_data.tasks_mut("hal")[2].sprint = Some("Marketing".to_string());
+        let bugs = Filter { text: Some("bug".to_string()), ..Filter::default() };
+
+        assert_eq!(app_data.bulk_assign_sprint("hal", &bugs, "Sprint 7".to_string()), 2);
+        assert_eq!(app_data.bulk_assign_sprint("hal", &bugs, "Sprint 7".to_string()), 0);
+        let sprints: Vec<Option<&str>> = app_data.tasks("hal").iter().map(|task| task.sprint.as_deref()).collect();
+        assert_eq!(sprints, [Some("Sprint 7"), Some("Sprint 7"), Some("Marketing")]);
+
+        assert_eq!(app_data.bulk_assign_sprint("hal", &bugs, String::new()), 2);
+        let sprints: Vec<Option<&str>> = app_data.tasks("hal").iter().map(|task| task.sprint.as_deref()).collect();
+        assert_eq!(sprints, [None, None, Some("Marketing")]);
+    }
+
+    #[test]
+    fn menu_keys_map_to_choices_and_are_highlighted() {
+        let press = |code: KeyCode, modifiers: KeyModifiers| menu_key(&KeyEvent::new(code, modifiers));
+        assert_eq!(press(KeyCode::Char('v'), KeyModifiers::NONE), MenuKey::Choice("1"));
+        assert_eq!(press(KeyCode::Char('A'), KeyModifiers::SHIFT), MenuKey::Choice("2"));
+        assert_eq!(press(KeyCode::Char('q'), KeyModifiers::NONE), MenuKey::Choice(MENU_SAVE_AND_EXIT));
+        assert_eq!(press(KeyCode::Char('c'), KeyModifiers::NONE), MenuKey::Choice("5"));
+        // Ctrl-C is a key press in raw mode and still saves before exiting.
+        assert_eq!(press(KeyCode::Char('c'), KeyModifiers::CONTROL), MenuKey::Choice(MENU_SAVE_AND_EXIT));
+        assert_eq!(press(KeyCode::Char('4'), KeyModifiers::NONE), MenuKey::Digit('4'));
+        assert_eq!(press(KeyCode::Char('z'), KeyModifiers::NONE), MenuKey::Ignored);
+        assert_eq!(press(KeyCode::Enter, KeyModifiers::NONE), MenuKey::Ignored);
+
+        assert_eq!(highlight_key("View tasks", "1"), "[V]iew tasks");
+        assert_eq!(highlight_key("Mark task as completed", "5"), "Mark task as [c]ompleted");
+        assert_eq!(highlight_key("Save and exit", MENU_SAVE_AND_EXIT), "Save and exit [q]");
+        assert_eq!(highlight_key("Settings", "7"), "Settings");
+    }
+
+    #[test]
+    fn single_key_input_falls_back_without_a_terminal() {
+        // Under CI or with input piped in, raw mode must not be attempted.
+        if !io::stdin().is_terminal() {
+            assert!(!single_key_available());
+        }
+    }
+
+    #[test]
+    fn summary_table_has_a_row_per_user_and_is_for_admins_only() {
+        let mut app_data = AppData::new();
+        for (username, role) in [("ivy", Role::Admin), ("jon", Role::User), ("kim", Role::User)] {
+            let user = User {
+                username: username.to_string(),
+                password: String::new(),
+                role,
+                email: None,
+                display_name: None,
+                settings: UserSettings::default(),
+                workflow: Vec::new(),
+            };
+            app_data.users.insert(username.to_string(), user);
+        }
+        for description in ["Backups", "Audit", "Onboarding"] {
+            app_data.add_task_force("kim", description.to_string()).unwrap();
+        }
+        app_data.add_task_force("jon", "Timesheet".to_string()).unwrap();
+
+        let mut out = Vec::new();
+        assert!(matches!(app_data.print_summary_table("jon", &mut out), Err(AppError::PermissionDenied)));
+        assert!(matches!(app_data.print_summary_table("nobody", &mut out), Err(AppError::PermissionDenied)));
+        assert!(out.is_empty());
+
+        app_data.print_summary_table("ivy", &mut out).unwrap();
+        let table = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = table.lines().filter(|line| line.starts_with("| ")).skip(1).collect();
+        assert_eq!(rows.len(), app_data.users.len());
+        // Busiest first.
+        assert!(rows[0].contains("kim") && rows[1].contains("jon") && rows[2].contains("ivy"));
+        assert!(rows[2].contains("never"));
+    }
+
+    #[test]
+    fn smart_schedule_spreads_tasks_over_workdays() {
+        let mut app_data = AppData::new();
+        let created = "2024-01-01T09:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        for (index, description) in ["One", "Two", "Three", "Four", "Five", "Has a date", "Done"].iter().enumerate() {
+            app_data.add_task_force("lou", description.to_string()).unwrap();
+            app_data.tasks_mut("lou")[index].created_at = created + TimeDelta::hours(index as i64);
+        }
+        let fixed = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        {
+            let tasks = app_data.tasks_mut("lou");
+            tasks[4].priority = Priority::High;
+            tasks[5].due_date = Some(fixed);
+            tasks[6].completed = true;
+        }
+        let due_dates = |app_data: &AppData| -> Vec<Option<NaiveDate>> {
+            app_data.tasks("lou").iter().map(|task| task.due_date).collect()
+        };
+        let day = |day: u32| Some(NaiveDate::from_ymd_opt(2024, 1, day).unwrap());
+
+        // Thursday the 4th: two on Thursday, two on Friday, the last on Monday.
+        let thursday = NaiveDate::from_ymd_opt(2024, 1, 4).unwrap();
+        assert_eq!(app_data.smart_schedule("lou", 2, thursday, true).unwrap(), 5);
+        assert_eq!(due_dates(&app_data), [day(4), day(5), day(5), day(8), day(4), Some(fixed), None]);
+
+        for task in app_data.tasks_mut("lou").iter_mut().take(5) {
+            task.due_date = None;
+        }
+        assert_eq!(app_data.smart_schedule("lou", 2, thursday, false).unwrap(), 5);
+        assert_eq!(due_dates(&app_data), [day(4), day(5), day(5), day(6), day(4), Some(fixed), None]);
+
+        assert!(matches!(app_data.smart_schedule("lou", 0, thursday, true), Err(AppError::InvalidSchedule(_))));
+        assert_eq!(app_data.smart_schedule("lou", 2, thursday, true).unwrap(), 0);
+    }
+
+    #[test]
+    fn created_by_weekday_counts_monday_first() {
+        let mut app_data = AppData::new();
+        // A Monday, two Wednesdays and a Sunday.
+        let created = ["2024-01-01T10:00:00Z", "2024-01-03T08:00:00Z", "2024-01-10T17:30:00Z", "2024-01-07T12:00:00Z"];
+        for (index, created_at) in created.iter().enumerate() {
+            app_data.add_task_force("mia", format!("Task {}", index + 1)).unwrap();
+            app_data.tasks_mut("mia")[index].created_at = created_at.parse().unwrap();
+        }
+
+        assert_eq!(app_data.created_by_weekday("mia"), [1, 0, 2, 0, 0, 0, 1]);
+        assert_eq!(app_data.created_by_weekday("nobody"), [0; 7]);
+
+        // Evenings in UTC are already the next day further east.
+        app_data.config = Config::builder().with_timezone(Some("Asia/Tokyo".to_string())).build().unwrap();
+        app_data.tasks_mut("mia")[3].created_at = "2024-01-07T22:00:00Z".parse().unwrap();
+        assert_eq!(app_data.created_by_weekday("mia"), [2, 0, 1, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn undo_history_drops_the_oldest_steps_past_the_depth() {
+        let mut app_data = AppData::new();
+        app_data.config = Config::builder().with_undo_depth(3).build().unwrap();
+        for description in ["First", "Second", "Third", "Fourth"] {
+            app_data.execute("ned", Op::AddTask { description: description.to_string() }).unwrap();
+        }
+
+        for remaining in [3, 2, 1] {
+            app_data.execute("ned", Op::Undo).unwrap();
+            assert_eq!(app_data.tasks("ned").len(), remaining);
+        }
+        // Adding "First" fell off the history, so it stays.
+        assert!(matches!(app_data.execute("ned", Op::Undo), Err(AppError::NothingToUndo)));
+        assert_eq!(app_data.tasks("ned")[0].description, "First");
+
+        app_data.config = Config::builder().with_undo_depth(0).build().unwrap();
+        app_data.execute("ned", Op::AddTask { description: "Kept".to_string() }).unwrap();
+        assert!(matches!(app_data.execute("ned", Op::Undo), Err(AppError::NothingToUndo)));
+        assert_eq!(app_data.tasks("ned").len(), 2);
+    }
+
+    #[test]
+    fn complete_overdue_skips_blocked_and_locked_tasks() {
+        let mut app_data = AppData::new();
+        let today = NaiveDate::from_ymd_opt(2024, 6, 10).unwrap();
+        let day = |day: u32| Some(NaiveDate::from_ymd_opt(2024, 6, day).unwrap());
+        for description in ["Overdue", "Waits on 6", "Locked", "Due today", "No date", "Open dependency"] {
+            app_data.add_task_force("ola", description.to_string()).unwrap();
+        }
+        {
+            let tasks = app_data.tasks_mut("ola");
+            tasks[0].due_date = day(3);
+            tasks[1].due_date = day(9);
+            tasks[1].depends_on = vec![6];
+            tasks[2].due_date = day(1);
+            tasks[2].locked = true;
+            tasks[3].due_date = day(10);
+            tasks[5].due_date = day(20);
+        }
+
+        let blocked: Vec<u32> = app_data.blocked_overdue("ola", today).iter().map(|task| task.id).collect();
+        assert_eq!(blocked, [2, 3]);
+        assert_eq!(app_data.complete_overdue("ola", today), 1);
+        let completed: Vec<bool> = app_data.tasks("ola").iter().map(|task| task.completed).collect();
+        assert_eq!(completed, [true, false, false, false, false, false]);
+        assert_eq!(app_data.tasks("ola")[0].progress, 100);
+        assert_eq!(app_data.complete_overdue("ola", today), 0);
+
+        // Once its dependency is done the waiting task goes too.
+        app_data.tasks_mut("ola")[5].completed = true;
+        assert_eq!(app_data.complete_overdue("ola", today), 1);
+        assert!(app_data.tasks("ola")[1].completed);
+    }
+
+    #[test]
+    fn eisenhower_quadrants_follow_the_due_soon_window_and_priority() {
+        let mut app_data = AppData::new();
+        app_data.add_task_force("pia", "Probe".to_string()).unwrap();
+        let config = Config::builder().with_due_soon_days(3).build().unwrap();
+        let now = "2024-06-10T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let date = |day: u32| Some(NaiveDate::from_ymd_opt(2024, 6, day).unwrap());
+        let quadrant = |due_date: Option<NaiveDate>, priority: Priority, config: &Config| {
+            let task = Task { due_date, priority, ..app_data.tasks("pia")[0].clone() };
+            Quadrant::of(&task, now, config)
+        };
+
+        // Overdue, today and the last day of the window are urgent; the day after and no date aren't.
+        let cases = [(date(1), true), (date(10), true), (date(13), true), (date(14), false), (None, false)];
+        for (due_date, urgent) in cases {
+            let expected = if urgent { Quadrant::UrgentImportant } else { Quadrant::Important };
+            assert_eq!(quadrant(due_date, Priority::High, &config), expected, "{:?}", due_date);
+            for priority in [Priority::Medium, Priority::Low] {
+                let expected = if urgent { Quadrant::Urgent } else { Quadrant::Neither };
+                assert_eq!(quadrant(due_date, priority, &config), expected, "{:?} {:?}", due_date, priority);
+            }
+        }
+
+        // The window is counted from today in the display timezone.
+        let config = Config::builder()
+            .with_due_soon_days(3)
+            .with_timezone(Some("Pacific/Kiritimati".to_string()))
+            .build()
+            .unwrap();
+        assert_eq!(quadrant(date(14), Priority::Low, &config), Quadrant::Urgent);
+
+        // An escalated Medium task counts as important.
+        let config = Config::builder().with_due_soon_days(3).with_escalation(1, false).build().unwrap();
+        assert_eq!(quadrant(date(11), Priority::Medium, &config), Quadrant::UrgentImportant);
+        assert_eq!(quadrant(date(13), Priority::Medium, &config), Quadrant::Urgent);
+    }
+
+    #[test]
+    fn csv_export_of_all_users_keeps_ownership_and_order() {
+        let mut app_data = AppData::new();
+        let admin = User {
+            username: "root".to_string(),
+            password: String::new(),
+            role: Role::Admin,
+            email: None,
+            display_name: None,
+            settings: UserSettings::default(),
+            workflow: Vec::new(),
+        };
+        app_data.users.insert("root".to_string(), admin);
+        app_data.add_task_force("zed", "Zed's only task".to_string()).unwrap();
+        app_data.add_task_force("amy", "Plan, then build".to_string()).unwrap();
+        app_data.add_task_force("amy", "Ship".to_string()).unwrap();
+        app_data.add_task_force("bo", "Review \"draft\"".to_string()).unwrap();
+        app_data.tasks_mut("amy")[1].completed = true;
+        app_data.tasks_mut("amy")[1].tags = vec!["release".to_string(), "q3".to_string()];
+        app_data.tasks_mut("bo")[0].due_date = NaiveDate::from_ymd_opt(2024, 7, 1);
+
+        let mut out = Vec::new();
+        assert!(matches!(app_data.export_csv_all_users("amy", &mut out), Err(AppError::PermissionDenied)));
+        assert!(out.is_empty());
+        app_data.export_csv_all_users("root", &mut out).unwrap();
+
+        let mut reader = csv::Reader::from_reader(out.as_slice());
+        let header: Vec<String> = reader.headers().unwrap().iter().map(str::to_string).collect();
+        let columns = ["username", "task_id", "description", "status", "priority", "due_date", "created_at", "tags"];
+        assert_eq!(header, columns);
+        let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        let summary: Vec<[&str; 4]> = rows.iter().map(|row| [&row[0], &row[1], &row[2], &row[3]]).collect();
+        assert_eq!(
+            summary,
+            [
+                ["amy", "1", "Plan, then build", "Pending"],
+                ["amy", "2", "Ship", "Completed"],
+                ["bo", "1", "Review \"draft\"", "Pending"],
+                ["zed", "1", "Zed's only task", "Pending"],
+            ]
+        );
+        assert_eq!(&rows[1][7], "release,q3");
+        assert_eq!((&rows[2][5], &rows[0][5]), ("2024-07-01", ""));
+    }
+
+    #[test]
+    fn parse_errors_name_the_file_and_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+        let path = config.data_file("users", "json");
+        let contents = [
+            "[",
+            "  {\"username\": \"sam\", \"password\": \"x\"},",
+            "  {\"username\": \"tia\" \"password\": \"y\"}",
+            "]",
+        ];
+        fs::write(&path, contents.join("\n")).unwrap();
+
+        let error = match AppData::load_with_config(config, true) {
+            Err(error) => error,
+            Ok(_) => panic!("a malformed users file must not load"),
+        };
+        let message = error.to_string();
+        assert!(message.starts_with(&format!("Could not parse {} at line 3, column ", path.display())), "{}", message);
+        assert!(!message.contains(" at line 3 column "), "{}", message);
+        let lines: Vec<&str> = message.lines().collect();
+        assert_eq!(lines[1], contents[2]);
+        assert_eq!(lines[2].trim_start(), "^");
+        assert_eq!(lines[2].len(), "  {\"username\": \"tia\" \"".len());
+    }
+
+    #[test]
+    fn contexts_are_validated_filtered_and_listed() {
+        let mut app_data = AppData::new();
+        for description in ["Print slides", "Call bank", "Buy stamps", "Book flights"] {
+            app_data.add_task_force("uma", description.to_string()).unwrap();
+        }
+
+        for invalid in ["office", "@", "@home office", "@e-mail", "@@phone"] {
+            match app_data.set_context("uma", 1, Some(invalid.to_string())) {
+                Err(AppError::InvalidContext(context)) => assert_eq!(context, invalid),
+                other => panic!("expected {} to be refused, got {:?}", invalid, other),
+            }
+        }
+        assert_eq!(app_data.task("uma", 1).unwrap().context, None);
+
+        app_data.set_context("uma", 1, Some("@office".to_string())).unwrap();
+        app_data.set_context("uma", 2, Some("@phone".to_string())).unwrap();
+        app_data.set_context("uma", 3, Some("@errands2".to_string())).unwrap();
+        app_data.set_context("uma", 4, Some("@Office".to_string())).unwrap();
+
+        let office: Vec<u32> = app_data.tasks_by_context("uma", "@OFFICE").iter().map(|task| task.id).collect();
+        assert_eq!(office, [1, 4]);
+        assert!(app_data.tasks_by_context("uma", "@home").is_empty());
+        assert_eq!(app_data.all_contexts("uma"), ["@errands2", "@office", "@phone"]);
+
+        app_data.set_context("uma", 1, None).unwrap();
+        assert_eq!(app_data.all_contexts("uma"), ["@errands2", "@Office", "@phone"]);
+        assert!(app_data.all_contexts("nobody").is_empty());
+    }
+
+    #[test]
+    fn all_tags_merges_every_users_tags_sorted() {
+        let mut app_data = AppData::new();
+        app_data.add_task_force("vic", "Quarterly report".to_string()).unwrap();
+        app_data.add_task_force("vic", "Team lunch".to_string()).unwrap();
+        app_data.add_task_force("wes", "Budget review".to_string()).unwrap();
+        app_data.tasks_mut("vic")[0].tags = vec!["work".to_string(), "finance".to_string()];
+        app_data.tasks_mut("vic")[1].tags = vec!["work".to_string(), "social".to_string()];
+        app_data.tasks_mut("wes")[0].tags = vec!["finance".to_string(), "admin".to_string()];
+
+        let tags: Vec<String> = app_data.all_tags().into_iter().collect();
+        assert_eq!(tags, ["admin", "finance", "social", "work"]);
+        assert!(AppData::new().all_tags().is_empty());
+    }
+
+    #[test]
+    fn age_marker_adds_a_dot_per_threshold_passed() {
+        let now = "2024-05-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let days_ago = |days: i64| now - TimeDelta::days(days);
+        let defaults = [7, 30, 90];
+
+        let markers: Vec<&str> =
+            [0, 6, 7, 29, 30, 89, 90, 400].iter().map(|&days| age_marker(days_ago(days), now, defaults)).collect();
+        assert_eq!(markers, ["", "", "•", "•", "••", "••", "•••", "•••"]);
+        // A day counts once it has fully passed.
+        assert_eq!(age_marker(days_ago(7) + TimeDelta::seconds(1), now, defaults), "");
+        // Timestamps from a clock running ahead don't get a marker.
+        assert_eq!(age_marker(now + TimeDelta::days(10), now, defaults), "");
+
+        assert_eq!(age_marker(days_ago(3), now, [1, 2, 3]), "•••");
+        assert_eq!(age_marker(days_ago(14), now, [14, 14, 60]), "••");
+    }
+
+    #[test]
+    fn archive_stale_moves_only_long_untouched_tasks() {
+        let mut app_data = AppData::new();
+        for description in ["Forgotten", "Recently edited", "Recently completed", "Old but locked"] {
+            app_data.add_task_force("xia", description.to_string()).unwrap();
+        }
+        let long_ago = Utc::now() - TimeDelta::days(120);
+        for task in app_data.tasks_mut("xia").iter_mut() {
+            task.last_modified_at = long_ago;
+        }
+        app_data.tasks_mut("xia")[3].locked = true;
+        let edit = Op::EditTask { task_id: 2, description: "Edited".to_string(), expected_version: None };
+        app_data.execute("xia", edit).unwrap();
+        app_data.execute("xia", Op::MarkCompleted { task_id: 3 }).unwrap();
+
+        assert_eq!(app_data.archive_stale("xia", 90, Utc::now()), 1);
+        let remaining: Vec<u32> = app_data.tasks("xia").iter().map(|task| task.id).collect();
+        assert_eq!(remaining, [2, 3, 4]);
+        assert_eq!(app_data.list_archived("xia")[0].description, "Forgotten");
+
+        // Measured from `now`, so a later run catches up with the rest.
+        let later = Utc::now() + TimeDelta::days(100);
+        assert_eq!(app_data.archive_stale("xia", 90, later), 2);
+        assert_eq!(app_data.tasks("xia")[0].description, "Old but locked");
+        assert_eq!(app_data.archive_stale("nobody", 90, later), 0);
+    }
+
+    #[test]
+    fn cross_user_search_and_overview_are_for_admins_only() {
+        let mut app_data = AppData::new();
+        for (username, role) in [("yan", Role::Admin), ("zoe", Role::User)] {
+            let user = User {
+                username: username.to_string(),
+                password: String::new(),
+                role,
+                email: None,
+                display_name: None,
+                settings: UserSettings::default(),
+                workflow: Vec::new(),
+            };
+            app_data.users.insert(username.to_string(), user);
+        }
+        app_data.add_task_force("zoe", "Renew passport".to_string()).unwrap();
+        app_data.add_task_force("zoe", "Pay water bill".to_string()).unwrap();
+        app_data.add_task_force("abe", "Passport photos".to_string()).unwrap();
+
+        for non_admin in ["zoe", "abe", ""] {
+            assert!(matches!(app_data.search_all_users(non_admin, "passport"), Err(AppError::PermissionDenied)));
+            let mut out = Vec::new();
+            assert!(matches!(app_data.print_summary_table(non_admin, &mut out), Err(AppError::PermissionDenied)));
+        }
+
+        let found: Vec<(&str, &str)> = app_data
+            .search_all_users("yan", "PASSPORT")
+            .unwrap()
+            .into_iter()
+            .map(|(owner, task)| (owner, task.description.as_str()))
+            .collect();
+        assert_eq!(found, [("abe", "Passport photos"), ("zoe", "Renew passport")]);
+        assert!(app_data.search_all_users("yan", "groceries").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rebalance_priorities_splits_open_tasks_into_equal_tiers() {
+        let mut app_data = AppData::new();
+        let created = "2024-01-01T09:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        for index in 0..14 {
+            app_data.add_task_force("ada", format!("Task {}", index + 1)).unwrap();
+            let task = &mut app_data.tasks_mut("ada")[index];
+            task.created_at = created + TimeDelta::hours(index as i64);
+            // Nearly everything was marked Critical.
+            task.priority = if index < 10 { Priority::Critical } else { Priority::Low };
+        }
+        {
+            let tasks = app_data.tasks_mut("ada");
+            tasks[12].completed = true;
+            tasks[13].locked = true;
+            // A due date puts task 10 ahead of the older Critical tasks.
+            tasks[9].due_date = NaiveDate::from_ymd_opt(2024, 2, 1);
+        }
+
+        let planned = app_data.plan_rebalance("ada");
+        let changes = app_data.rebalance_priorities("ada");
+        assert_eq!(changes, planned);
+        let open: Vec<&Task> = app_data.tasks("ada").iter().filter(|task| !task.completed && !task.locked).collect();
+        assert_eq!(open.len(), 12);
+        for priority in Priority::ALL {
+            assert_eq!(open.iter().filter(|task| task.priority == priority).count(), 3, "{:?}", priority);
+        }
+        let priorities: Vec<Priority> = app_data.tasks("ada").iter().map(|task| task.priority).collect();
+        use Priority::{Critical, High, Low, Medium};
+        assert_eq!(
+            priorities,
+            [Critical, Critical, High, High, High, Medium, Medium, Medium, Low, Critical, Low, Low, Low, Low]
+        );
+        assert_eq!(changes.len(), 7);
+        assert_eq!((changes[&3], changes[&6], changes[&9]), ((Critical, High), (Critical, Medium), (Critical, Low)));
+        assert!(!changes.contains_key(&10) && !changes.contains_key(&11) && !changes.contains_key(&12));
+        assert!(app_data.rebalance_priorities("ada").is_empty());
+    }
+
+    #[test]
+    fn work_breakdown_nests_milestone_sprint_and_priority() {
+        let mut app_data = AppData::new();
+        for description in ["Schema", "API", "Launch post", "Refactor"] {
+            app_data.add_task_force("bea", description.to_string()).unwrap();
+        }
+        {
+            let tasks = app_data.tasks_mut("bea");
+            tasks[0].milestone = Some("v1.0".to_string());
+            tasks[0].sprint = Some("Sprint 1".to_string());
+            tasks[0].priority = Priority::High;
+            tasks[0].completed = true;
+            tasks[1].milestone = Some("v1.0".to_string());
+            tasks[1].sprint = Some("Sprint 2".to_string());
+            tasks[2].milestone = Some("v2.0".to_string());
+            tasks[2].sprint = Some("Sprint 2".to_string());
+            tasks[2].priority = Priority::Low;
+        }
+
+        let wbs = app_data.compute_work_breakdown_structure("bea");
+        let expected = serde_json::json!({
+            "Unassigned": {
+                "Backlog": { "Medium": [{ "id": 4, "description": "Refactor", "completed": false }] },
+            },
+            "v1.0": {
+                "Sprint 1": { "High": [{ "id": 1, "description": "Schema", "completed": true }] },
+                "Sprint 2": { "Medium": [{ "id": 2, "description": "API", "completed": false }] },
+            },
+            "v2.0": {
+                "Sprint 2": { "Low": [{ "id": 3, "description": "Launch post", "completed": false }] },
+            },
+        });
+        assert_eq!(wbs, expected);
+        assert_eq!(app_data.compute_work_breakdown_structure("nobody"), serde_json::json!({}));
+    }
+
+    #[test]
+    fn saving_under_a_profile_writes_its_own_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = || Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let work = builder().with_profile("work".to_string()).with_journal_fsync(false).build().unwrap();
+        let default = builder().with_journal_fsync(false).build().unwrap();
+
+        let mut app_data = AppData::load_with_config(work.clone(), true).unwrap();
+        app_data.register_user("cal".to_string(), "secret".to_string()).unwrap();
+        app_data.add_task_force("cal", "Quarterly planning".to_string()).unwrap();
+        app_data.save().unwrap();
+
+        assert!(dir.path().join("tasks_work.json").exists());
+        assert!(dir.path().join("users_work.json").exists());
+        assert!(!dir.path().join("tasks.json").exists());
+        assert!(!dir.path().join("users.json").exists());
+
+        let home = AppData::load_with_config(default, true).unwrap();
+        assert!(home.users.is_empty() && home.tasks("cal").is_empty());
+        let reloaded = AppData::load_with_config(work, true).unwrap();
+        assert_eq!(reloaded.tasks("cal")[0].description, "Quarterly planning");
+        assert!(reloaded.authenticate("cal", "secret"));
+    }
+
+    #[test]
+    fn find_replace_changes_only_matching_unlocked_tasks() {
+        let mut app_data = AppData::new();
+        for description in ["Email Acme re: Acme invoice", "Call Acme", "Lunch with Bo", "Acme contract"] {
+            app_data.add_task_force("dex", description.to_string()).unwrap();
+        }
+        app_data.tasks_mut("dex")[3].locked = true;
+        let untouched_since = app_data.tasks("dex")[2].last_modified_at;
+
+        assert_eq!(app_data.find_replace("dex", "Acme", "Initech"), 2);
+        let descriptions: Vec<&str> = app_data.tasks("dex").iter().map(|task| task.description.as_str()).collect();
+        assert_eq!(
+            descriptions,
+            ["Email Initech re: Initech invoice", "Call Initech", "Lunch with Bo", "Acme contract"]
+        );
+        assert_eq!(app_data.tasks("dex")[2].last_modified_at, untouched_since);
+
+        // Literal and case-sensitive.
+        assert_eq!(app_data.find_replace("dex", "initech", "X"), 0);
+        assert_eq!(app_data.find_replace("dex", ".*", "X"), 0);
+        assert_eq!(app_data.find_replace("dex", "", "X"), 0);
+    }
+
+    #[test]
+    fn task_json_round_trips_to_the_same_task() {
+        let mut app_data = AppData::new();
+        app_data.add_task_force("eli", "Draft the \"Q3\" plan".to_string()).unwrap();
+        app_data.add_task_force("eli", "Review".to_string()).unwrap();
+        {
+            let task = &mut app_data.tasks_mut("eli")[1];
+            task.priority = Priority::High;
+            task.due_date = NaiveDate::from_ymd_opt(2024, 9, 30);
+            task.tags = vec!["planning".to_string()];
+            task.depends_on = vec![1];
+            task.link = Some("https://example.com/plan".to_string());
+            task.started_at = Some("2024-09-01T08:00:00Z".parse().unwrap());
+            task.progress = 25;
+        }
+
+        for task_id in [1, 2] {
+            let json = app_data.task_json("eli", task_id).unwrap();
+            let task: Task = serde_json::from_str(&json).unwrap();
+            assert_eq!(&task, app_data.task("eli", task_id).unwrap());
+        }
+        match app_data.task_json("eli", 3) {
+            Err(AppError::TaskNotFound(id)) => assert_eq!(id, "3"),
+            other => panic!("expected TaskNotFound, got {:?}", other),
+        }
+        assert!(matches!(app_data.task_json("nobody", 1), Err(AppError::TaskNotFound(_))));
+    }
+
+    #[test]
+    fn custom_workflow_validates_orders_and_only_terminal_counts_as_done() {
+        for (input, problem) in [
+            ("Only", "at least two"),
+            ("Todo, , Done", "can't be empty"),
+            ("Todo, todo, Done", "more than once"),
+            ("Todo, Done*, Archived*", "exactly one"),
+            ("Done*, Todo", "first status"),
+        ] {
+            match WorkflowStatus::parse_workflow(input) {
+                Err(AppError::InvalidWorkflow(message)) => assert!(message.contains(problem), "{}: {}", input, message),
+                other => panic!("expected {} to be refused, got {:?}", input, other),
+            }
+        }
+        let workflow = WorkflowStatus::parse_workflow("Backlog, Ready, Doing, Review, Done").unwrap();
+        let terminal: Vec<&str> = workflow.iter().filter(|step| step.terminal).map(|step| step.name.as_str()).collect();
+        assert_eq!(terminal, ["Done"]);
+
+        let mut app_data = AppData::new();
+        let user = User {
+            username: "fay".to_string(),
+            password: String::new(),
+            role: Role::default(),
+            email: None,
+            display_name: None,
+            settings: UserSettings::default(),
+            workflow: Vec::new(),
+        };
+        app_data.users.insert("fay".to_string(), user);
+        app_data.add_task_force("fay", "Migrate DB".to_string()).unwrap();
+        app_data.add_task_force("fay", "Old work".to_string()).unwrap();
+        app_data.add_task_force("fay", "Started".to_string()).unwrap();
+        app_data.tasks_mut("fay")[1].completed = true;
+        app_data.tasks_mut("fay")[2].started_at = Some(Utc::now());
+        app_data.set_workflow("fay", workflow).unwrap();
+
+        // Existing statuses map onto the new workflow.
+        let steps = |app_data: &AppData| -> Vec<(String, Vec<u32>)> {
+            app_data
+                .tasks_by_workflow("fay")
+                .into_iter()
+                .map(|(step, tasks)| (step.name, tasks.iter().map(|task| task.id).collect()))
+                .collect()
+        };
+        let names = ["Backlog", "Ready", "Doing", "Review", "Done"].map(str::to_string);
+        let ids = [vec![1], vec![3], vec![], vec![], vec![2]];
+        assert_eq!(steps(&app_data), names.into_iter().zip(ids).collect::<Vec<_>>());
+
+        assert!(matches!(app_data.set_status("fay", 1, "Blocked"), Err(AppError::UnknownStatus(_))));
+        app_data.set_status("fay", 1, " review ").unwrap();
+        let today = Utc::now().date_naive();
+        assert_eq!(app_data.task_stats("fay", today).completed, 1);
+        assert!(app_data.task("fay", 1).unwrap().started_at.is_some());
+
+        app_data.step_status("fay", 1, true).unwrap();
+        assert_eq!(app_data.task_stats("fay", today).completed, 2);
+        app_data.step_status("fay", 1, true).unwrap();
+        assert_eq!(app_data.task("fay", 1).unwrap().workflow_status.as_deref(), Some("Done"));
+        app_data.step_status("fay", 1, false).unwrap();
+        assert!(!app_data.task("fay", 1).unwrap().completed);
+        assert_eq!(app_data.task_stats("fay", today).completed, 1);
+
+        app_data.step_status("fay", 3, false).unwrap();
+        app_data.step_status("fay", 3, false).unwrap();
+        assert_eq!(app_data.task("fay", 3).unwrap().started_at, None);
+        assert_eq!(steps(&app_data)[0], ("Backlog".to_string(), vec![3]));
+    }
+
+    #[test]
+    fn completion_heatmap_counts_recent_completion_days_only() {
+        let mut app_data = AppData::new();
+        let now = Utc::now();
+        let completed = [Some(0), Some(0), Some(2), Some(6), Some(7), None];
+        for (index, days_ago) in completed.iter().enumerate() {
+            app_data.add_task_force("gil", format!("Task {}", index + 1)).unwrap();
+            let task = &mut app_data.tasks_mut("gil")[index];
+            task.completed = days_ago.is_some();
+            task.completed_at = days_ago.map(|days| now - TimeDelta::days(days));
+        }
+        let today = now.date_naive();
+        let day = |days_ago: u64| today - Days::new(days_ago);
+
+        let heatmap = app_data.task_completion_heatmap_data("gil", 7);
+        assert_eq!(heatmap, HashMap::from([(day(0), 2), (day(2), 1), (day(6), 1)]));
+        assert!(!heatmap.contains_key(&day(1)));
+
+        assert_eq!(app_data.task_completion_heatmap_data("gil", 1), HashMap::from([(day(0), 2)]));
+        assert!(app_data.task_completion_heatmap_data("gil", 0).is_empty());
+        assert_eq!(app_data.task_completion_heatmap_data("gil", 30).values().sum::<usize>(), 5);
+    }
+
+    #[test]
+    fn add_tag_lowercases_and_refuses_spaces_and_commas() {
+        let mut app_data = AppData::new();
+        app_data.add_task_force("hana", "Book venue".to_string()).unwrap();
+
+        app_data.add_tag("hana", 1, " Wedding-2025 ").unwrap();
+        app_data.add_tag("hana", 1, "WEDDING-2025").unwrap();
+        assert_eq!(app_data.task("hana", 1).unwrap().tags, ["wedding-2025"]);
+
+        for invalid in ["big day", "venue,catering", "tab\there", "   "] {
+            match app_data.add_tag("hana", 1, invalid) {
+                Err(AppError::InvalidTag(tag)) => assert_eq!(tag, invalid.trim()),
+                other => panic!("expected {:?} to be refused, got {:?}", invalid, other),
+            }
+        }
+        assert_eq!(app_data.task("hana", 1).unwrap().tags, ["wedding-2025"]);
+    }
+
+    #[test]
+    fn priority_escalation_for_display_and_when_overdue() {
+        let mut app_data = AppData::new();
+        for description in ["Due in two days", "Due in three days", "No due date", "Overdue", "Done"] {
+            app_data.add_task_force("ivo", description.to_string()).unwrap();
+        }
+        let now = "2024-04-10T09:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let date = |day: u32| NaiveDate::from_ymd_opt(2024, 4, day);
+        {
+            let tasks = app_data.tasks_mut("ivo");
+            tasks[0].due_date = date(12);
+            tasks[1].due_date = date(13);
+            tasks[3].due_date = date(8);
+            tasks[3].priority = Priority::Low;
+            tasks[4].due_date = date(10);
+            tasks[4].completed = true;
+        }
+        let effective = |app_data: &AppData| -> Vec<Priority> {
+            app_data.tasks("ivo").iter().map(|task| effective_priority(task, now, &app_data.config)).collect()
+        };
+        use Priority::{High, Low, Medium};
+
+        // Off by default; on, only pending tasks due within the window go up.
+        assert_eq!(effective(&app_data), [Medium, Medium, Medium, Low, Medium]);
+        app_data.config = Config::builder().with_escalation(2, false).build().unwrap();
+        assert_eq!(effective(&app_data), [High, Medium, Medium, Medium, Medium]);
+        let stored: Vec<Priority> = app_data.tasks("ivo").iter().map(|task| task.priority).collect();
+        assert_eq!(stored, [Medium, Medium, Medium, Low, Medium]);
+
+        // Persisting raises the overdue task once and records when.
+        assert_eq!(app_data.escalate_overdue("ivo", now), 1);
+        let overdue = app_data.task("ivo", 4).unwrap();
+        assert_eq!((overdue.priority, overdue.escalated_at), (Medium, Some(now)));
+        assert_eq!(app_data.escalate_overdue("ivo", now + TimeDelta::days(1)), 0);
+        // Already raised for being overdue, so display doesn't add a second level.
+        assert_eq!(effective(&app_data)[3], Medium);
+
+        // A new due date allows another escalation later.
+        app_data.set_due_date("ivo", 4, date(9));
+        assert_eq!(app_data.task("ivo", 4).unwrap().escalated_at, None);
+        assert_eq!(app_data.escalate_overdue("ivo", now), 1);
+        assert_eq!(app_data.task("ivo", 4).unwrap().priority, High);
+    }
+
+    #[test]
+    fn menu_summary_line_counts_a_sample_list() {
+        let mut app_data = AppData::new();
+        let today = NaiveDate::from_ymd_opt(2024, 8, 15).unwrap();
+        for description in ["Overdue", "Due later", "No date", "Started", "Done late", "Done"] {
+            app_data.add_task_force("jas", description.to_string()).unwrap();
+        }
+        {
+            let tasks = app_data.tasks_mut("jas");
+            tasks[0].due_date = NaiveDate::from_ymd_opt(2024, 8, 14);
+            tasks[1].due_date = NaiveDate::from_ymd_opt(2024, 8, 15);
+            tasks[3].started_at = Some(Utc::now());
+            tasks[3].due_date = NaiveDate::from_ymd_opt(2024, 8, 1);
+            // Finished tasks aren't overdue, whatever their due date.
+            tasks[4].due_date = NaiveDate::from_ymd_opt(2024, 8, 1);
+            tasks[4].completed = true;
+            tasks[5].completed = true;
+        }
+
+        let stats = app_data.task_stats("jas", today);
+        assert_eq!(stats.summary_line(false), "[3 pending | 1 in progress | 2 overdue | 2 done]");
+        assert_eq!(stats.summary_line(true), "3 pending, 1 in progress, 2 overdue, 2 done.");
+        let empty = app_data.task_stats("nobody", today);
+        assert_eq!(empty.summary_line(false), "[0 pending | 0 in progress | 0 overdue | 0 done]");
+    }
+
+    #[test]
+    fn truncate_display_cuts_between_graphemes_by_width() {
+        // ASCII: untouched when it fits, otherwise the ellipsis takes the last column.
+        assert!(matches!(truncate_display("Buy milk", 8), Cow::Borrowed("Buy milk")));
+        assert_eq!(truncate_display("Buy milk today", 10), "Buy milk …");
+        assert_eq!(truncate_display("Buy milk", 1), "…");
+        assert_eq!(truncate_display("Buy milk", 0), "");
+
+        // CJK characters are two columns wide and never halved.
+        assert_eq!(truncate_display("日本語のテキスト", 7), "日本語…");
+        assert_eq!(truncate_display("日本語のテキスト", 8), "日本語…");
+        assert_eq!(truncate_display("日本語のテキスト", 9), "日本語の…");
+
+        // A family emoji is one grapheme of five code points.
+        let family = "👨\u{200d}👩\u{200d}👧";
+        assert_eq!(truncate_display(&format!("{} trip planning", family), 4), format!("{} …", family));
+        assert_eq!(truncate_display(&format!("{} trip planning", family), 2), "…");
+
+        // Combining marks stay with their letter and take no column.
+        let cafe = "Cafe\u{301} au lait";
+        assert_eq!(truncate_display(cafe, 5), "Cafe\u{301}…");
+        assert_eq!(truncate_display(cafe, 4), "Caf…");
+        assert_eq!(truncate_display(cafe, 12), cafe);
+    }
+
+    #[test]
+    fn suggest_next_task_picks_the_highest_focus_score_that_can_start() {
+        let mut app_data = AppData::new();
+        let today = Utc::now().date_naive();
+        for description in ["Medium, no date", "High, no date", "Low, 3 days overdue", "Blocked", "Locked", "Done"] {
+            app_data.add_task_force("kai", description.to_string()).unwrap();
+        }
+        {
+            let tasks = app_data.tasks_mut("kai");
+            tasks[1].priority = Priority::High;
+            tasks[2].priority = Priority::Low;
+            tasks[2].due_date = Some(today - Days::new(3));
+            tasks[3].priority = Priority::High;
+            tasks[3].due_date = Some(today);
+            tasks[3].depends_on = vec![1];
+            tasks[4].priority = Priority::High;
+            tasks[4].due_date = Some(today - Days::new(10));
+            tasks[4].locked = true;
+            tasks[5].priority = Priority::High;
+            tasks[5].due_date = Some(today);
+            tasks[5].completed = true;
+        }
+        let now = Utc::now();
+        let scores: Vec<u32> =
+            app_data.tasks("kai").iter().map(|task| compute_focus_score(task, now, &app_data.config)).collect();
+        assert_eq!(scores[..3], [20, 30, 43]);
+        assert_eq!(app_data.suggest_next_task("kai").unwrap().id, 3);
+
+        // Once its dependency is done the blocked task (High, due today: 55) wins.
+        app_data.tasks_mut("kai")[0].completed = true;
+        assert_eq!(app_data.suggest_next_task("kai").unwrap().id, 4);
+
+        // Equal scores go to the lower ID.
+        app_data.tasks_mut("kai")[2].completed = true;
+        app_data.tasks_mut("kai")[3].completed = true;
+        app_data.add_task_force("kai", "Another High, no date".to_string()).unwrap();
+        app_data.tasks_mut("kai")[6].priority = Priority::High;
+        assert_eq!(app_data.suggest_next_task("kai").unwrap().id, 2);
+
+        for task in app_data.tasks_mut("kai").iter_mut().filter(|task| !task.locked) {
+            task.completed = true;
+        }
+        assert!(app_data.suggest_next_task("kai").is_none());
+        assert!(app_data.suggest_next_task("nobody").is_none());
+    }
+
+    #[test]
+    fn import_markdown_reads_checklist_items_and_skips_other_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("todo.md");
+        let markdown = [
+            "# Moving house",
+            "",
+            "Some notes about the move.",
+            "- [ ] Book the van",
+            "- [x] Give notice to landlord",
+            "  * [X] Cancel internet",
+            "+ [ ]   Pack the kitchen  ",
+            "- plain bullet",
+            "- [ ]",
+            "-[ ] missing space",
+            "1. [ ] numbered item",
+            "- [?] unknown mark",
+        ];
+        fs::write(&path, markdown.join("\n")).unwrap();
+
+        let mut app_data = AppData::new();
+        app_data.add_task_force("lee", "Already here".to_string()).unwrap();
+        assert_eq!(app_data.import_markdown("lee", &path).unwrap(), 4);
+
+        let imported: Vec<(u32, &str, bool)> =
+            app_data.tasks("lee").iter().map(|task| (task.id, task.description.as_str(), task.completed)).collect();
+        assert_eq!(
+            imported,
+            [
+                (1, "Already here", false),
+                (2, "Book the van", false),
+                (3, "Give notice to landlord", true),
+                (4, "Cancel internet", true),
+                (5, "Pack the kitchen", false),
+            ]
+        );
+        assert!(app_data.tasks("lee")[2].completed_at.is_some());
+
+        fs::write(&path, "Nothing to do here.\n").unwrap();
+        assert_eq!(app_data.import_markdown("lee", &path).unwrap(), 0);
+        assert!(matches!(app_data.import_markdown("lee", &dir.path().join("missing.md")), Err(AppError::Io(_))));
+    }
+
+    /// A Nextcloud-style VTODO with a time zone, an alarm, a folded line and
+    /// escaped text.
+    #[cfg(feature = "caldav")]
+    const VTODO_FIXTURE: &str = concat!(
+        "BEGIN:VCALENDAR\r\n",
+        "VERSION:2.0\r\n",
+        "PRODID:-//Nextcloud Tasks v0.16.0\r\n",
+        "BEGIN:VTIMEZONE\r\n",
+        "TZID:Europe/Kyiv\r\n",
+        "END:VTIMEZONE\r\n",
+        "BEGIN:VTODO\r\n",
+        "UID:3b8f0c52-6f1e-4d3a-9a57-1f2d9c4e8b10\r\n",
+        "DTSTAMP:20240301T090000Z\r\n",
+        "CREATED:20240301T080000Z\r\n",
+        "LAST-MODIFIED:20240302T101500Z\r\n",
+        "SUMMARY:Renew passport\\, then book flights\\; the summary is long enough t\r\n",
+        " o be folded\r\n",
+        "STATUS:IN-PROCESS\r\n",
+        "DUE;VALUE=DATE:20240315\r\n",
+        "PRIORITY:2\r\n",
+        "PERCENT-COMPLETE:40\r\n",
+        "CATEGORIES:travel,admin\r\n",
+        "DESCRIPTION:Bring two photos\r\n",
+        "BEGIN:VALARM\r\n",
+        "ACTION:DISPLAY\r\n",
+        "TRIGGER:-PT15M\r\n",
+        "END:VALARM\r\n",
+        "END:VTODO\r\n",
+        "END:VCALENDAR\r\n",
+    );
+
+    #[cfg(feature = "caldav")]
+    #[test]
+    fn vtodo_fixture_parses_and_serializes_back_unchanged() {
+        let todo = VTodo::parse(VTODO_FIXTURE).unwrap();
+        assert_eq!(todo.uid, "3b8f0c52-6f1e-4d3a-9a57-1f2d9c4e8b10");
+        assert_eq!(todo.summary, "Renew passport, then book flights; the summary is long enough to be folded");
+        assert_eq!(todo.status.as_deref(), Some("IN-PROCESS"));
+        assert_eq!(todo.created, Some("2024-03-01T08:00:00Z".parse().unwrap()));
+        assert_eq!(todo.last_modified, Some("2024-03-02T10:15:00Z".parse().unwrap()));
+        assert_eq!(todo.due, NaiveDate::from_ymd_opt(2024, 3, 15));
+        assert_eq!((todo.priority, todo.percent_complete), (Some(2), Some(40)));
+        assert_eq!(todo.categories, ["travel", "admin"]);
+        let alarm = ["BEGIN:VALARM", "ACTION:DISPLAY", "TRIGGER:-PT15M", "END:VALARM"];
+        assert_eq!(todo.extra[0], "DESCRIPTION:Bring two photos");
+        assert_eq!(todo.extra[1..], alarm);
+        assert_eq!(todo.calendar_extra, ["BEGIN:VTIMEZONE", "TZID:Europe/Kyiv", "END:VTIMEZONE"]);
+
+        let ics = todo.to_ics();
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.split("\r\n").all(|line| line.len() <= 75 && !line.contains('\n')), "{}", ics);
+        assert_eq!(VTodo::parse(&ics).unwrap(), todo);
+
+        // Pushing a task built on the server's copy keeps what tasks don't have.
+        let mut task = Task::new(1, String::new());
+        todo.apply_to(&mut task);
+        assert_eq!((task.priority, task.progress, task.status()), (Priority::High, 40, Status::InProgress));
+        assert_eq!(task.tags, ["travel", "admin"]);
+        let pushed = VTodo::from_task(&task, Some(&todo));
+        assert_eq!((pushed.uid.as_str(), pushed.priority), (todo.uid.as_str(), Some(2)));
+        // Only 1 means Critical, so it survives the round trip.
+        task.priority = Priority::Critical;
+        let critical = VTodo::from_task(&task, None);
+        assert_eq!(critical.priority, Some(1));
+        task.priority = Priority::Low;
+        critical.apply_to(&mut task);
+        assert_eq!(task.priority, Priority::Critical);
+        assert_eq!((&pushed.extra, &pushed.calendar_extra), (&todo.extra, &todo.calendar_extra));
+    }
+
+    #[cfg(feature = "caldav")]
+    #[test]
+    fn caldav_plan_picks_the_newer_side_and_respects_local_deletions() {
+        let at = |hour: u32| format!("2024-03-10T{:02}:00:00Z", hour).parse::<DateTime<Utc>>().unwrap();
+        let mut app_data = AppData::new();
+        for description in ["Local edit", "Remote edit", "Gone remotely", "Deleted here"] {
+            app_data.add_task_force("uma", description.to_string()).unwrap();
+        }
+        for (task, hour) in app_data.tasks_mut("uma").iter_mut().zip([11, 7, 7, 7]) {
+            task.last_modified_at = at(hour);
+        }
+        let remote = |task: &Task, summary: &str, modified: DateTime<Utc>| RemoteTodo {
+            href: Some(format!("{}.ics", task.uuid)),
+            etag: Some("\"1\"".to_string()),
+            todo: VTodo { summary: summary.to_string(), last_modified: Some(modified), ..VTodo::from_task(task, None) },
+        };
+        let tasks = app_data.tasks("uma").to_vec();
+        let from_phone = VTodo {
+            uid: "phone-123".to_string(),
+            summary: "From phone".to_string(),
+            last_modified: Some(at(9)),
+            ..VTodo::default()
+        };
+        let remote_todos = vec![
+            remote(&tasks[0], "Old summary", at(9)),
+            remote(&tasks[1], "Remote edit, renamed", at(10)),
+            remote(&tasks[3], "Deleted here", at(7)),
+            RemoteTodo { href: Some("phone-123.ics".to_string()), etag: None, todo: from_phone },
+        ];
+        let list = &mut app_data.task_lists[0];
+        list.caldav_synced_at = Some(at(8));
+        list.remove_tasks(&[4]);
+
+        let plan = plan_caldav_sync("uma", Some(&app_data.task_lists[0]), remote_todos);
+        let report = &plan.report;
+        assert_eq!((report.pulled_new, report.pulled_updated, report.pushed_new, report.pushed_updated), (1, 1, 1, 1));
+        // Only the local edit happened on both sides since the last sync.
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].task_id, 1);
+        assert_eq!(report.conflicts[0].resolution, "changed on both sides, kept the newer local version");
+
+        // The newer remote summary comes down; the task deleted here doesn't come back.
+        let pulled: Vec<(u32, &str)> = plan.pull.iter().map(|task| (task.id, task.description.as_str())).collect();
+        assert_eq!(pulled, [(2, "Remote edit, renamed"), (0, "From phone")]);
+        assert_eq!(plan.pull[1].uuid, caldav_uuid("phone-123"));
+
+        // The newer local summary goes up against the server's ETag; the task
+        // deleted on the server is uploaded again as new.
+        assert_eq!(plan.push.len(), 2);
+        assert_eq!(plan.push[0].todo.summary, "Local edit");
+        assert_eq!(plan.push[0].etag.as_deref(), Some("\"1\""));
+        assert_eq!((plan.push[1].href.as_deref(), plan.push[1].todo.summary.as_str()), (None, "Gone remotely"));
+        assert_eq!(plan.push[1].todo.uid, tasks[2].uuid.to_string());
+
+        app_data.execute("uma", Op::CalDavPull { tasks: plan.pull, synced_at: at(12) }).unwrap();
+        let descriptions: Vec<&str> = app_data.tasks("uma").iter().map(|task| task.description.as_str()).collect();
+        assert_eq!(descriptions, ["Local edit", "Remote edit, renamed", "Gone remotely", "From phone"]);
+        assert_eq!(app_data.task_lists[0].caldav_synced_at, Some(at(12)));
+    }
+
+    #[cfg(feature = "caldav")]
+    #[test]
+    fn multistatus_responses_yield_their_vtodos() {
+        let passport = VTODO_FIXTURE.replace("Renew passport", "Passport &amp; visa");
+        let xml = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">
+  <d:response><d:href>/dav/tasks/</d:href><d:propstat><d:prop><d:getetag/></d:prop></d:propstat></d:response>
+  <d:response>
+    <d:href>/dav/tasks/passport.ics</d:href>
+    <d:propstat><d:prop>
+      <d:getetag>&quot;42&quot;</d:getetag>
+      <cal:calendar-data>{}</cal:calendar-data>
+    </d:prop></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/dav/tasks/milk.ics</d:href>
+    <d:propstat><d:prop><cal:calendar-data><![CDATA[BEGIN:VCALENDAR
+BEGIN:VTODO
+UID:milk
+SUMMARY:Buy <milk>
+END:VTODO
+END:VCALENDAR]]></cal:calendar-data></d:prop></d:propstat>
+  </d:response>
+</d:multistatus>"#,
+            passport
+        );
+
+        let todos = parse_multistatus(&xml).unwrap();
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos[0].href.as_deref(), Some("/dav/tasks/passport.ics"));
+        assert_eq!(todos[0].etag.as_deref(), Some("\"42\""));
+        assert!(todos[0].todo.summary.starts_with("Passport & visa, then book flights"));
+        assert_eq!(todos[0].todo.extra.len(), 5);
+        assert_eq!((todos[1].href.as_deref(), todos[1].etag.as_deref()), (Some("/dav/tasks/milk.ics"), None));
+        assert_eq!((todos[1].todo.uid.as_str(), todos[1].todo.summary.as_str()), ("milk", "Buy <milk>"));
+
+        let broken = xml.replace("UID:milk", "X-NO-UID:milk");
+        let error = parse_multistatus(&broken).unwrap_err().to_string();
+        assert!(error.contains("VTODO without a UID") && error.contains("/dav/tasks/milk.ics"), "{}", error);
+    }
+
+    /// Talks to a real server, so it only runs on request:
+    /// CALDAV_TEST_URL=https://.../calendars/bob/scratch/ cargo test --features caldav -- --ignored
+    /// with CALDAV_TEST_USERNAME and CALDAV_TEST_PASSWORD if the calendar
+    /// needs them. Use a scratch calendar; the test leaves its task there.
+    #[cfg(feature = "caldav")]
+    #[test]
+    #[ignore]
+    fn caldav_sync_round_trips_through_a_real_server() {
+        let Ok(url) = env::var("CALDAV_TEST_URL") else {
+            eprintln!("CALDAV_TEST_URL not set, skipping");
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let device = |name: &str| {
+            let config = Config::builder()
+                .with_cost(4)
+                .with_data_dir(dir.path().join(name))
+                .with_journal_fsync(false)
+                .with_caldav(url.clone(), env::var("CALDAV_TEST_USERNAME").ok(), env::var("CALDAV_TEST_PASSWORD").ok())
+                .build()
+                .unwrap();
+            fs::create_dir_all(&config.data_dir).unwrap();
+            AppData::load_with_config(config, true).unwrap()
+        };
+        let description = format!("CalDAV round trip {}", Uuid::new_v4());
+
+        let mut laptop = device("laptop");
+        laptop.add_task_force("vic", description.clone()).unwrap();
+        laptop.tasks_mut("vic")[0].priority = Priority::High;
+        let report = laptop.sync_caldav("vic").unwrap();
+        assert!(report.pushed_new >= 1, "{}", report);
+
+        let mut phone = device("phone");
+        phone.sync_caldav("vic").unwrap();
+        let pulled = phone.tasks("vic").iter().find(|task| task.description == description).expect("task was pulled");
+        assert_eq!((pulled.uuid, pulled.priority), (laptop.tasks("vic")[0].uuid, Priority::High));
+
+        // Nothing changed since, so a second sync moves nothing.
+        let report = laptop.sync_caldav("vic").unwrap();
+        assert_eq!((report.pushed_new, report.pushed_updated, report.pulled_updated), (0, 0, 0), "{}", report);
+    }
+
+    /// An HTTP request as `serve_one_request` saw it.
+    #[cfg(any(feature = "import", feature = "webhooks"))]
+    struct ReceivedRequest {
+        /// E.g. "GET /repos/o/r/issues?state=all HTTP/1.1".
+        request_line: String,
+        /// Keyed by lowercased name.
+        headers: HashMap<String, String>,
+        body: String,
+    }
+
+    /// Answers one HTTP request on a local port with `status` and `body`.
+    /// Returns the base URL and a handle yielding the request.
+    #[cfg(any(feature = "import", feature = "webhooks"))]
+    fn serve_one_request(status: &str, body: &str) -> (String, thread::JoinHandle<ReceivedRequest>) {
+        use std::io::{BufRead, BufReader, Read};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut headers = HashMap::new();
+            let mut line = String::new();
+            loop {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+                let Some((name, value)) = line.split_once(':') else {
+                    break;
+                };
+                headers.insert(name.to_ascii_lowercase(), value.trim().to_string());
+            }
+            let length = headers.get("content-length").map_or(0, |length| length.parse().unwrap());
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            stream.write_all(response.as_bytes()).unwrap();
+            ReceivedRequest {
+                request_line: request_line.trim_end().to_string(),
+                headers,
+                body: String::from_utf8(body).unwrap(),
+            }
+        });
+        (url, handle)
+    }
+
+    #[cfg(feature = "webhooks")]
+    #[test]
+    fn webhook_posts_the_event_signed_with_the_secret() {
+        use hmac::{Hmac, Mac};
+
+        let (url, server) = serve_one_request("204 No Content", "");
+        let mut app_data = AppData::new();
+        let worker = WebhookWorker::start(vec![format!("{}/hooks/tasks", url)], Some("s3cret".to_string())).unwrap();
+        app_data.webhooks = Some(worker);
+        app_data.execute("wes", Op::AddTask { description: "Ship the release".to_string() }).unwrap();
+        // Dropping the worker waits for the queued delivery.
+        app_data.webhooks = None;
+
+        let request = server.join().unwrap();
+        assert_eq!(request.request_line, "POST /hooks/tasks HTTP/1.1");
+        assert_eq!(request.headers["content-type"], "application/json");
+        let event: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(event["event"], "task_added");
+        assert_eq!(event["username"], "wes");
+        assert_eq!(event["task"]["description"], "Ship the release");
+        assert_eq!(event["task"]["id"], 1);
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"s3cret").unwrap();
+        mac.update(request.body.as_bytes());
+        assert_eq!(request.headers["x-signature-256"], format!("sha256={:x}", mac.finalize().into_bytes()));
+    }
+
+    #[cfg(feature = "import")]
+    #[test]
+    fn github_import_reads_a_page_of_issues_and_skips_pull_requests() {
+        let page = r#"[
+            {
+                "title": "Crash on empty config",
+                "html_url": "https://github.com/octo/tasks/issues/7",
+                "state": "open",
+                "closed_at": null,
+                "labels": [{"name": "bug"}, {"name": "good first issue"}],
+                "milestone": {"title": "v1.2", "due_on": "2024-05-31T07:00:00Z"},
+                "pull_request": null
+            },
+            {
+                "title": "Fix crash on empty config",
+                "html_url": "https://github.com/octo/tasks/pull/8",
+                "state": "open",
+                "closed_at": null,
+                "labels": [{"name": "bug"}],
+                "milestone": null,
+                "pull_request": {"url": "https://api.github.com/repos/octo/tasks/pulls/8"}
+            },
+            {
+                "title": "Document the CLI",
+                "html_url": "https://github.com/octo/tasks/issues/3",
+                "state": "closed",
+                "closed_at": "2024-04-02T16:20:00Z",
+                "milestone": null
+            }
+        ]"#;
+        let (url, server) = serve_one_request("200 OK", page);
+        let mut app_data = AppData::new();
+
+        let imported = app_data.import_github_issues_from(&url, "gus", "octo", "tasks", "t0ken").unwrap();
+        assert_eq!(imported, 2);
+        let request = server.join().unwrap();
+        assert_eq!(request.request_line, "GET /repos/octo/tasks/issues?state=all&per_page=100 HTTP/1.1");
+        assert_eq!(request.headers["authorization"], "Bearer t0ken");
+        assert!(request.body.is_empty());
+
+        let tasks = app_data.tasks("gus");
+        let open = &tasks[0];
+        assert_eq!((open.description.as_str(), open.completed), ("Crash on empty config", false));
+        assert_eq!(open.tags, ["bug", "good first issue"]);
+        assert_eq!(open.milestone.as_deref(), Some("v1.2"));
+        assert_eq!(open.due_date, NaiveDate::from_ymd_opt(2024, 5, 31));
+        assert_eq!(open.uuid, Uuid::new_v5(&Uuid::NAMESPACE_URL, b"https://github.com/octo/tasks/issues/7"));
+        let closed = &tasks[1];
+        assert_eq!((closed.description.as_str(), closed.completed), ("Document the CLI", true));
+        assert_eq!(closed.completed_at, Some("2024-04-02T16:20:00Z".parse().unwrap()));
+        assert!(closed.tags.is_empty() && closed.milestone.is_none());
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn summary_pdf_is_written_and_overflowing_tasks_do_not_panic() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app_data = AppData::new();
+        app_data.add_task_force("pam", "Short one".to_string()).unwrap();
+        let short = dir.path().join("short.pdf");
+        app_data.export_summary_pdf("pam", &short).unwrap();
+        let bytes = fs::read(&short).unwrap();
+        assert!(bytes.starts_with(b"%PDF-"));
+        assert!(bytes.trim_ascii_end().ends_with(b"%%EOF"));
+
+        // Far more than a page, each wrapping over several lines, including
+        // multi-byte text and words longer than the wrap width.
+        let long = "Quartalszahlen prüfen und eine Zusammenfassung für das Team schreiben ".repeat(3);
+        for i in 0..200 {
+            let description = format!("{} {} {}", i, long, "x".repeat(90));
+            app_data.add_task_force("pam", description).unwrap();
+        }
+        let overflow = dir.path().join("overflow.pdf");
+        app_data.export_summary_pdf("pam", &overflow).unwrap();
+        assert!(fs::read(&overflow).unwrap().starts_with(b"%PDF-"));
+    }
+
+    #[test]
+    fn search_all_covers_own_and_shared_lists_but_never_foreign_ones() {
+        let mut app_data = AppData::new();
+        app_data.add_task_force("ola", "Buy milk".to_string()).unwrap();
+        app_data.add_task_force("pat", "Milk for the cat".to_string()).unwrap();
+        app_data.add_task_force("pat", "Bread".to_string()).unwrap();
+        app_data.add_task_force("quinn", "Milk the budget".to_string()).unwrap();
+        for list in &mut app_data.task_lists {
+            match list.username.as_str() {
+                "ola" => list.name = "home".to_string(),
+                "pat" => {
+                    list.name = "groceries".to_string();
+                    list.shared_with = vec!["ola".to_string()];
+                }
+                _ => list.name = "private".to_string(),
+            }
+        }
+
+        let found = |results: Vec<(&str, &Task)>| -> Vec<(String, String)> {
+            results.into_iter().map(|(list, task)| (list.to_string(), task.description.clone())).collect()
+        };
+        let pair = |list: &str, description: &str| (list.to_string(), description.to_string());
+        assert_eq!(
+            found(app_data.search_all("ola", "MILK")),
+            [pair("groceries", "Milk for the cat"), pair("home", "Buy milk")]
+        );
+        assert_eq!(found(app_data.search_own("ola", "milk")), [pair("home", "Buy milk")]);
+        // Sharing is one way: pat doesn't see ola's list, and nobody sees quinn's.
+        assert_eq!(found(app_data.search_all("pat", "milk")), [pair("groceries", "Milk for the cat")]);
+        for username in ["ola", "pat", "rex"] {
+            assert!(app_data.search_all(username, "budget").is_empty(), "{}", username);
+        }
+
+        let results = app_data.search_all("ola", "milk");
+        let json = search_results_json(&results);
+        let entries = json.as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        let keys: Vec<&String> = entries[0].as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["list", "task"]);
+        assert_eq!(entries[0]["list"], "groceries");
+        assert_eq!(entries[0]["task"]["id"], 1);
+        assert_eq!(entries[0]["task"]["description"], "Milk for the cat");
+        assert_eq!(entries[0]["task"]["completed"], false);
+        assert_eq!(entries[1]["list"], "home");
+        assert_eq!(entries[1]["task"]["description"], "Buy milk");
+    }
+
+    #[test]
+    fn review_answers_are_collected_then_applied_only_once_confirmed() {
+        let mut app_data = AppData::new();
+        for description in ["Water plants", "File taxes", "Call mum", "Old idea", "Fix bike"] {
+            app_data.add_task_force("rae", description.to_string()).unwrap();
+        }
+        let everything = Filter { text: None, status: None, priority: None, tag: None };
+
+        // Keep, complete, an invalid answer then skip, edit, delete.
+        let tasks = app_data.review_candidates("rae", &everything);
+        let mut script = io::Cursor::new("n\ny\nmaybe\ns\ne\nOld idea, revisited\nd\n");
+        let expected = [
+            (1, ReviewDecision::Keep),
+            (2, ReviewDecision::Complete),
+            (4, ReviewDecision::Edit("Old idea, revisited".to_string())),
+            (5, ReviewDecision::Delete),
+        ];
+        assert_eq!(collect_review_decisions(&tasks, &mut script), expected);
+        // Quitting, or running out of input, keeps what was decided so far.
+        let mut script = io::Cursor::new("n\ny\nq\nd\n");
+        assert_eq!(collect_review_decisions(&tasks, &mut script), expected[..2]);
+        let mut script = io::Cursor::new("n\ne\n");
+        assert_eq!(collect_review_decisions(&tasks, &mut script), expected[..1]);
+
+        // Stopping after the fourth task and declining, or never answering the
+        // confirmation, leaves every task as it was.
+        let before = serde_json::to_value(app_data.tasks("rae")).unwrap();
+        for script in ["n\ny\ns\nd\nq\nn\n", "n\ny\ns\nd\nq\n"] {
+            run_review(&mut app_data, "rae", &everything, &mut io::Cursor::new(script));
+            assert_eq!(serde_json::to_value(app_data.tasks("rae")).unwrap(), before, "{:?}", script);
+        }
+
+        run_review(&mut app_data, "rae", &everything, &mut io::Cursor::new("n\ny\ns\nd\nq\ny\n"));
+        let tasks: Vec<(u32, bool)> = app_data.tasks("rae").iter().map(|task| (task.id, task.completed)).collect();
+        assert_eq!(tasks, [(1, false), (2, true), (3, false), (5, false)]);
+    }
+
+    #[test]
+    fn standup_lists_recent_work_todays_focus_and_blockers() {
+        let since = "2024-09-09T09:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let standup = |app_data: &AppData| {
+            let mut out = Vec::new();
+            app_data.export_plaintext_standup("sol", since, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let mut app_data = AppData::new();
+        app_data.add_task_force("sol", "Probe".to_string()).unwrap();
+        app_data.tasks_mut("sol")[0].completed = true;
+        app_data.tasks_mut("sol")[0].completed_at = Some(since - TimeDelta::hours(1));
+        assert_eq!(standup(&app_data), "Done yesterday:\n- None\n\nDoing today:\n- None\n\nBlockers:\n- None\n");
+
+        let descriptions = [
+            "Wrote release notes",
+            "Review PR",
+            "Low chore",
+            "Urgent fix",
+            "Plan sprint",
+            "Write docs",
+            "Deploy",
+            "Wait for legal",
+            "Migrate",
+        ];
+        for description in descriptions {
+            app_data.add_task_force("sol", description.to_string()).unwrap();
+        }
+        {
+            let tasks = app_data.tasks_mut("sol");
+            tasks[1].completed = true;
+            tasks[1].completed_at = Some(since);
+            tasks[2].started_at = Some(since);
+            tasks[3].priority = Priority::Low;
+            tasks[4].priority = Priority::High;
+            tasks[5].due_date = NaiveDate::from_ymd_opt(2099, 1, 1);
+            tasks[7].depends_on = vec![3];
+            tasks[8].tags = vec!["blocked".to_string()];
+            tasks[9].depends_on = vec![2];
+        }
+
+        // Completed before `since` stays out; in-progress work comes first,
+        // then only the top three pending tasks, dated before undated.
+        let expected = "\
+Done yesterday:
+- Wrote release notes
+
+Doing today:
+- Review PR
+- Urgent fix
+- Plan sprint
+- Write docs
+
+Blockers:
+- Deploy
+- Wait for legal
+";
+        assert_eq!(standup(&app_data), expected);
+
+        // Finishing the dependency unblocks "Deploy".
+        app_data.tasks_mut("sol")[2].completed = true;
+        assert!(standup(&app_data).ends_with("Blockers:\n- Wait for legal\n"));
+    }
+
+    #[test]
+    fn change_plans_are_computed_without_touching_data_and_applied_as_planned() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+        let mut app_data = AppData::load_with_config(config.clone(), true).unwrap();
+        for description in ["Keep", "Done", "Also keep", "Done but locked", "Locked"] {
+            app_data.add_task_force("tom", description.to_string()).unwrap();
+        }
+        {
+            let tasks = app_data.tasks_mut("tom");
+            tasks[1].completed = true;
+            tasks[3].completed = true;
+            tasks[3].locked = true;
+            tasks[4].locked = true;
+        }
+
+        assert_eq!(app_data.plan_remove("tom", &[3, 1, 3]).unwrap().remove, [3, 1]);
+        assert!(matches!(app_data.plan_remove("tom", &[1, 9]), Err(AppError::TaskNotFound(id)) if id == "9"));
+        assert!(matches!(app_data.plan_remove("tom", &[5]), Err(AppError::TaskLocked(5))));
+        assert_eq!(app_data.plan_clear_completed("tom").remove, [2]);
+
+        let before = serde_json::to_value(&app_data.task_lists).unwrap();
+        let plan = app_data.plan_remove("tom", &[1, 2]).unwrap();
+        assert_eq!(execute_plan(&mut app_data, plan, true), EXIT_OK);
+        assert_eq!(serde_json::to_value(&app_data.task_lists).unwrap(), before);
+        assert!(!config.data_file("tasks", "json").exists());
+
+        let plan = app_data.plan_remove("tom", &[1, 2]).unwrap();
+        assert_eq!(execute_plan(&mut app_data, plan, false), EXIT_OK);
+        let ids = |app_data: &AppData| -> Vec<u32> { app_data.tasks("tom").iter().map(|task| task.id).collect() };
+        assert_eq!(ids(&app_data), [3, 4, 5]);
+        let trash: Vec<u32> = app_data.task_lists[0].trash.iter().map(|task| task.id).collect();
+        assert_eq!(trash, [1, 2]);
+        // Executing also saves.
+        assert_eq!(ids(&AppData::load_with_config(config, true).unwrap()), [3, 4, 5]);
+
+        let plan = app_data.plan_clear_completed("tom");
+        assert!(plan.is_empty());
+        assert_eq!(execute_plan(&mut app_data, plan, false), EXIT_NOTHING_TO_DO);
+    }
+
+    #[test]
+    fn daily_capacity_sums_points_per_day_and_finds_the_next_day_with_room() {
+        let mut app_data = AppData::new();
+        app_data.config = Config::builder().with_daily_capacity(8).with_default_task_points(2).build().unwrap();
+        let day = |day: u32| NaiveDate::from_ymd_opt(2024, 10, day).unwrap();
+        let plan = [
+            (Some(day(1)), Some(3), false),
+            (Some(day(1)), None, false),
+            (Some(day(1)), Some(10), true),
+            (Some(day(2)), Some(8), false),
+            (Some(day(3)), Some(5), false),
+            (Some(day(3)), Some(4), false),
+            (None, Some(20), false),
+        ];
+        for (i, (due_date, points, completed)) in plan.into_iter().enumerate() {
+            app_data.add_task_force("uli", format!("Task {}", i + 1)).unwrap();
+            let task = app_data.tasks_mut("uli").last_mut().unwrap();
+            (task.due_date, task.estimated_story_points, task.completed) = (due_date, points, completed);
+        }
+
+        // Unestimated tasks count the default; completed and undated ones don't count.
+        let load = app_data.points_by_day(app_data.tasks("uli"));
+        assert_eq!(load, BTreeMap::from([(day(1), 5), (day(2), 8), (day(3), 9)]));
+        // Exactly at capacity is still fine.
+        assert_eq!(app_data.overbooked_days("uli"), [(day(3), 9)]);
+
+        // 5 + 3 fills the 1st exactly; 4 more points don't fit until the 4th.
+        let before = day(1).pred_opt().unwrap();
+        assert_eq!(app_data.next_free_day(&load, before, 3), day(1));
+        assert_eq!(app_data.next_free_day(&load, before, 4), day(4));
+        assert_eq!(app_data.next_free_day(&load, day(1), 0), day(2));
+        // Too big for any day: the next day with nothing due.
+        assert_eq!(app_data.next_free_day(&load, before, 20), day(4));
+        // Postponing leaves the task's own points out of the load.
+        assert_eq!(app_data.postpone_date("uli", 6, day(1)), Some(day(4)));
+        assert_eq!(app_data.postpone_date("uli", 1, before), Some(day(4)));
+
+        app_data.config = Config::builder().with_daily_capacity(0).build().unwrap();
+        assert!(app_data.overbooked_days("uli").is_empty());
+        assert_eq!(app_data.next_free_day(&load, day(1), 100), day(2));
+    }
+
+    #[test]
+    fn ops_reach_the_journal_before_they_are_applied() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = Config::builder().with_cost(4).with_data_dir(dir.path().to_path_buf());
+        let config = builder.with_journal_fsync(false).build().unwrap();
+        let journal_path = config.data_file("journal", "jsonl");
+        let mut app_data = AppData::load_with_config(config.clone(), true).unwrap();
+        app_data.execute("vi", Op::AddTask { description: "Saved".to_string() }).unwrap();
+        app_data.save().unwrap();
+
+        // Killed after the journal write but before the op was applied or
+        // saved: the next start still has it.
+        let entry = JournalEntry {
+            op: Op::AddTask { description: "Journaled, never applied".to_string() },
+            user: "vi".to_string(),
+            timestamp: Utc::now() + TimeDelta::seconds(1),
+        };
+        app_data.journal.as_ref().unwrap().append(&entry).unwrap();
+        std::mem::forget(app_data);
+        let mut app_data = AppData::load_with_config(config.clone(), true).unwrap();
+        let descriptions = |app_data: &AppData| -> Vec<String> {
+            app_data.tasks("vi").iter().map(|task| task.description.clone()).collect()
+        };
+        assert_eq!(descriptions(&app_data), ["Saved", "Journaled, never applied"]);
+
+        // An op that can't be journaled isn't applied either.
+        fs::remove_file(&journal_path).unwrap();
+        fs::create_dir(&journal_path).unwrap();
+        assert!(matches!(
+            app_data.execute("vi", Op::AddTask { description: "Unrecorded".to_string() }),
+            Err(AppError::Io(_))
+        ));
+        assert_eq!(descriptions(&app_data), ["Saved", "Journaled, never applied"]);
     }
 }
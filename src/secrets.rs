@@ -0,0 +1,257 @@
+//! Secrets such as API tokens and passwords, kept out of config.json.
+//!
+//! With the "keyring" feature they go to the OS keyring when one is
+//! available. Otherwise, e.g. on a headless server, they are stored in
+//! `secrets.enc` in the data directory, encrypted with ChaCha20-Poly1305
+//! under a random key kept next to it in `secrets.key`. Both files are
+//! readable by their owner only. Whoever can read both can decrypt the
+//! secrets, so keep `secrets.key` out of backups that leave the machine.
+
+use crate::AppError;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Key for the HMAC signature of webhook requests.
+#[cfg(feature = "webhooks")]
+pub const WEBHOOK_SECRET: &str = "webhook-secret";
+/// Basic auth password for the CalDAV server.
+#[cfg(feature = "caldav")]
+pub const CALDAV_PASSWORD: &str = "caldav-password";
+/// Access token for `import-github`.
+#[cfg(feature = "import")]
+pub const GITHUB_TOKEN: &str = "github-token";
+
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "Lab_3";
+const NONCE_LEN: usize = 12;
+
+/// Where a secret ended up.
+#[derive(Debug, PartialEq)]
+pub enum Store {
+    #[cfg_attr(not(feature = "keyring"), allow(dead_code))]
+    Keyring,
+    File(PathBuf),
+}
+
+/// The encrypted fallback store in `data_dir`.
+pub fn file_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("secrets.enc")
+}
+
+/// The key the fallback store is encrypted with.
+pub fn key_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("secrets.key")
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_entry(name: &str) -> Option<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, name).ok()
+}
+
+/// Looks a secret up in the keyring, then in the fallback file, since it
+/// may have been set while the keyring was unavailable.
+#[cfg_attr(not(any(feature = "import", feature = "webhooks", feature = "caldav")), allow(dead_code))]
+pub fn get(data_dir: &Path, name: &str) -> Result<Option<String>, AppError> {
+    #[cfg(feature = "keyring")]
+    if let Some(Ok(value)) = keyring_entry(name).map(|entry| entry.get_password()) {
+        return Ok(Some(value));
+    }
+    Ok(read_file(data_dir)?.remove(name))
+}
+
+/// Stores a secret in the keyring if possible, otherwise in the fallback
+/// file. Any copy left in the file from before is removed once the keyring
+/// has it.
+pub fn set(data_dir: &Path, name: &str, value: &str) -> Result<Store, AppError> {
+    #[cfg(feature = "keyring")]
+    if let Some(Ok(())) = keyring_entry(name).map(|entry| entry.set_password(value)) {
+        let mut secrets = read_file(data_dir)?;
+        if secrets.remove(name).is_some() {
+            write_file(data_dir, &secrets)?;
+        }
+        return Ok(Store::Keyring);
+    }
+
+    let mut secrets = read_file(data_dir)?;
+    secrets.insert(name.to_string(), value.to_string());
+    write_file(data_dir, &secrets)?;
+    Ok(Store::File(file_path(data_dir)))
+}
+
+/// Removes a secret from both stores. Returns whether it existed.
+pub fn delete(data_dir: &Path, name: &str) -> Result<bool, AppError> {
+    #[allow(unused_mut)]
+    let mut deleted = false;
+    #[cfg(feature = "keyring")]
+    if let Some(Ok(())) = keyring_entry(name).map(|entry| entry.delete_credential()) {
+        deleted = true;
+    }
+
+    let mut secrets = read_file(data_dir)?;
+    if secrets.remove(name).is_some() {
+        write_file(data_dir, &secrets)?;
+        deleted = true;
+    }
+    Ok(deleted)
+}
+
+/// Decrypts the fallback file; a missing file holds no secrets.
+fn read_file(data_dir: &Path) -> Result<BTreeMap<String, String>, AppError> {
+    let path = file_path(data_dir);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let unreadable = || {
+        AppError::Secret(format!("{} can't be decrypted with {}", path.display(), key_path(data_dir).display()))
+    };
+    let bytes = fs::read(&path)?;
+    if bytes.len() < NONCE_LEN {
+        return Err(unreadable());
+    }
+    let key = fs::read(key_path(data_dir))?;
+    if key.len() != 32 {
+        return Err(unreadable());
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let plaintext = ChaCha20Poly1305::new(Key::from_slice(&key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| unreadable())?;
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+/// Encrypts `secrets` into the fallback file as nonce followed by
+/// ciphertext, creating the key on first use.
+fn write_file(data_dir: &Path, secrets: &BTreeMap<String, String>) -> Result<(), AppError> {
+    let key = load_or_create_key(data_dir)?;
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(&key)
+        .encrypt(&nonce, serde_json::to_vec(secrets)?.as_slice())
+        .map_err(|_| AppError::Secret("encryption failed".to_string()))?;
+
+    let mut bytes = nonce.to_vec();
+    bytes.extend_from_slice(&ciphertext);
+    write_private(&file_path(data_dir), &bytes)
+}
+
+fn load_or_create_key(data_dir: &Path) -> Result<Key, AppError> {
+    let path = key_path(data_dir);
+    if path.exists() {
+        let key = fs::read(&path)?;
+        if key.len() != 32 {
+            return Err(AppError::Secret(format!("{} is not a 32-byte key", path.display())));
+        }
+        return Ok(*Key::from_slice(&key));
+    }
+
+    let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+    write_private(&path, &key)?;
+    Ok(key)
+}
+
+/// Replaces `path` with `bytes` through a temporary file that only the
+/// owner can read or write (mode 0600 on Unix).
+fn write_private(path: &Path, bytes: &[u8]) -> Result<(), AppError> {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".tmp");
+    let temp_path = PathBuf::from(name);
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // `mode` only applies to new files; a leftover temp file keeps its own.
+        if temp_path.exists() {
+            fs::set_permissions(&temp_path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    let mut file = options.open(&temp_path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fallback_file_round_trips_without_plaintext_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(read_file(dir.path()).unwrap().is_empty());
+
+        let secrets = BTreeMap::from([("caldav-password".to_string(), "hunter2-hunter2".to_string())]);
+        write_file(dir.path(), &secrets).unwrap();
+        assert_eq!(file_path(dir.path()), dir.path().join("secrets.enc"));
+        assert_eq!(fs::read(key_path(dir.path())).unwrap().len(), 32);
+        let bytes = fs::read(file_path(dir.path())).unwrap();
+        assert!(!bytes.windows(7).any(|window| window == b"hunter2"));
+        assert_eq!(read_file(dir.path()).unwrap(), secrets);
+
+        // A fresh nonce per write, under the same key.
+        let key = fs::read(key_path(dir.path())).unwrap();
+        write_file(dir.path(), &secrets).unwrap();
+        assert_ne!(fs::read(file_path(dir.path())).unwrap(), bytes);
+        assert_eq!(fs::read(key_path(dir.path())).unwrap(), key);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fallback_files_are_readable_by_their_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        // A leftover temp file with looser permissions mustn't leak them.
+        let mut leftover = file_path(dir.path()).into_os_string();
+        leftover.push(".tmp");
+        fs::write(&leftover, b"").unwrap();
+        fs::set_permissions(&leftover, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_file(dir.path(), &BTreeMap::from([("name".to_string(), "value".to_string())])).unwrap();
+        for path in [file_path(dir.path()), key_path(dir.path())] {
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600, "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn tampered_file_or_wrong_key_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), &BTreeMap::from([("name".to_string(), "value".to_string())])).unwrap();
+
+        let path = file_path(dir.path());
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+        fs::write(&path, &bytes).unwrap();
+        assert!(matches!(read_file(dir.path()), Err(AppError::Secret(_))));
+
+        bytes[last] ^= 0x01;
+        fs::write(&path, &bytes).unwrap();
+        fs::write(key_path(dir.path()), [7u8; 32]).unwrap();
+        assert!(matches!(read_file(dir.path()), Err(AppError::Secret(_))));
+        fs::write(key_path(dir.path()), [7u8; 16]).unwrap();
+        assert!(matches!(read_file(dir.path()), Err(AppError::Secret(_))));
+    }
+
+    // With the keyring feature these would go to the real OS keyring.
+    #[cfg(not(feature = "keyring"))]
+    #[test]
+    fn set_get_and_delete_use_the_fallback_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(set(dir.path(), "token", "abc").unwrap(), Store::File(file_path(dir.path())));
+        set(dir.path(), "other", "xyz").unwrap();
+        assert_eq!(get(dir.path(), "token").unwrap().as_deref(), Some("abc"));
+
+        assert!(delete(dir.path(), "token").unwrap());
+        assert!(!delete(dir.path(), "token").unwrap());
+        assert_eq!(get(dir.path(), "token").unwrap(), None);
+        assert_eq!(get(dir.path(), "other").unwrap().as_deref(), Some("xyz"));
+    }
+}